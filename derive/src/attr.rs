@@ -0,0 +1,195 @@
+use syn::{
+  parse::{
+    Parse,
+    ParseStream,
+  },
+  punctuated::Punctuated,
+  spanned::Spanned,
+  Attribute,
+  Error,
+  Expr,
+  ExprLit,
+  Ident,
+  Lit,
+  LitStr,
+  Token,
+};
+
+/// A single `name` or `name = value` entry of an `#[ipld(...)]` attribute.
+struct Entry {
+  name: Ident,
+  value: Option<Expr>,
+}
+
+impl Parse for Entry {
+  fn parse(input: ParseStream) -> syn::Result<Self> {
+    let name = input.parse()?;
+    let value = if input.peek(Token![=]) {
+      input.parse::<Token![=]>()?;
+      Some(input.parse()?)
+    }
+    else {
+      None
+    };
+    Ok(Self { name, value })
+  }
+}
+
+/// Collects the entries of every `#[ipld(...)]` attribute in `attrs`.
+fn entries(attrs: &[Attribute]) -> syn::Result<Vec<Entry>> {
+  let mut entries = Vec::new();
+  for attr in attrs.iter().filter(|attr| attr.path.is_ident("ipld")) {
+    let parsed = attr
+      .parse_args_with(Punctuated::<Entry, Token![,]>::parse_terminated)?;
+    entries.extend(parsed);
+  }
+  Ok(entries)
+}
+
+fn lit_str(entry: &Entry) -> syn::Result<LitStr> {
+  match &entry.value {
+    Some(Expr::Lit(ExprLit { lit: Lit::Str(lit), .. })) => Ok(lit.clone()),
+    _ => Err(Error::new(
+      entry.name.span(),
+      format!("expected `{} = \"...\"`", entry.name),
+    )),
+  }
+}
+
+fn flag(entry: &Entry) -> syn::Result<()> {
+  match &entry.value {
+    None => Ok(()),
+    Some(value) => Err(Error::new(
+      value.span(),
+      format!("`{}` does not take a value", entry.name),
+    )),
+  }
+}
+
+fn unknown(entry: &Entry) -> Error {
+  Error::new(
+    entry.name.span(),
+    format!("unknown ipld attribute `{}`", entry.name),
+  )
+}
+
+/// The IPLD schema representation of a struct or enum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repr {
+  /// A struct encoded as a map from field names to values.
+  Map,
+  /// A struct encoded as a list of field values.
+  Tuple,
+  /// An enum encoded as a single entry map from variant name to value.
+  Keyed,
+  /// An enum encoded as the value of its variant, distinguished by kind.
+  Kinded,
+  /// A unit enum encoded as the integer discriminant of its variant.
+  Int,
+  /// A unit enum encoded as the name of its variant.
+  String,
+}
+
+/// Attributes on the type being derived.
+#[derive(Default)]
+pub struct ContainerAttrs {
+  pub repr: Option<Repr>,
+}
+
+impl ContainerAttrs {
+  pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+    let mut result = Self::default();
+    for entry in entries(attrs)? {
+      if entry.name == "repr" {
+        let lit = lit_str(&entry)?;
+        result.repr = Some(match lit.value().as_str() {
+          "map" => Repr::Map,
+          "tuple" => Repr::Tuple,
+          "keyed" => Repr::Keyed,
+          "kinded" => Repr::Kinded,
+          "int" => Repr::Int,
+          "string" => Repr::String,
+          repr => {
+            return Err(Error::new(
+              lit.span(),
+              format!("unknown ipld representation `{}`", repr),
+            ));
+          }
+        });
+      }
+      else {
+        return Err(unknown(&entry));
+      }
+    }
+    Ok(result)
+  }
+}
+
+/// How a missing map entry is decoded.
+pub enum FieldDefault {
+  /// The entry is required.
+  None,
+  /// The entry defaults to `Default::default()`.
+  Trait,
+  /// The entry defaults to the given expression.
+  Expr(Box<Expr>),
+}
+
+/// Attributes on a struct or variant field.
+pub struct FieldAttrs {
+  pub rename: Option<LitStr>,
+  pub optional: bool,
+  pub default: FieldDefault,
+}
+
+impl FieldAttrs {
+  pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+    let mut result =
+      Self { rename: None, optional: false, default: FieldDefault::None };
+    for entry in entries(attrs)? {
+      if entry.name == "rename" {
+        result.rename = Some(lit_str(&entry)?);
+      }
+      else if entry.name == "optional" {
+        flag(&entry)?;
+        result.optional = true;
+      }
+      else if entry.name == "default" {
+        result.default = match entry.value {
+          Some(value) => FieldDefault::Expr(Box::new(value)),
+          None => FieldDefault::Trait,
+        };
+      }
+      else {
+        return Err(unknown(&entry));
+      }
+    }
+    if result.optional && !matches!(result.default, FieldDefault::None) {
+      return Err(Error::new(
+        attrs[0].span(),
+        "a field cannot be both `optional` and have a `default`",
+      ));
+    }
+    Ok(result)
+  }
+}
+
+/// Attributes on an enum variant.
+pub struct VariantAttrs {
+  pub rename: Option<LitStr>,
+}
+
+impl VariantAttrs {
+  pub fn parse(attrs: &[Attribute]) -> syn::Result<Self> {
+    let mut result = Self { rename: None };
+    for entry in entries(attrs)? {
+      if entry.name == "rename" {
+        result.rename = Some(lit_str(&entry)?);
+      }
+      else {
+        return Err(unknown(&entry));
+      }
+    }
+    Ok(result)
+  }
+}
@@ -0,0 +1,774 @@
+use crate::attr::{
+  ContainerAttrs,
+  FieldAttrs,
+  FieldDefault,
+  Repr,
+  VariantAttrs,
+};
+use proc_macro2::{
+  Literal,
+  TokenStream,
+};
+use quote::{
+  format_ident,
+  quote,
+};
+use syn::{
+  ext::IdentExt,
+  spanned::Spanned,
+  Data,
+  DataEnum,
+  DeriveInput,
+  Error,
+  Expr,
+  ExprLit,
+  ExprUnary,
+  Fields,
+  Ident,
+  Lit,
+  Member,
+  Type,
+  UnOp,
+};
+
+/// A field of a struct or enum variant.
+struct Field {
+  member: Member,
+  /// The variable the field is bound to when matching on a variant, or
+  /// decoded into.
+  binding: Ident,
+  key: String,
+  ty: Type,
+  attrs: FieldAttrs,
+}
+
+impl Field {
+  fn collect(fields: &Fields) -> syn::Result<Vec<Self>> {
+    fields
+      .iter()
+      .enumerate()
+      .map(|(i, field)| {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        let (member, name) = match &field.ident {
+          Some(ident) => {
+            (Member::Named(ident.clone()), ident.unraw().to_string())
+          }
+          None => (Member::Unnamed(i.into()), i.to_string()),
+        };
+        let key = attrs.rename.as_ref().map_or(name, |rename| rename.value());
+        Ok(Self {
+          member,
+          binding: format_ident!("__field{}", i),
+          key,
+          ty: field.ty.clone(),
+          attrs,
+        })
+      })
+      .collect()
+  }
+
+  /// Returns the condition under which the field is written to a map, given
+  /// an expression `value` referencing it, or `None` if it always is.
+  fn present(&self, value: &TokenStream) -> Option<TokenStream> {
+    let ty = &self.ty;
+    if self.attrs.optional {
+      return Some(quote!(::core::option::Option::is_some(#value)));
+    }
+    match &self.attrs.default {
+      FieldDefault::None => None,
+      FieldDefault::Trait => {
+        Some(quote!(#value != &<#ty as ::core::default::Default>::default()))
+      }
+      FieldDefault::Expr(expr) => Some(quote!(#value != &(#expr))),
+    }
+  }
+
+  /// Returns the value of the field once its map entry has been decoded into
+  /// `self.binding`.
+  fn finish(&self) -> TokenStream {
+    let binding = &self.binding;
+    let key = &self.key;
+    if self.attrs.optional {
+      return quote!(#binding.unwrap_or_default());
+    }
+    match &self.attrs.default {
+      FieldDefault::None => {
+        let missing =
+          fail(quote!(MissingKey(::sp_ipld::__private::String::from(#key))));
+        quote! {
+          match #binding {
+            ::core::option::Option::Some(value) => value,
+            ::core::option::Option::None => return #missing,
+          }
+        }
+      }
+      FieldDefault::Trait => quote!(#binding.unwrap_or_default()),
+      FieldDefault::Expr(expr) => quote!(#binding.unwrap_or_else(|| #expr)),
+    }
+  }
+
+  fn check_positional(&self) -> syn::Result<()> {
+    let attrs = &self.attrs;
+    if attrs.rename.is_some()
+      || attrs.optional
+      || !matches!(attrs.default, FieldDefault::None)
+    {
+      return Err(Error::new(
+        self.ty.span(),
+        "`rename`, `optional` and `default` require the map representation",
+      ));
+    }
+    Ok(())
+  }
+}
+
+/// Returns an expression failing to decode with the `ErrorKind` variant
+/// `kind`, at the current position of `r`.
+fn fail(kind: TokenStream) -> TokenStream {
+  quote! {
+    ::core::result::Result::Err(
+      ::sp_ipld::codec::Error::new(::sp_ipld::codec::ErrorKind::#kind)
+        .at(r.position()),
+    )
+  }
+}
+
+/// How the fields of a struct or enum variant are laid out.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+  /// A map from field keys to values.
+  Map,
+  /// A list of field values.
+  Tuple,
+  /// The value of the only field.
+  Newtype,
+  /// Null.
+  Unit,
+}
+
+/// Returns statements writing `fields` with the given `shape`, where `access`
+/// returns a reference to a field.
+fn encode_fields(
+  shape: Shape,
+  fields: &[Field],
+  access: &dyn Fn(&Field) -> TokenStream,
+) -> TokenStream {
+  match shape {
+    Shape::Map => {
+      let mut sorted: Vec<&Field> = fields.iter().collect();
+      // dag-cbor sorts map keys by length first, then bytewise
+      sorted.sort_by(|a, b| (a.key.len(), &a.key).cmp(&(b.key.len(), &b.key)));
+      let required = sorted
+        .iter()
+        .filter(|f| f.present(&access(f)).is_none())
+        .count() as u64;
+      let counts: Vec<TokenStream> = sorted
+        .iter()
+        .filter_map(|f| f.present(&access(f)))
+        .map(|present| quote!(if #present { len += 1; }))
+        .collect();
+      let len = if counts.is_empty() {
+        quote!(let len: u64 = #required;)
+      }
+      else {
+        quote!(let mut len: u64 = #required; #(#counts)*)
+      };
+      let entries = sorted.iter().map(|f| {
+        let key = &f.key;
+        let value = access(f);
+        let write = quote! {
+          <str as ::sp_ipld::codec::Encode<
+            ::sp_ipld::dag_cbor::DagCborCodec,
+          >>::encode(#key, c, w)?;
+          ::sp_ipld::codec::Encode::encode(#value, c, w)?;
+        };
+        match f.present(&value) {
+          Some(present) => quote!(if #present { #write }),
+          None => write,
+        }
+      });
+      quote! {
+        #len
+        ::sp_ipld::__private::write_u64(w, 5, len)?;
+        #(#entries)*
+      }
+    }
+    Shape::Tuple => {
+      let len = fields.len() as u64;
+      let values = fields.iter().map(access);
+      quote! {
+        ::sp_ipld::__private::write_u64(w, 4, #len)?;
+        #(::sp_ipld::codec::Encode::encode(#values, c, w)?;)*
+      }
+    }
+    Shape::Newtype => {
+      let value = access(&fields[0]);
+      quote!(::sp_ipld::codec::Encode::encode(#value, c, w)?;)
+    }
+    Shape::Unit => quote!(::sp_ipld::__private::write_null(w)?;),
+  }
+}
+
+/// Returns an expression computing the encoded length of `fields` with the
+/// given `shape`, where `access` returns a reference to a field.
+fn encoded_len_fields(
+  shape: Shape,
+  fields: &[Field],
+  access: &dyn Fn(&Field) -> TokenStream,
+) -> TokenStream {
+  match shape {
+    Shape::Map => {
+      let entries = fields.iter().map(|f| {
+        let key = &f.key;
+        let value = access(f);
+        let add = quote! {
+          entries += 1;
+          len += <str as ::sp_ipld::codec::EncodedLen<
+            ::sp_ipld::dag_cbor::DagCborCodec,
+          >>::encoded_len(#key, c)?;
+          len += ::sp_ipld::codec::EncodedLen::encoded_len(#value, c)?;
+        };
+        match f.present(&value) {
+          Some(present) => quote!(if #present { #add }),
+          None => add,
+        }
+      });
+      quote! {{
+        let mut entries: u64 = 0;
+        let mut len: usize = 0;
+        #(#entries)*
+        ::sp_ipld::__private::header_len(entries) + len
+      }}
+    }
+    Shape::Tuple => {
+      let len = fields.len() as u64;
+      let values = fields.iter().map(access);
+      quote! {
+        ::sp_ipld::__private::header_len(#len)
+          #(+ ::sp_ipld::codec::EncodedLen::encoded_len(#values, c)?)*
+      }
+    }
+    Shape::Newtype => {
+      let value = access(&fields[0]);
+      quote!(::sp_ipld::codec::EncodedLen::encoded_len(#value, c)?)
+    }
+    Shape::Unit => quote!(1),
+  }
+}
+
+/// Returns an expression reading `fields` with the given `shape` and building
+/// them with the struct or variant path `ctor`.
+fn decode_fields(
+  shape: Shape,
+  fields: &[Field],
+  ctor: &TokenStream,
+) -> TokenStream {
+  let members: Vec<&Member> = fields.iter().map(|f| &f.member).collect();
+  let bindings: Vec<&Ident> = fields.iter().map(|f| &f.binding).collect();
+  match shape {
+    Shape::Map => {
+      let types = fields.iter().map(|f| &f.ty);
+      let keys: Vec<&String> = fields.iter().map(|f| &f.key).collect();
+      let values = fields.iter().map(Field::finish);
+      let duplicate = fail(quote!(DuplicateKey(key)));
+      let unknown = fail(quote!(UnexpectedKey(key)));
+      quote! {{
+        let mut len = ::sp_ipld::__private::read_map_len(r)?;
+        #(
+          let mut #bindings: ::core::option::Option<#types> =
+            ::core::option::Option::None;
+        )*
+        while ::sp_ipld::__private::has_next(r, &mut len)? {
+          let key = <::sp_ipld::__private::String as ::sp_ipld::codec::Decode<
+            ::sp_ipld::dag_cbor::DagCborCodec,
+          >>::decode_limited(c, r, budget)?;
+          match key.as_str() {
+            #(
+              #keys => {
+                if #bindings.is_some() {
+                  return #duplicate;
+                }
+                #bindings = ::core::option::Option::Some(
+                  ::sp_ipld::codec::Decode::decode_limited(c, r, budget)
+                    .map_err(|e| e.within(#keys))?,
+                );
+              }
+            )*
+            _ => return #unknown,
+          }
+        }
+        #ctor { #(#members: #values),* }
+      }}
+    }
+    Shape::Tuple => {
+      let len = fields.len();
+      let wrong_len = fail(quote!(WrongLength(#len)));
+      let missing = fields.iter().map(|_| &wrong_len);
+      let segments = (0..len).map(|i| i.to_string());
+      let too_many = &wrong_len;
+      quote! {{
+        let mut len = ::sp_ipld::__private::read_list_len(r)?;
+        #(
+          if !::sp_ipld::__private::has_next(r, &mut len)? {
+            return #missing;
+          }
+          let #bindings =
+            ::sp_ipld::codec::Decode::decode_limited(c, r, budget)
+              .map_err(|e| e.within(#segments))?;
+        )*
+        if ::sp_ipld::__private::has_next(r, &mut len)? {
+          return #too_many;
+        }
+        #ctor { #(#members: #bindings),* }
+      }}
+    }
+    Shape::Newtype => quote! {
+      #ctor { 0: ::sp_ipld::codec::Decode::decode_limited(c, r, budget)? }
+    },
+    Shape::Unit => quote! {{
+      ::sp_ipld::__private::read_null(r)?;
+      #ctor {}
+    }},
+  }
+}
+
+/// Returns the shape of fields of an enum variant encoded as a value.
+fn variant_shape(fields: &Fields) -> Shape {
+  match fields {
+    Fields::Named(_) => Shape::Map,
+    Fields::Unnamed(fields) if fields.unnamed.len() == 1 => Shape::Newtype,
+    Fields::Unnamed(_) => Shape::Tuple,
+    Fields::Unit => Shape::Unit,
+  }
+}
+
+/// Returns the bodies of the `encode`, `encoded_len` and `decode` methods.
+type Bodies = (TokenStream, TokenStream, TokenStream);
+
+fn expand_struct(
+  input: &DeriveInput,
+  repr: Option<Repr>,
+  fields: &Fields,
+) -> syn::Result<Bodies> {
+  let shape = match (fields, repr) {
+    (Fields::Named(_), None) | (Fields::Named(_), Some(Repr::Map)) => {
+      Shape::Map
+    }
+    (Fields::Named(_), Some(Repr::Tuple))
+    | (Fields::Unnamed(_), None)
+    | (Fields::Unnamed(_), Some(Repr::Tuple)) => Shape::Tuple,
+    (Fields::Unit, None) => Shape::Unit,
+    (Fields::Unnamed(_), Some(Repr::Map)) => {
+      return Err(Error::new(
+        input.ident.span(),
+        "the map representation requires named fields",
+      ));
+    }
+    _ => {
+      return Err(Error::new(
+        input.ident.span(),
+        "structs support the `map` and `tuple` representations",
+      ));
+    }
+  };
+  let fields = Field::collect(fields)?;
+  if shape == Shape::Tuple {
+    fields.iter().try_for_each(Field::check_positional)?;
+  }
+  let encode = encode_fields(shape, &fields, &|f| {
+    let member = &f.member;
+    quote!(&self.#member)
+  });
+  let encoded_len = encoded_len_fields(shape, &fields, &|f| {
+    let member = &f.member;
+    quote!(&self.#member)
+  });
+  let decode = decode_fields(shape, &fields, &quote!(Self));
+  Ok((
+    quote! {
+      #encode
+      ::core::result::Result::Ok(())
+    },
+    quote!(::core::result::Result::Ok(#encoded_len)),
+    quote!(::core::result::Result::Ok(#decode)),
+  ))
+}
+
+/// A variant of an enum.
+struct Variant {
+  ident: Ident,
+  name: String,
+  shape: Shape,
+  fields: Vec<Field>,
+}
+
+impl Variant {
+  fn collect(data: &DataEnum) -> syn::Result<Vec<Self>> {
+    data
+      .variants
+      .iter()
+      .map(|variant| {
+        let attrs = VariantAttrs::parse(&variant.attrs)?;
+        let fields = Field::collect(&variant.fields)?;
+        let shape = variant_shape(&variant.fields);
+        if shape != Shape::Map {
+          fields.iter().try_for_each(Field::check_positional)?;
+        }
+        Ok(Self {
+          ident: variant.ident.clone(),
+          name: attrs
+            .rename
+            .map_or_else(|| variant.ident.unraw().to_string(), |r| r.value()),
+          shape,
+          fields,
+        })
+      })
+      .collect()
+  }
+
+  fn pattern(&self) -> TokenStream {
+    let ident = &self.ident;
+    let members = self.fields.iter().map(|f| &f.member);
+    let bindings = self.fields.iter().map(|f| &f.binding);
+    quote!(Self::#ident { #(#members: #bindings),* })
+  }
+
+  fn ctor(&self) -> TokenStream {
+    let ident = &self.ident;
+    quote!(Self::#ident)
+  }
+
+  fn encode(&self) -> TokenStream {
+    encode_fields(self.shape, &self.fields, &|f| {
+      let binding = &f.binding;
+      quote!(#binding)
+    })
+  }
+
+  fn encoded_len(&self) -> TokenStream {
+    encoded_len_fields(self.shape, &self.fields, &|f| {
+      let binding = &f.binding;
+      quote!(#binding)
+    })
+  }
+
+  fn decode(&self) -> TokenStream {
+    decode_fields(self.shape, &self.fields, &self.ctor())
+  }
+}
+
+/// Returns the discriminants of the variants of a unit enum.
+fn discriminants(data: &DataEnum) -> syn::Result<Vec<i128>> {
+  let mut next = 0i128;
+  let mut result = Vec::new();
+  for variant in &data.variants {
+    if let Some((_, expr)) = &variant.discriminant {
+      next = match expr {
+        Expr::Lit(ExprLit { lit: Lit::Int(int), .. }) => int.base10_parse()?,
+        Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr, .. }) => match &**expr {
+          Expr::Lit(ExprLit { lit: Lit::Int(int), .. }) => {
+            -int.base10_parse::<i128>()?
+          }
+          _ => return Err(Error::new(expr.span(), "expected an integer")),
+        },
+        _ => return Err(Error::new(expr.span(), "expected an integer")),
+      };
+    }
+    result.push(next);
+    next += 1;
+  }
+  Ok(result)
+}
+
+/// Returns the tokens of an integer literal, which may be negative.
+fn int_literal(int: i128) -> TokenStream {
+  let abs = Literal::u128_unsuffixed(int.unsigned_abs());
+  if int < 0 { quote!(-#abs) } else { quote!(#abs) }
+}
+
+fn expand_enum(
+  input: &DeriveInput,
+  repr: Option<Repr>,
+  data: &DataEnum,
+) -> syn::Result<Bodies> {
+  let variants = Variant::collect(data)?;
+  let repr = repr.unwrap_or(Repr::Keyed);
+  if variants.is_empty() {
+    return Ok((
+      quote!(match *self {}),
+      quote!(match *self {}),
+      fail(quote!(NoMatchingVariant)),
+    ));
+  }
+  let patterns: Vec<TokenStream> =
+    variants.iter().map(Variant::pattern).collect();
+  let ctors: Vec<TokenStream> = variants.iter().map(Variant::ctor).collect();
+  let names: Vec<&String> = variants.iter().map(|v| &v.name).collect();
+  match repr {
+    Repr::Keyed => {
+      let encodes = variants.iter().map(Variant::encode);
+      let lens = variants.iter().map(Variant::encoded_len);
+      let decodes = variants.iter().map(Variant::decode);
+      let single = fail(quote!(WrongLength(1)));
+      let unknown = fail(quote!(UnknownVariant(key)));
+      Ok((
+        quote! {
+          match self {
+            #(
+              #patterns => {
+                ::sp_ipld::__private::write_u64(w, 5, 1)?;
+                <str as ::sp_ipld::codec::Encode<
+                  ::sp_ipld::dag_cbor::DagCborCodec,
+                >>::encode(#names, c, w)?;
+                #encodes
+              }
+            )*
+          }
+          ::core::result::Result::Ok(())
+        },
+        quote! {
+          ::core::result::Result::Ok(match self {
+            #(
+              #patterns => {
+                ::sp_ipld::__private::header_len(1)
+                  + <str as ::sp_ipld::codec::EncodedLen<
+                    ::sp_ipld::dag_cbor::DagCborCodec,
+                  >>::encoded_len(#names, c)?
+                  + #lens
+              }
+            )*
+          })
+        },
+        quote! {
+          let mut len = ::sp_ipld::__private::read_map_len(r)?;
+          if !::sp_ipld::__private::has_next(r, &mut len)? {
+            return #single;
+          }
+          let key = <::sp_ipld::__private::String as ::sp_ipld::codec::Decode<
+            ::sp_ipld::dag_cbor::DagCborCodec,
+          >>::decode_limited(c, r, budget)?;
+          let value = match key.as_str() {
+            #(#names => #decodes,)*
+            _ => return #unknown,
+          };
+          if ::sp_ipld::__private::has_next(r, &mut len)? {
+            return #single;
+          }
+          ::core::result::Result::Ok(value)
+        },
+      ))
+    }
+    Repr::Kinded => {
+      if let Some(v) = variants
+        .iter()
+        .find(|v| !matches!(v.shape, Shape::Newtype | Shape::Unit))
+      {
+        return Err(Error::new(
+          v.ident.span(),
+          "the kinded representation requires newtype or unit variants",
+        ));
+      }
+      let encodes = variants.iter().map(Variant::encode);
+      let lens = variants.iter().map(Variant::encoded_len);
+      let decodes = variants.iter().map(Variant::decode);
+      let none = fail(quote!(NoMatchingVariant));
+      Ok((
+        quote! {
+          match self {
+            #(#patterns => { #encodes })*
+          }
+          ::core::result::Result::Ok(())
+        },
+        quote! {
+          ::core::result::Result::Ok(match self {
+            #(#patterns => #lens,)*
+          })
+        },
+        quote! {
+          let position = r.position();
+          let saved = ::core::clone::Clone::clone(&*budget);
+          #(
+            let result = (|| -> ::core::result::Result<
+              Self,
+              ::sp_ipld::codec::Error,
+            > { ::core::result::Result::Ok(#decodes) })();
+            match result {
+              ::core::result::Result::Ok(value) => {
+                return ::core::result::Result::Ok(value);
+              }
+              ::core::result::Result::Err(_) => {
+                r.set_position(position);
+                *budget = ::core::clone::Clone::clone(&saved);
+              }
+            }
+          )*
+          #none
+        },
+      ))
+    }
+    Repr::Int | Repr::String => {
+      if let Some(v) = variants.iter().find(|v| v.shape != Shape::Unit) {
+        return Err(Error::new(
+          v.ident.span(),
+          "the int and string representations require unit variants",
+        ));
+      }
+      if repr == Repr::String {
+        let unknown = fail(quote!(UnknownVariant(key)));
+        return Ok((
+          quote! {
+            match self {
+              #(
+                #patterns => <str as ::sp_ipld::codec::Encode<
+                  ::sp_ipld::dag_cbor::DagCborCodec,
+                >>::encode(#names, c, w),
+              )*
+            }
+          },
+          quote! {
+            match self {
+              #(
+                #patterns => <str as ::sp_ipld::codec::EncodedLen<
+                  ::sp_ipld::dag_cbor::DagCborCodec,
+                >>::encoded_len(#names, c),
+              )*
+            }
+          },
+          quote! {
+            let key = <::sp_ipld::__private::String as ::sp_ipld::codec::Decode<
+              ::sp_ipld::dag_cbor::DagCborCodec,
+            >>::decode_limited(c, r, budget)?;
+            match key.as_str() {
+              #(#names => ::core::result::Result::Ok(#ctors {}),)*
+              _ => #unknown,
+            }
+          },
+        ));
+      }
+      let ints: Vec<TokenStream> =
+        discriminants(data)?.into_iter().map(int_literal).collect();
+      let unknown = fail(quote!(UnknownVariant(
+        ::sp_ipld::__private::format!("{}", int)
+      )));
+      Ok((
+        quote! {
+          match self {
+            #(
+              #patterns => <i128 as ::sp_ipld::codec::Encode<
+                ::sp_ipld::dag_cbor::DagCborCodec,
+              >>::encode(&(#ints), c, w),
+            )*
+          }
+        },
+        quote! {
+          match self {
+            #(
+              #patterns => <i128 as ::sp_ipld::codec::EncodedLen<
+                ::sp_ipld::dag_cbor::DagCborCodec,
+              >>::encoded_len(&(#ints), c),
+            )*
+          }
+        },
+        quote! {
+          match ::sp_ipld::__private::read_int(r)? {
+            #(#ints => ::core::result::Result::Ok(#ctors {}),)*
+            int => #unknown,
+          }
+        },
+      ))
+    }
+    Repr::Map | Repr::Tuple => Err(Error::new(
+      input.ident.span(),
+      "enums support the `keyed`, `kinded`, `int` and `string` \
+       representations",
+    )),
+  }
+}
+
+/// Expands `#[derive(DagCbor)]` into implementations of `Encode` and `Decode`
+/// for `DagCborCodec`.
+pub fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+  let attrs = ContainerAttrs::parse(&input.attrs)?;
+  let (encode, encoded_len, decode) = match &input.data {
+    Data::Struct(data) => expand_struct(input, attrs.repr, &data.fields)?,
+    Data::Enum(data) => expand_enum(input, attrs.repr, data)?,
+    Data::Union(_) => {
+      return Err(Error::new(
+        input.ident.span(),
+        "unions cannot derive `DagCbor`",
+      ));
+    }
+  };
+  let ident = &input.ident;
+  let codec = quote!(::sp_ipld::dag_cbor::DagCborCodec);
+  let mut encode_generics = input.generics.clone();
+  let mut len_generics = input.generics.clone();
+  let mut decode_generics = input.generics.clone();
+  for param in input.generics.type_params() {
+    let param = &param.ident;
+    encode_generics
+      .make_where_clause()
+      .predicates
+      .push(syn::parse_quote!(#param: ::sp_ipld::codec::Encode<#codec>));
+    len_generics
+      .make_where_clause()
+      .predicates
+      .push(syn::parse_quote!(#param: ::sp_ipld::codec::EncodedLen<#codec>));
+    decode_generics
+      .make_where_clause()
+      .predicates
+      .push(syn::parse_quote!(#param: ::sp_ipld::codec::Decode<#codec>));
+  }
+  let (impl_generics, ty_generics, where_clause) =
+    encode_generics.split_for_impl();
+  let (len_impl_generics, _, len_where_clause) = len_generics.split_for_impl();
+  let (decode_impl_generics, _, decode_where_clause) =
+    decode_generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::sp_ipld::codec::Encode<#codec>
+      for #ident #ty_generics #where_clause
+    {
+      #[allow(unused_variables)]
+      fn encode<__W: ::sp_ipld::codec::Writer>(
+        &self,
+        c: #codec,
+        w: &mut __W,
+      ) -> ::core::result::Result<(), ::sp_ipld::codec::Error> {
+        #encode
+      }
+    }
+
+    impl #len_impl_generics ::sp_ipld::codec::EncodedLen<#codec>
+      for #ident #ty_generics #len_where_clause
+    {
+      #[allow(unused_variables)]
+      fn encoded_len(
+        &self,
+        c: #codec,
+      ) -> ::core::result::Result<usize, ::sp_ipld::codec::Error> {
+        #encoded_len
+      }
+    }
+
+    impl #decode_impl_generics ::sp_ipld::codec::Decode<#codec>
+      for #ident #ty_generics #decode_where_clause
+    {
+      fn decode(
+        c: #codec,
+        r: &mut ::sp_ipld::codec::SliceCursor,
+      ) -> ::core::result::Result<Self, ::sp_ipld::codec::Error> {
+        let mut budget = ::core::default::Default::default();
+        Self::decode_limited(c, r, &mut budget)
+      }
+
+      #[allow(unused_variables)]
+      fn decode_limited(
+        c: #codec,
+        r: &mut ::sp_ipld::codec::SliceCursor,
+        budget: &mut ::sp_ipld::limits::DecodeBudget,
+      ) -> ::core::result::Result<Self, ::sp_ipld::codec::Error> {
+        #decode
+      }
+    }
+  })
+}
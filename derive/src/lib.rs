@@ -0,0 +1,40 @@
+//! Derive macros for the `sp-ipld` dag-cbor codec.
+
+extern crate proc_macro;
+
+mod attr;
+mod expand;
+
+use proc_macro::TokenStream;
+use syn::{
+  parse_macro_input,
+  DeriveInput,
+};
+
+/// Derives `Encode<DagCborCodec>`, `EncodedLen<DagCborCodec>` and
+/// `Decode<DagCborCodec>` following an IPLD schema representation, chosen with
+/// `#[ipld(repr = "...")]`:
+///
+/// - `map`: the default for structs with named fields, encoded as a map from
+///   field names to values. Unknown or repeated keys fail to decode.
+/// - `tuple`: the default for tuple structs, encoded as a list of field values.
+/// - `keyed`: the default for enums, encoded as a single entry map from the
+///   variant name to its fields. Variants with named fields are encoded as a
+///   map, newtype variants as their value, tuple variants as a list and unit
+///   variants as null.
+/// - `kinded`: for enums of newtype and unit variants, encoded as the value of
+///   the variant. Decoding tries each variant in order.
+/// - `int`: for enums of unit variants, encoded as the discriminant.
+/// - `string`: for enums of unit variants, encoded as the variant name.
+///
+/// Fields and variants can be renamed with `#[ipld(rename = "...")]`. In the
+/// map representation, `#[ipld(optional)]` omits an `Option` field that is
+/// `None`, and `#[ipld(default)]` or `#[ipld(default = expr)]` omits a field
+/// equal to its default. Either may be missing when decoding.
+///
+/// The types of all fields must implement `EncodedLen<DagCborCodec>` as well.
+#[proc_macro_derive(DagCbor, attributes(ipld))]
+pub fn derive_dag_cbor(input: TokenStream) -> TokenStream {
+  let input = parse_macro_input!(input as DeriveInput);
+  expand::derive(&input).unwrap_or_else(|e| e.to_compile_error()).into()
+}
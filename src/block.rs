@@ -0,0 +1,167 @@
+use crate::{
+  codec::{
+    Codec,
+    Decode,
+    Encode,
+    Error,
+    ErrorKind,
+    References,
+    SliceCursor,
+  },
+  ipld::Ipld,
+};
+
+use alloc::vec::Vec;
+use core::{
+  convert::TryFrom,
+  marker::PhantomData,
+};
+use sp_cid::Cid;
+use sp_multihash::{
+  Code,
+  MultihashDigest,
+};
+
+/// The encoded data of a value together with its cid, whose hash was
+/// checked to match the data unless the block was built with
+/// `Block::new_unchecked`.
+///
+/// The codec named in the cid must be supported by `C`, which can be
+/// `IpldCodec` for blocks of any codec of this build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Block<C> {
+  cid: Cid,
+  data: Vec<u8>,
+  codec: PhantomData<C>,
+}
+
+impl<C: Codec> Block<C> {
+  /// Encodes `value` with `codec` and returns it as a block with a CIDv1
+  /// hashed with `hash`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `value` cannot be encoded with `codec`
+  pub fn encode<T: Encode<C> + ?Sized>(
+    codec: C,
+    hash: Code,
+    value: &T,
+  ) -> Result<Self, Error> {
+    let data = codec.encode(value)?.into_inner();
+    let cid = Cid::new_v1(codec.into(), hash.digest(&data));
+    Ok(Self::new_unchecked(cid, data))
+  }
+
+  /// Returns the block of `data` addressed by `cid`, after hashing `data`
+  /// with the hash function of `cid`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the codec or hash function of `cid` is not
+  /// supported, or if the hash of `data` does not match `cid`
+  pub fn new(cid: Cid, data: Vec<u8>) -> Result<Self, Error> {
+    C::try_from(cid.codec())?;
+    let code = cid.hash().code();
+    let hash = Code::try_from(code)
+      .map_err(|_| Error::new(ErrorKind::UnsupportedHash(code)))?;
+    if hash.digest(&data) != *cid.hash() {
+      return Err(Error::new(ErrorKind::HashMismatch));
+    }
+    Ok(Self::new_unchecked(cid, data))
+  }
+
+  /// Returns the block of `data` addressed by `cid`, trusting that they
+  /// match.
+  pub fn new_unchecked(cid: Cid, data: Vec<u8>) -> Self {
+    Self { cid, data, codec: PhantomData }
+  }
+
+  /// Returns the cid of the block.
+  pub fn cid(&self) -> &Cid { &self.cid }
+
+  /// Returns the encoded data of the block.
+  pub fn data(&self) -> &[u8] { &self.data }
+
+  /// Returns the cid and the encoded data of the block.
+  pub fn into_inner(self) -> (Cid, Vec<u8>) { (self.cid, self.data) }
+
+  /// Returns the codec named in the cid.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `C` does not support the codec of the cid
+  pub fn codec(&self) -> Result<C, Error> { Ok(C::try_from(self.cid.codec())?) }
+
+  /// Decodes the data with the codec named in the cid, reading it in place.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the codec is not supported or the data cannot be
+  /// decoded into a `T`
+  pub fn decode<T: Decode<C>>(&self) -> Result<T, Error> {
+    T::decode(self.codec()?, &mut SliceCursor::new(&self.data))
+  }
+
+  /// Extends `set` with the cids the block links to, reading the data in
+  /// place.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the codec is not supported or the data cannot be
+  /// decoded
+  pub fn references<E: Extend<Cid>>(&self, set: &mut E) -> Result<(), Error>
+  where Ipld: References<C> {
+    let mut r = SliceCursor::new(&self.data);
+    <Ipld as References<C>>::references(self.codec()?, &mut r, set)
+  }
+}
+
+impl<C> AsRef<[u8]> for Block<C> {
+  fn as_ref(&self) -> &[u8] { &self.data }
+}
+
+#[cfg(all(test, feature = "dag-cbor", feature = "dag-json"))]
+pub mod tests {
+  use super::*;
+  use crate::{
+    dag_cbor::{
+      self,
+      DagCborCodec,
+    },
+    dag_json::DagJsonCodec,
+    ipld_codec::IpldCodec,
+  };
+
+  #[test]
+  fn block() {
+    let link = dag_cbor::cid(&ipld!("leaf"));
+    let ipld = ipld!({ "a": [1, link], "b": "c" });
+    let block = Block::encode(DagCborCodec, Code::Blake2b256, &ipld).unwrap();
+    assert_eq!(block.cid(), &dag_cbor::cid(&ipld));
+    assert_eq!(block.decode::<Ipld>(), Ok(ipld.clone()));
+    let mut set = Vec::new();
+    block.references(&mut set).unwrap();
+    assert_eq!(set, vec![link]);
+
+    let (cid, data) = block.into_inner();
+    let block = Block::<IpldCodec>::new(cid, data).unwrap();
+    assert_eq!(block.codec(), Ok(IpldCodec::DagCbor));
+    assert_eq!(block.decode::<Ipld>(), Ok(ipld));
+  }
+
+  #[test]
+  fn block_errors() {
+    let block =
+      Block::encode(DagJsonCodec, Code::Sha2_256, &ipld!([1])).unwrap();
+    let (cid, mut data) = block.into_inner();
+    let e = Block::<DagCborCodec>::new(cid, data.clone()).unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::UnsupportedCodec(0x0129));
+    data.push(b' ');
+    let e = Block::<DagJsonCodec>::new(cid, data.clone()).unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::HashMismatch);
+    let block = Block::<DagJsonCodec>::new_unchecked(cid, data);
+    assert_eq!(block.decode::<Ipld>(), Ok(ipld!([1])));
+    let block = Block::<DagCborCodec>::new_unchecked(cid, Vec::new());
+    assert!(block.decode::<Ipld>().is_err());
+  }
+}
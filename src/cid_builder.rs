@@ -0,0 +1,139 @@
+use crate::{
+  codec::{
+    Codec,
+    Error,
+    ErrorKind,
+  },
+  ipld::Ipld,
+  ipld_codec::IpldCodec,
+};
+
+use alloc::string::ToString;
+use sp_cid::{
+  Cid,
+  Version,
+};
+use sp_multihash::{
+  Code,
+  MultihashDigest,
+};
+
+/// Builds the cid of a value from its codec, hash function and cid version.
+///
+/// Defaults to sha2-256 and CIDv1, which is what IPFS uses for dag-cbor and
+/// dag-json blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CidBuilder {
+  codec: IpldCodec,
+  hash: Code,
+  version: Version,
+}
+
+impl CidBuilder {
+  /// Returns a builder for cids of values encoded with `codec`.
+  pub fn new<C: Into<IpldCodec>>(codec: C) -> Self {
+    Self { codec: codec.into(), hash: Code::Sha2_256, version: Version::V1 }
+  }
+
+  /// Sets the codec the value is encoded with.
+  pub fn codec<C: Into<IpldCodec>>(mut self, codec: C) -> Self {
+    self.codec = codec.into();
+    self
+  }
+
+  /// Sets the hash function of the multihash.
+  pub fn hash(mut self, hash: Code) -> Self {
+    self.hash = hash;
+    self
+  }
+
+  /// Sets the cid version. CIDv0 is only valid for dag-pb, so it fails to
+  /// build with any codec supported by this crate.
+  pub fn version(mut self, version: Version) -> Self {
+    self.version = version;
+    self
+  }
+
+  /// Returns the cid of `ipld`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `ipld` cannot be encoded with the codec, or the
+  /// cid version does not allow the codec or hash function
+  pub fn build(&self, ipld: &Ipld) -> Result<Cid, Error> {
+    let bytes = self.codec.encode(ipld)?.into_inner();
+    self.build_from_bytes(&bytes)
+  }
+
+  /// Returns the cid of `bytes`, which are already encoded with the codec.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the cid version does not allow the codec or hash
+  /// function
+  pub fn build_from_bytes(&self, bytes: &[u8]) -> Result<Cid, Error> {
+    let hash = self.hash.digest(bytes);
+    Cid::new(self.version, self.codec.into(), hash)
+      .map_err(|e| Error::new(ErrorKind::InvalidCid(e.to_string())))
+  }
+}
+
+/// Returns the cid of `ipld` encoded with `codec`, hashed with `hash`.
+///
+/// # Errors
+///
+/// Will return `Err` if `ipld` cannot be encoded with `codec`, or `version`
+/// does not allow `codec` or `hash`
+pub fn cid_with<C: Into<IpldCodec>>(
+  ipld: &Ipld,
+  codec: C,
+  hash: Code,
+  version: Version,
+) -> Result<Cid, Error> {
+  CidBuilder::new(codec).hash(hash).version(version).build(ipld)
+}
+
+#[cfg(all(test, feature = "dag-cbor", feature = "dag-json"))]
+pub mod tests {
+  use super::*;
+  use crate::{
+    dag_cbor::{
+      self,
+      DagCborCodec,
+    },
+    dag_json::{
+      self,
+      DagJsonCodec,
+    },
+  };
+
+  #[test]
+  fn builder() {
+    let ipld = ipld!({ "a": [1, 2], "b": "c" });
+    let cid = CidBuilder::new(DagCborCodec).build(&ipld).unwrap();
+    assert_eq!(cid.codec(), 0x71);
+    assert_eq!(cid.version(), Version::V1);
+    assert_eq!(cid.hash().code(), u64::from(Code::Sha2_256));
+    let bytes = DagCborCodec.encode(&ipld).unwrap().into_inner();
+    assert_eq!(cid.hash(), &Code::Sha2_256.digest(&bytes));
+
+    let builder = CidBuilder::new(DagCborCodec).hash(Code::Blake2b256);
+    assert_eq!(builder.build(&ipld), Ok(dag_cbor::cid(&ipld)));
+    let builder = builder.codec(DagJsonCodec);
+    assert_eq!(builder.build(&ipld), Ok(dag_json::cid(&ipld)));
+    let cid = cid_with(&ipld, DagJsonCodec, Code::Sha3_512, Version::V1);
+    assert_eq!(cid.unwrap().hash().code(), u64::from(Code::Sha3_512));
+  }
+
+  #[test]
+  fn builder_errors() {
+    let ipld = ipld!(null);
+    let cid = cid_with(&ipld, DagCborCodec, Code::Sha2_256, Version::V0);
+    assert!(matches!(cid.unwrap_err().kind(), ErrorKind::InvalidCid(_)));
+    #[cfg(feature = "unleashed")]
+    {
+      let ipld = Ipld::Tag(1, alloc::boxed::Box::new(ipld));
+      assert!(CidBuilder::new(DagJsonCodec).build(&ipld).is_err());
+    }
+  }
+}
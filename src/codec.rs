@@ -1,19 +1,376 @@
+use crate::{
+  ipld::IpldKind,
+  limits::{
+    DecodeBudget,
+    DecodeLimits,
+    LimitExceeded,
+  },
+  path::{
+    IpldPath,
+    PathError,
+  },
+};
+
 use bytecursor::ByteCursor;
 use sp_cid::Cid;
 
 use alloc::{
+  boxed::Box,
   string::String,
   vec::Vec
 };
 use core::{
   convert::TryFrom,
+  fmt,
+  iter,
   ops::Deref,
 };
 
+/// The error of converting a multicodec code the codec does not support.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct UnsupportedCodec(pub u64);
 
-pub enum Error {
+impl fmt::Display for UnsupportedCodec {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", ErrorKind::UnsupportedCodec(self.0))
+  }
+}
+
+/// The kind of failure of encoding or decoding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+  /// The input ended in the middle of a value.
+  UnexpectedEof,
+  /// A byte that cannot start the value being decoded. For dag-cbor this is
+  /// the initial byte holding the major type.
+  InvalidMajorType(u8),
+  /// A string that is not valid UTF-8.
+  InvalidUtf8,
+  /// A tag that is not supported.
+  UnknownTag(u64),
+  /// Bytes that are not a valid CID, with the reason.
+  InvalidCid(String),
+  /// An integer or length that does not fit the type being decoded, or a
+  /// number that cannot be encoded.
+  OutOfRange,
+  /// A limit of `DecodeLimits` was exceeded.
+  LimitExceeded(LimitExceeded),
+  /// The buffer being encoded into is too small for the encoded value.
+  BufferTooSmall,
+  /// The multicodec code is not supported.
   UnsupportedCodec(u64),
+  /// The multihash code is not supported.
+  UnsupportedHash(u64),
+  /// The hash of a block's data does not match the hash in its cid.
+  HashMismatch,
+  /// A segment of a path could not be followed through the encoded value.
+  Path(PathError),
+  /// Input that does not follow the syntax of the codec, such as malformed
+  /// JSON for dag-json or a tag that is not in its shortest form for dag-cbor.
+  InvalidSyntax,
+  /// A value of a different kind than the one being decoded.
+  WrongKind {
+    /// The kind that was expected.
+    expected: IpldKind,
+    /// The kind that was found instead.
+    found: IpldKind,
+  },
+  /// A map lacks a key that the type being decoded requires.
+  MissingKey(String),
+  /// A map has a key that the type being decoded does not know.
+  UnexpectedKey(String),
+  /// A map has the same key more than once.
+  DuplicateKey(String),
+  /// A list or map has a different number of entries than the type being
+  /// decoded requires, which is given.
+  WrongLength(usize),
+  /// A variant name or discriminant that the enum being decoded does not
+  /// have.
+  UnknownVariant(String),
+  /// A value that none of the variants of the enum being decoded accepts.
+  NoMatchingVariant,
+  /// A string that is not a valid JSON pointer.
+  InvalidPointer(String),
+  /// Any other failure, described by a message.
+  Message(String),
+}
+
+impl fmt::Display for ErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::UnexpectedEof => write!(f, "Unexpected end of input"),
+      Self::InvalidMajorType(major) => {
+        write!(f, "Unexpected code `0x{:02x}`", major)
+      }
+      Self::InvalidUtf8 => write!(f, "Invalid UTF-8"),
+      Self::UnknownTag(tag) => write!(f, "Unknown tag `{}`", tag),
+      Self::InvalidCid(e) => write!(f, "Invalid Cid: {}", e),
+      Self::OutOfRange => write!(f, "Number out of range"),
+      Self::LimitExceeded(e) => write!(f, "{}", e),
+      Self::BufferTooSmall => write!(f, "Buffer too small"),
+      Self::UnsupportedCodec(code) => {
+        write!(f, "Unsupported codec `0x{:x}`", code)
+      }
+      Self::UnsupportedHash(code) => {
+        write!(f, "Unsupported hash `0x{:x}`", code)
+      }
+      Self::HashMismatch => write!(f, "Hash does not match the Cid"),
+      Self::Path(e) => write!(f, "{}", e),
+      Self::InvalidSyntax => write!(f, "Invalid syntax"),
+      Self::WrongKind { expected, found } => {
+        write!(f, "Expected {}, found {}", expected, found)
+      }
+      Self::MissingKey(key) => write!(f, "Missing key `{}`", key),
+      Self::UnexpectedKey(key) => write!(f, "Unexpected key `{}`", key),
+      Self::DuplicateKey(key) => write!(f, "Duplicate key `{}`", key),
+      Self::WrongLength(len) => write!(f, "Expected {} entries", len),
+      Self::UnknownVariant(name) => write!(f, "Unknown variant `{}`", name),
+      Self::NoMatchingVariant => write!(f, "No variant matched"),
+      Self::InvalidPointer(pointer) => {
+        write!(f, "Invalid pointer {:?}", pointer)
+      }
+      Self::Message(msg) => write!(f, "{}", msg),
+    }
+  }
+}
+
+/// An error of a codec, with where it happened.
+///
+/// The details are boxed to keep `Result`s small, since they are returned
+/// from every level of recursive decoding.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Error(Box<ErrorImpl>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ErrorImpl {
+  kind: ErrorKind,
+  offset: Option<u64>,
+  path: IpldPath,
+}
+
+impl Error {
+  /// Returns an error of the given kind, without offset or path.
+  pub fn new(kind: ErrorKind) -> Self {
+    Self(Box::new(ErrorImpl { kind, offset: None, path: IpldPath::new() }))
+  }
+
+  /// Returns an `ErrorKind::Message` error.
+  pub fn message<S: Into<String>>(message: S) -> Self {
+    Self::new(ErrorKind::Message(message.into()))
+  }
+
+  /// Returns what went wrong.
+  pub fn kind(&self) -> &ErrorKind { &self.0.kind }
+
+  /// Returns the position of the cursor when the error happened, if known.
+  pub fn offset(&self) -> Option<u64> { self.0.offset }
+
+  /// Returns the path of the value that was being decoded, which is empty if
+  /// unknown or at the root.
+  pub fn path(&self) -> &IpldPath { &self.0.path }
+
+  /// Returns what went wrong, discarding where.
+  pub fn into_kind(self) -> ErrorKind { self.0.kind }
+
+  /// Sets the offset of the error, unless one was already set closer to
+  /// where it happened.
+  pub fn at(mut self, offset: u64) -> Self {
+    self.0.offset.get_or_insert(offset);
+    self
+  }
+
+  /// Moves the offset of the error by `base`, for errors of decoding a part
+  /// of the input that starts at `base`.
+  #[cfg(feature = "dag-cbor")]
+  pub(crate) fn offset_by(mut self, base: u64) -> Self {
+    if let Some(offset) = &mut self.0.offset {
+      *offset += base;
+    }
+    self
+  }
+
+  /// Records that the error happened within the child `segment` of the
+  /// value being decoded.
+  pub fn within<S: Into<String>>(mut self, segment: S) -> Self {
+    let segments = self.0.path.segments().iter().cloned();
+    self.0.path = iter::once(segment.into()).chain(segments).collect();
+    self
+  }
+}
+
+impl From<ErrorKind> for Error {
+  fn from(kind: ErrorKind) -> Self { Self::new(kind) }
+}
+
+impl From<UnsupportedCodec> for Error {
+  fn from(e: UnsupportedCodec) -> Self {
+    Self::new(ErrorKind::UnsupportedCodec(e.0))
+  }
+}
+
+impl From<LimitExceeded> for ErrorKind {
+  fn from(e: LimitExceeded) -> Self { Self::LimitExceeded(e) }
+}
+
+impl From<LimitExceeded> for Error {
+  fn from(e: LimitExceeded) -> Self { Self::new(ErrorKind::LimitExceeded(e)) }
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.kind())?;
+    if let Some(offset) = self.offset() {
+      write!(f, " at byte {}", offset)?;
+    }
+    if !self.path().is_empty() {
+      write!(f, " in `{}`", self.path())?;
+    }
+    Ok(())
+  }
+}
+
+/// A sink that values are encoded into.
+pub trait Writer {
+  /// Writes all of `buf`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if not all of `buf` could be written
+  fn write_all(&mut self, buf: &[u8]) -> Result<(), Error>;
+
+  /// Returns the position the next byte is written at, which is used as the
+  /// offset of errors.
+  fn position(&self) -> u64;
+}
+
+impl<W: Writer + ?Sized> Writer for &mut W {
+  fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+    (**self).write_all(buf)
+  }
+
+  fn position(&self) -> u64 { (**self).position() }
+}
+
+/// A cursor over borrowed bytes that values are decoded from, so that
+/// decoding reads the bytes in place instead of copying them.
+#[derive(Clone, Debug)]
+pub struct SliceCursor<'a> {
+  pub(crate) bytes: &'a [u8],
+  pub(crate) pos: usize,
+}
+
+impl<'a> SliceCursor<'a> {
+  /// Returns a cursor at the start of `bytes`.
+  pub fn new(bytes: &'a [u8]) -> Self { Self { bytes, pos: 0 } }
+
+  /// Returns all the bytes of the cursor, including those already read.
+  pub fn get_ref(&self) -> &'a [u8] { self.bytes }
+
+  /// Returns the position the next byte is read at.
+  pub fn position(&self) -> u64 { self.pos as u64 }
+
+  /// Sets the position the next byte is read at, which is clamped to the
+  /// end of the bytes.
+  pub fn set_position(&mut self, pos: u64) {
+    self.pos = usize::try_from(pos).map_or(self.bytes.len(), |pos| {
+      pos.min(self.bytes.len())
+    });
+  }
+
+  /// Returns the number of bytes left to read.
+  pub fn remaining(&self) -> usize { self.bytes.len() - self.pos }
+
+  /// Reads the next `len` bytes.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if less than `len` bytes are left to read
+  pub fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+    if len > self.remaining() {
+      return Err(Error::new(ErrorKind::UnexpectedEof).at(self.position()));
+    }
+    let bytes = &self.bytes[self.pos..self.pos + len];
+    self.pos += len;
+    Ok(bytes)
+  }
+
+  /// Reads a single byte.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if no bytes are left to read
+  pub fn read_u8(&mut self) -> Result<u8, Error> { Ok(self.take(1)?[0]) }
+
+  /// Returns the next byte without reading it.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if no bytes are left to read
+  pub fn peek_u8(&self) -> Result<u8, Error> {
+    self
+      .bytes
+      .get(self.pos)
+      .copied()
+      .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof).at(self.position()))
+  }
+}
+
+impl<'a> From<&'a ByteCursor> for SliceCursor<'a> {
+  /// Returns a cursor over the bytes of `cursor`, at its position.
+  fn from(cursor: &'a ByteCursor) -> Self {
+    let mut slice = Self::new(cursor.get_ref());
+    slice.set_position(cursor.position());
+    slice
+  }
+}
+
+impl Writer for ByteCursor {
+  fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+    let offset = self.position();
+    ByteCursor::write_all(self, buf).map_err(|e| Error::message(e).at(offset))
+  }
+
+  fn position(&self) -> u64 { ByteCursor::position(self) }
+}
+
+/// Appends to the vector.
+impl Writer for Vec<u8> {
+  fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+    self.extend_from_slice(buf);
+    Ok(())
+  }
+
+  fn position(&self) -> u64 { self.len() as u64 }
+}
+
+/// A writer into a fixed slice, which fails with `ErrorKind::BufferTooSmall`
+/// instead of growing.
+#[derive(Debug)]
+pub struct SliceWriter<'a> {
+  buf: &'a mut [u8],
+  written: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+  /// Returns a writer starting at the beginning of `buf`.
+  pub fn new(buf: &'a mut [u8]) -> Self { Self { buf, written: 0 } }
+
+  /// Returns the number of bytes written so far.
+  pub fn written(&self) -> usize { self.written }
+}
+
+impl<'a> Writer for SliceWriter<'a> {
+  fn write_all(&mut self, buf: &[u8]) -> Result<(), Error> {
+    let rest = &mut self.buf[self.written..];
+    if buf.len() > rest.len() {
+      return Err(Error::new(ErrorKind::BufferTooSmall).at(self.position()));
+    }
+    rest[..buf.len()].copy_from_slice(buf);
+    self.written += buf.len();
+    Ok(())
+  }
+
+  fn position(&self) -> u64 { self.written as u64 }
 }
 
 pub trait Codec:
@@ -32,21 +389,70 @@ pub trait Codec:
   fn encode<T: Encode<Self> + ?Sized>(
     &self,
     obj: &T,
-  ) -> Result<ByteCursor, String> {
+  ) -> Result<ByteCursor, Error> {
     let mut buf = ByteCursor::new(Vec::with_capacity(u16::MAX as usize));
     obj.encode(*self, &mut buf)?;
     Ok(buf)
   }
 
+  /// Encodes `obj` by appending it to `buf`, which is not cleared first, so
+  /// that one buffer can be reused for many values.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if there was a problem encoding the object
+  fn encode_into<T: Encode<Self> + ?Sized>(
+    &self,
+    obj: &T,
+    buf: &mut Vec<u8>,
+  ) -> Result<(), Error> {
+    obj.encode(*self, buf)
+  }
+
+  /// Encodes `obj` into the start of `buf` and returns the number of bytes
+  /// written.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if there was a problem encoding the object, with
+  /// `ErrorKind::BufferTooSmall` if it does not fit into `buf`
+  fn encode_to_slice<T: Encode<Self> + ?Sized>(
+    &self,
+    obj: &T,
+    buf: &mut [u8],
+  ) -> Result<usize, Error> {
+    let mut w = SliceWriter::new(buf);
+    obj.encode(*self, &mut w)?;
+    Ok(w.written())
+  }
+
+  /// Decodes the bytes into a `T` within the default `DecodeLimits`, which
+  /// bound the nesting depth of lists, maps and tags to 128. Deeper values
+  /// fail to decode with `LimitExceeded::Depth`; use `decode_with_limits` to
+  /// decode them.
+  ///
   /// # Errors
   ///
   /// Will return `Err` if there was a problem decoding the `ByteCursor` into an
-  /// object
-  fn decode<T: Decode<Self>>(
+  /// object, or if it is nested deeper than the default limits allow
+  fn decode<T: Decode<Self>>(&self, bytes: ByteCursor) -> Result<T, Error> {
+    T::decode(*self, &mut SliceCursor::from(&bytes))
+  }
+
+  /// Decodes like `decode`, but within `limits` instead of the default
+  /// limits.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if there was a problem decoding the `ByteCursor` into an
+  /// object, or if decoding exceeded `limits`
+  fn decode_with_limits<T: Decode<Self>>(
     &self,
-    mut bytes: ByteCursor,
-  ) -> Result<T, String> {
-    T::decode(*self, &mut bytes)
+    bytes: ByteCursor,
+    limits: DecodeLimits,
+  ) -> Result<T, Error> {
+    let mut budget = DecodeBudget::new(limits);
+    T::decode_limited(*self, &mut SliceCursor::from(&bytes), &mut budget)
   }
 
   /// Extends `set` with any cids the type encoded in the bytecursor
@@ -57,10 +463,28 @@ pub trait Codec:
   /// Returns `Err` if there were any errors decoding the bytecursor.
   fn references<T: References<Self>, E: Extend<Cid>>(
     &self,
-    mut bytes: ByteCursor,
+    bytes: ByteCursor,
     set: &mut E,
-  ) -> Result<(), String> {
-    T::references(*self, &mut bytes, set)
+  ) -> Result<(), Error> {
+    T::references(*self, &mut SliceCursor::from(&bytes), set)
+  }
+
+  /// Extends `set` like `references`, but within `limits` instead of the
+  /// default limits.
+  ///
+  /// # Errors
+  ///
+  /// Returns `Err` if there were any errors decoding the bytecursor, or if
+  /// decoding exceeded `limits`.
+  fn references_with_limits<T: References<Self>, E: Extend<Cid>>(
+    &self,
+    bytes: ByteCursor,
+    set: &mut E,
+    limits: DecodeLimits,
+  ) -> Result<(), Error> {
+    let mut budget = DecodeBudget::new(limits);
+    let mut r = SliceCursor::from(&bytes);
+    T::references_limited(*self, &mut r, set, &mut budget)
   }
 }
 
@@ -73,17 +497,39 @@ pub trait Encode<C: Codec> {
   /// # Errors
   ///
   /// Will return `Err` if there was a problem during encoding
-  fn encode(&self, c: C, w: &mut ByteCursor) -> Result<(), String>;
+  fn encode<W: Writer>(&self, c: C, w: &mut W) -> Result<(), Error>;
 }
 
 impl<C: Codec, T: Encode<C>> Encode<C> for &T {
-  fn encode(&self, c: C, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(&self, c: C, w: &mut W) -> Result<(), Error> {
     self.deref().encode(c, w)
   }
 }
 
+/// A trait representing the ability to compute the length of the
+/// encoding of the type with the codec `C` without encoding it.
+pub trait EncodedLen<C: Codec> {
+  /// Returns the exact number of bytes `Self` is encoded to with the codec
+  /// `C`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `Self` cannot be encoded with the codec `C`
+  fn encoded_len(&self, c: C) -> Result<usize, Error>;
+}
+
+impl<C: Codec, T: EncodedLen<C> + ?Sized> EncodedLen<C> for &T {
+  fn encoded_len(&self, c: C) -> Result<usize, Error> {
+    (**self).encoded_len(c)
+  }
+}
+
 /// A trait representing the ability to decode with
 /// the codec `C` for the type.
+///
+/// `DecodeLimits` are only enforced by impls that override `decode_limited`.
+/// The default impl ignores its budget, so a type relying on it is decoded
+/// without limits, even when nested in a type that enforces them.
 pub trait Decode<C: Codec>: Sized {
   /// Decodes the bytes in `r` using the codec `C` into
   /// `Self`. Returns `ok` if the bytes represented a valid
@@ -92,11 +538,31 @@ pub trait Decode<C: Codec>: Sized {
   /// # Errors
   ///
   /// Will return `Err` if there was a problem during decoding
-  fn decode(c: C, r: &mut ByteCursor) -> Result<Self, String>;
+  fn decode(c: C, r: &mut SliceCursor) -> Result<Self, Error>;
+
+  /// Decodes like `decode`, charging nested values and allocations to
+  /// `budget`. The default implementation ignores `budget`, which is only
+  /// correct for types that neither nest nor allocate.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if there was a problem during decoding, or if
+  /// decoding exceeded the limits of `budget`
+  fn decode_limited(
+    c: C,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let _ = budget;
+    Self::decode(c, r)
+  }
 }
 
 /// A trait representing the ability to count cid references in the
 /// encoding of the type with the codec `C`
+///
+/// Like for `Decode`, `DecodeLimits` are only enforced by impls that override
+/// `references_limited`, whose default impl ignores its budget.
 pub trait References<C: Codec>: Sized {
   /// Extends `set` with any Cid references found in the encoding
   /// of the type in `r` with the codec `C`
@@ -107,17 +573,53 @@ pub trait References<C: Codec>: Sized {
   /// type with codec `C`.
   fn references<E: Extend<Cid>>(
     c: C,
-    r: &mut ByteCursor,
+    r: &mut SliceCursor,
+    set: &mut E,
+  ) -> Result<(), Error>;
+
+  /// Extends `set` like `references`, charging nested values to `budget`.
+  /// The default implementation ignores `budget`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `r` did not contain a valid encoding of the
+  /// type with codec `C`, or if decoding exceeded the limits of `budget`.
+  fn references_limited<E: Extend<Cid>>(
+    c: C,
+    r: &mut SliceCursor,
     set: &mut E,
-  ) -> Result<(), String>;
+    budget: &mut DecodeBudget,
+  ) -> Result<(), Error> {
+    let _ = budget;
+    Self::references(c, r, set)
+  }
 }
 
 /// A trait for codecs representing the ability to skip values.
+///
+/// Like for `Decode`, `DecodeLimits` are only enforced by impls that override
+/// `skip_limited`, whose default impl ignores its budget.
 pub trait SkipOne: Codec {
   /// Skips a single value of the encoded type using the given codec in `r`.
   ///
   /// # Errors
   ///
   /// Will return `Err` if there was a problem during skipping
-  fn skip(&self, r: &mut ByteCursor) -> Result<(), String>;
+  fn skip(&self, r: &mut SliceCursor) -> Result<(), Error>;
+
+  /// Skips a single value like `skip`, charging nested values to `budget`.
+  /// The default implementation ignores `budget`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if there was a problem during skipping, or if
+  /// skipping exceeded the limits of `budget`
+  fn skip_limited(
+    &self,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<(), Error> {
+    let _ = budget;
+    self.skip(r)
+  }
 }
@@ -0,0 +1,392 @@
+use crate::ipld::{
+  Ipld,
+  IpldKind,
+};
+
+use alloc::{
+  borrow::ToOwned,
+  collections::btree_map::BTreeMap,
+  string::String,
+  vec::Vec,
+};
+use core::{
+  convert::{
+    Infallible,
+    TryFrom,
+  },
+  fmt,
+  num::TryFromIntError,
+};
+use sp_cid::Cid;
+
+/// An error returned when an `Ipld` value cannot be converted into a Rust
+/// type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConversionError {
+  /// The value is not of the kind the target type is converted from.
+  WrongKind {
+    /// The kind the target type is converted from.
+    expected: IpldKind,
+    /// The kind of the value.
+    found: IpldKind,
+  },
+  /// The integer does not fit into the target integer type.
+  OutOfRange {
+    /// The integer that was converted.
+    value: i128,
+    /// The name of the target integer type.
+    target: &'static str,
+  },
+  /// The float cannot be represented exactly by the target float type.
+  Inexact {
+    /// The name of the target float type.
+    target: &'static str,
+  },
+}
+
+impl ConversionError {
+  pub(crate) fn wrong_kind(expected: IpldKind, found: &Ipld) -> Self {
+    Self::WrongKind { expected, found: found.kind() }
+  }
+}
+
+impl fmt::Display for ConversionError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::WrongKind { expected, found } => {
+        write!(f, "Expected {}, found {}", expected, found)
+      }
+      Self::OutOfRange { value, target } => {
+        write!(f, "Integer {} out of range for {}", value, target)
+      }
+      Self::Inexact { target } => {
+        write!(f, "Float cannot be represented exactly as {}", target)
+      }
+    }
+  }
+}
+
+impl From<Infallible> for ConversionError {
+  fn from(x: Infallible) -> Self { match x {} }
+}
+
+macro_rules! derive_integer {
+  ($($ty:ident),*) => {
+    $(
+      impl From<$ty> for Ipld {
+        fn from(x: $ty) -> Self { Self::Integer(i128::from(x)) }
+      }
+    )*
+    derive_try_integer!($($ty),*);
+  };
+}
+
+macro_rules! derive_try_integer {
+  ($($ty:ident),*) => {
+    $(
+      impl TryFrom<Ipld> for $ty {
+        type Error = ConversionError;
+
+        fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+          match ipld {
+            Ipld::Integer(x) => Self::try_from(x).map_err(|_| {
+              ConversionError::OutOfRange { value: x, target: stringify!($ty) }
+            }),
+            ipld => Err(ConversionError::wrong_kind(IpldKind::Integer, &ipld)),
+          }
+        }
+      }
+    )*
+  };
+}
+
+derive_integer!(i8, i16, i32, i64, u8, u16, u32, u64);
+derive_try_integer!(isize, usize, u128);
+
+/// `isize` is at most 64 bits wide, so it always fits.
+impl From<isize> for Ipld {
+  fn from(x: isize) -> Self { Self::Integer(x as i128) }
+}
+
+/// `usize` is at most 64 bits wide, so it always fits.
+impl From<usize> for Ipld {
+  fn from(x: usize) -> Self { Self::Integer(x as i128) }
+}
+
+/// Integers above `i128::MAX` cannot be converted.
+impl TryFrom<u128> for Ipld {
+  type Error = TryFromIntError;
+
+  fn try_from(x: u128) -> Result<Self, Self::Error> {
+    i128::try_from(x).map(Self::Integer)
+  }
+}
+
+macro_rules! derive_scalar {
+  ($($ty:ty => $kind:ident),*) => {
+    $(
+      impl From<$ty> for Ipld {
+        fn from(x: $ty) -> Self { Self::$kind(x) }
+      }
+
+      impl TryFrom<Ipld> for $ty {
+        type Error = ConversionError;
+
+        fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+          match ipld {
+            Ipld::$kind(x) => Ok(x),
+            ipld => Err(ConversionError::wrong_kind(IpldKind::$kind, &ipld)),
+          }
+        }
+      }
+    )*
+  };
+}
+
+derive_scalar!(
+  bool => Bool,
+  i128 => Integer,
+  f64 => Float,
+  String => String,
+  Vec<u8> => Bytes,
+  Cid => Link
+);
+
+impl From<f32> for Ipld {
+  fn from(x: f32) -> Self { Self::Float(f64::from(x)) }
+}
+
+/// Only floats that an `f32` represents exactly are converted, which
+/// includes infinities and NaN.
+impl TryFrom<Ipld> for f32 {
+  type Error = ConversionError;
+
+  fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+    let x = f64::try_from(ipld)?;
+    let narrowed = x as f32;
+    if f64::from(narrowed) == x || x.is_nan() {
+      Ok(narrowed)
+    }
+    else {
+      Err(ConversionError::Inexact { target: "f32" })
+    }
+  }
+}
+
+impl From<&str> for Ipld {
+  fn from(x: &str) -> Self { Self::String(x.to_owned()) }
+}
+
+impl From<&[u8]> for Ipld {
+  fn from(x: &[u8]) -> Self { Self::Bytes(x.to_vec()) }
+}
+
+impl<const N: usize> From<&[u8; N]> for Ipld {
+  fn from(x: &[u8; N]) -> Self { Self::Bytes(x.to_vec()) }
+}
+
+impl From<&Cid> for Ipld {
+  fn from(x: &Cid) -> Self { Self::Link(x.to_owned()) }
+}
+
+/// `None` is converted into `Ipld::Null`.
+impl<T: Into<Ipld>> From<Option<T>> for Ipld {
+  fn from(x: Option<T>) -> Self { x.map_or(Self::Null, Into::into) }
+}
+
+impl<T: Into<Ipld>> From<BTreeMap<String, T>> for Ipld {
+  fn from(x: BTreeMap<String, T>) -> Self {
+    Self::StringMap(x.into_iter().map(|(k, v)| (k, v.into())).collect())
+  }
+}
+
+impl<T> TryFrom<Ipld> for BTreeMap<String, T>
+where
+  T: TryFrom<Ipld>,
+  ConversionError: From<T::Error>,
+{
+  type Error = ConversionError;
+
+  fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+    match ipld {
+      Ipld::StringMap(map) => map
+        .into_iter()
+        .map(|(k, v)| Ok((k, T::try_from(v)?)))
+        .collect(),
+      ipld => Err(ConversionError::wrong_kind(IpldKind::StringMap, &ipld)),
+    }
+  }
+}
+
+// `Vec<u8>` converts to `Ipld::Bytes` and `Option<Ipld>` already has an
+// infallible conversion, so the list and option conversions can't be generic
+// over the element type without overlapping.
+macro_rules! derive_list {
+  ($($ty:ty),*) => {
+    $(
+      impl From<Vec<$ty>> for Ipld {
+        fn from(x: Vec<$ty>) -> Self {
+          Self::List(x.into_iter().map(Into::into).collect())
+        }
+      }
+
+      impl TryFrom<Ipld> for Vec<$ty> {
+        type Error = ConversionError;
+
+        fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+          match ipld {
+            Ipld::List(list) => list
+              .into_iter()
+              .map(|x| <$ty>::try_from(x).map_err(Into::into))
+              .collect(),
+            ipld => Err(ConversionError::wrong_kind(IpldKind::List, &ipld)),
+          }
+        }
+      }
+    )*
+  };
+}
+
+derive_list!(
+  Ipld,
+  bool,
+  i8,
+  i16,
+  i32,
+  i64,
+  i128,
+  isize,
+  u16,
+  u32,
+  u64,
+  usize,
+  f32,
+  f64,
+  String,
+  Vec<u8>,
+  Cid,
+  BTreeMap<String, Ipld>
+);
+
+macro_rules! derive_option {
+  ($($ty:ty),*) => {
+    $(
+      /// `Ipld::Null` is converted into `None`.
+      impl TryFrom<Ipld> for Option<$ty> {
+        type Error = ConversionError;
+
+        fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+          match ipld {
+            Ipld::Null => Ok(None),
+            ipld => <$ty>::try_from(ipld).map(Some),
+          }
+        }
+      }
+    )*
+  };
+}
+
+derive_option!(
+  bool,
+  i8,
+  i16,
+  i32,
+  i64,
+  i128,
+  isize,
+  u8,
+  u16,
+  u32,
+  u64,
+  u128,
+  usize,
+  f32,
+  f64,
+  String,
+  Vec<u8>,
+  Cid,
+  Vec<Ipld>,
+  BTreeMap<String, Ipld>
+);
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[quickcheck]
+  fn integer_roundtrip(x: i64, y: u8, z: i128) -> bool {
+    i64::try_from(Ipld::from(x)) == Ok(x)
+      && u8::try_from(Ipld::from(y)) == Ok(y)
+      && i128::try_from(Ipld::from(z)) == Ok(z)
+  }
+
+  #[quickcheck]
+  fn string_list_roundtrip(x: Vec<String>) -> bool {
+    Vec::<String>::try_from(Ipld::from(x.clone())) == Ok(x)
+  }
+
+  #[quickcheck]
+  fn map_roundtrip(x: BTreeMap<String, Option<u32>>) -> bool {
+    BTreeMap::<String, Option<u32>>::try_from(Ipld::from(x.clone())) == Ok(x)
+  }
+
+  #[test]
+  fn narrowing() {
+    assert_eq!(
+      u8::try_from(Ipld::Integer(256)),
+      Err(ConversionError::OutOfRange { value: 256, target: "u8" })
+    );
+    assert_eq!(
+      u64::try_from(Ipld::Integer(-1)),
+      Err(ConversionError::OutOfRange { value: -1, target: "u64" })
+    );
+    assert_eq!(i8::try_from(Ipld::Integer(-128)), Ok(-128));
+    assert_eq!(
+      usize::try_from(Ipld::Integer(-1)),
+      Err(ConversionError::OutOfRange { value: -1, target: "usize" })
+    );
+    assert_eq!(
+      u128::try_from(Ipld::Integer(i128::MAX)),
+      Ok(i128::MAX as u128)
+    );
+    assert!(Ipld::try_from(u128::MAX).is_err());
+    assert_eq!(Ipld::try_from(7u128), Ok(Ipld::Integer(7)));
+    assert_eq!(Ipld::from(-1isize), Ipld::Integer(-1));
+  }
+
+  #[quickcheck]
+  fn size_roundtrip(x: usize, y: isize) -> bool {
+    usize::try_from(Ipld::from(x)) == Ok(x)
+      && isize::try_from(Ipld::from(y)) == Ok(y)
+  }
+
+  #[test]
+  fn float_narrowing() {
+    assert_eq!(f32::try_from(Ipld::Float(0.5)), Ok(0.5));
+    assert_eq!(f32::try_from(Ipld::Float(f64::INFINITY)), Ok(f32::INFINITY));
+    assert!(f32::try_from(Ipld::Float(f64::NAN)).unwrap().is_nan());
+    let inexact = Err(ConversionError::Inexact { target: "f32" });
+    assert_eq!(f32::try_from(Ipld::Float(0.1)), inexact);
+    assert_eq!(f32::try_from(Ipld::Float(f64::MAX)), inexact);
+  }
+
+  #[test]
+  fn wrong_kind() {
+    assert_eq!(
+      String::try_from(Ipld::Integer(1)),
+      Err(ConversionError::WrongKind {
+        expected: IpldKind::String,
+        found: IpldKind::Integer,
+      })
+    );
+    assert_eq!(
+      Vec::<u32>::try_from(Ipld::List(vec![Ipld::Integer(1), Ipld::Null])),
+      Err(ConversionError::WrongKind {
+        expected: IpldKind::Integer,
+        found: IpldKind::Null,
+      })
+    );
+    assert_eq!(Option::<bool>::try_from(Ipld::Null), Ok(None));
+    assert_eq!(Vec::<u8>::try_from(Ipld::Bytes(vec![1])), Ok(vec![1]));
+  }
+}
@@ -3,11 +3,18 @@ use crate::{
     Codec,
     Decode,
     Encode,
+    Error,
+    SliceCursor,
     UnsupportedCodec,
   },
   ipld::Ipld,
+  ipld_ref::IpldRef,
+  limits::DecodeLimits,
+  path::IpldPath,
 };
 
+use bytecursor::ByteCursor;
+
 use sp_cid::Cid;
 use sp_multihash::{
   Code,
@@ -17,14 +24,83 @@ use sp_multihash::{
 use core::convert::TryFrom;
 
 pub mod decode;
+pub mod decoder;
 pub mod encode;
 
+pub use decoder::{
+  DagCborDecoder,
+  Decoded,
+};
+
 /// A struct representing the dag-cbor IPLD codec.
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DagCborCodec;
 
 impl Codec for DagCborCodec {}
 
+impl DagCborCodec {
+  /// Decodes the value at the start of `bytes` without copying its strings
+  /// and bytes, which borrow from `bytes` instead. Decoding is bounded by
+  /// the default `DecodeLimits`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `bytes` does not start with a valid dag-cbor value,
+  /// or if decoding exceeded the default limits
+  pub fn decode_borrowed<'a>(
+    &self,
+    bytes: &'a [u8],
+  ) -> Result<IpldRef<'a>, Error> {
+    self.decode_borrowed_with_limits(bytes, DecodeLimits::default())
+  }
+
+  /// Decodes like `decode_borrowed`, but within `limits` instead of the
+  /// default limits.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `bytes` does not start with a valid dag-cbor value,
+  /// or if decoding exceeded `limits`
+  pub fn decode_borrowed_with_limits<'a>(
+    &self,
+    bytes: &'a [u8],
+    limits: DecodeLimits,
+  ) -> Result<IpldRef<'a>, Error> {
+    decode::decode_borrowed(bytes, limits)
+  }
+
+  /// Decodes only the value at `path` within the value at the start of
+  /// `bytes`, skipping the values beside the path instead of decoding them.
+  /// Decoding is bounded by the default `DecodeLimits`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` with `ErrorKind::Path` if a segment of `path` cannot
+  /// be followed, or any other kind if the bytes before the target are not
+  /// valid dag-cbor, the target cannot be decoded into a `T`, or decoding
+  /// exceeded the default limits
+  pub fn decode_path<T: Decode<Self>>(
+    bytes: ByteCursor,
+    path: &IpldPath,
+  ) -> Result<T, Error> {
+    Self::decode_path_with_limits(bytes, path, DecodeLimits::default())
+  }
+
+  /// Decodes like `decode_path`, but within `limits` instead of the default
+  /// limits.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` like `decode_path`, or if decoding exceeded `limits`
+  pub fn decode_path_with_limits<T: Decode<Self>>(
+    bytes: ByteCursor,
+    path: &IpldPath,
+    limits: DecodeLimits,
+  ) -> Result<T, Error> {
+    decode::decode_path(&mut SliceCursor::from(&bytes), path, limits)
+  }
+}
+
 impl From<DagCborCodec> for u64 {
   fn from(_: DagCborCodec) -> Self { 0x71 }
 }
@@ -32,7 +108,14 @@ impl From<DagCborCodec> for u64 {
 impl TryFrom<u64> for DagCborCodec {
   type Error = UnsupportedCodec;
 
-  fn try_from(_: u64) -> core::result::Result<Self, Self::Error> { Ok(Self) }
+  fn try_from(code: u64) -> core::result::Result<Self, Self::Error> {
+    if code == u64::from(Self) {
+      Ok(Self)
+    }
+    else {
+      Err(UnsupportedCodec(code))
+    }
+  }
 }
 
 /// A trait representing the capability to both decode and encode
@@ -42,7 +125,8 @@ pub trait DagCbor: Encode<DagCborCodec> + Decode<DagCborCodec> {}
 impl<T: Encode<DagCborCodec> + Decode<DagCborCodec>> DagCbor for T {}
 
 /// Returns the corresponding dag-cbor v1 Cid
-/// to the passed IPLD
+/// to the passed IPLD, hashed with blake2b-256.
+/// Use `CidBuilder` for other hash functions.
 /// # Panics
 /// Panics if x could not be encoded into a dag-cbor bytecursor
 pub fn cid(x: &Ipld) -> Cid {
@@ -56,7 +140,20 @@ pub fn cid(x: &Ipld) -> Cid {
 #[cfg(test)]
 pub mod tests {
   use super::*;
-  use crate::ipld::*;
+  use crate::{
+    codec::{
+      EncodedLen,
+      ErrorKind,
+      SkipOne,
+    },
+    ipld::*,
+    limits::LimitExceeded,
+    path::{
+      IpldPath,
+      PathError,
+      PathErrorKind,
+    },
+  };
   use bytecursor::ByteCursor;
   use quickcheck::{
     quickcheck,
@@ -65,13 +162,14 @@ pub mod tests {
   };
 
   use alloc::collections::btree_map::BTreeMap;
+  use sp_ipld_derive::DagCbor;
 
   fn encode_decode_id<T: DagCbor + PartialEq<T> + Clone>(value: T) -> bool {
     let mut bc = ByteCursor::new(Vec::new());
     match Encode::encode(&value, DagCborCodec, &mut bc) {
       Ok(()) => {
-        bc.set_position(0);
-        match Decode::decode(DagCborCodec, &mut bc) {
+        let mut r = SliceCursor::new(bc.get_ref());
+        match Decode::decode(DagCborCodec, &mut r) {
           Ok(new_value) => return value == new_value,
           Err(e) => println!("Error occurred during decoding: {}", e),
         }
@@ -92,6 +190,57 @@ pub mod tests {
     encode_decode_id(Ipld::Integer(x as i128))
   }
 
+  #[quickcheck]
+  pub fn edid_signed(a: i8, b: i16, c: i32, d: i64) -> bool {
+    encode_decode_id((a, b, c, d))
+  }
+
+  #[quickcheck]
+  pub fn signed_encodes_as_integer(x: i64) -> bool {
+    let bytes = DagCborCodec.encode(&x).unwrap().into_inner();
+    let ipld = DagCborCodec.encode(&Ipld::Integer(x.into())).unwrap();
+    bytes == ipld.into_inner()
+  }
+
+  #[test]
+  fn signed_integers() {
+    // Non-negative values use major type 0 and negative ones major type 1,
+    // always with the shortest argument.
+    let encodes = |x: i64, bytes: &[u8]| {
+      assert_eq!(DagCborCodec.encode(&x).unwrap().into_inner(), bytes);
+      if let Ok(x) = i8::try_from(x) {
+        assert_eq!(DagCborCodec.encode(&x).unwrap().into_inner(), bytes);
+      }
+      if let Ok(x) = i32::try_from(x) {
+        assert_eq!(DagCborCodec.encode(&x).unwrap().into_inner(), bytes);
+      }
+    };
+    encodes(0, &[0x00]);
+    encodes(23, &[0x17]);
+    encodes(24, &[0x18, 0x18]);
+    encodes(127, &[0x18, 0x7f]);
+    encodes(-1, &[0x20]);
+    encodes(-24, &[0x37]);
+    encodes(-25, &[0x38, 0x18]);
+    encodes(-128, &[0x38, 0x7f]);
+    encodes(-129, &[0x38, 0x80]);
+    encodes(i64::MAX, &[0x1b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+    encodes(i64::MIN, &[0x3b, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+
+    // Both major types decode into every signed type the value fits in.
+    assert_eq!(DagCborCodec.decode::<i8>(cursor_of(&[0x18, 0x7f])), Ok(127));
+    assert_eq!(DagCborCodec.decode::<i16>(cursor_of(&[0x38, 0x80])), Ok(-129));
+    let max = [0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+    let error = DagCborCodec.decode::<i64>(cursor_of(&max)).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::OutOfRange);
+    let int = DagCborCodec.decode::<i128>(cursor_of(&max));
+    assert_eq!(int, Ok(u64::MAX.into()));
+    let error = DagCborCodec.decode::<i8>(cursor_of(&[0x38, 0x80]));
+    assert_eq!(error.unwrap_err().kind(), &ErrorKind::OutOfRange);
+    let error = DagCborCodec.decode::<i32>(cursor_of(&[0xf6])).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::InvalidMajorType(0xf6));
+  }
+
   #[quickcheck]
   pub fn edid_bytes(x: Vec<u8>) -> bool { encode_decode_id(Ipld::Bytes(x)) }
 
@@ -118,4 +267,541 @@ pub mod tests {
 
   #[quickcheck]
   pub fn edid_link(x: ACid) -> bool { encode_decode_id(Ipld::Link(x.0)) }
+
+  #[cfg(feature = "unleashed")]
+  #[quickcheck]
+  pub fn edid_integer_map(x: BTreeMap<i64, Ipld>) -> bool {
+    // an empty map always decodes as a string map
+    x.is_empty() || encode_decode_id(Ipld::IntegerMap(x))
+  }
+
+  #[cfg(feature = "unleashed")]
+  #[quickcheck]
+  pub fn edid_tag(tag: u64, x: Ipld) -> bool {
+    // tag 42 is reserved for links
+    tag == 42 || encode_decode_id(Ipld::Tag(tag, Box::new(x)))
+  }
+
+  #[cfg(feature = "unleashed")]
+  #[test]
+  fn encode_link_tag() {
+    let ipld = Ipld::List(vec![Ipld::Tag(42, Box::new(ipld!("a")))]);
+    let e = DagCborCodec.encode(&ipld).unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::UnknownTag(42));
+    assert_eq!(e.offset(), Some(1));
+    assert!(ipld.encoded_len(DagCborCodec).is_err());
+  }
+
+  #[cfg(feature = "unleashed")]
+  #[test]
+  fn decode_tagged_integer_map() {
+    // {1: 1(1363896240), -2: "a"}
+    let bytes =
+      vec![0xa2, 0x01, 0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0, 0x21, 0x61, 0x61];
+    let mut map = BTreeMap::new();
+    map.insert(1, Ipld::Tag(1, Box::new(Ipld::Integer(1363896240))));
+    map.insert(-2, Ipld::String("a".to_owned()));
+    let ipld = Ipld::IntegerMap(map);
+    let decoded: Ipld =
+      DagCborCodec.decode(ByteCursor::new(bytes.clone())).unwrap();
+    assert_eq!(decoded, ipld);
+    assert_eq!(DagCborCodec.encode(&ipld).unwrap().into_inner(), bytes);
+    // {1: null, "a": null}
+    let bytes = [0xa2, 0x01, 0xf6, 0x61, 0x61, 0xf6];
+    let error = DagCborCodec.decode::<Ipld>(cursor_of(&bytes)).unwrap_err();
+    let kind = ErrorKind::WrongKind {
+      expected: IpldKind::Integer,
+      found: IpldKind::String,
+    };
+    assert_eq!(error.kind(), &kind);
+    assert_eq!(error.offset(), Some(5));
+    assert_eq!(DagCborCodec.decode_borrowed(&bytes), Err(error));
+  }
+
+  #[cfg(feature = "unleashed")]
+  #[test]
+  fn decode_non_minimal_tag() {
+    // [42(1)] with a two byte tag, and [7([null])] with a one byte tag
+    for bytes in [&[0x81, 0xd9, 0x00, 0x2a, 0x01][..], &[0x81, 0xd8, 0x07]] {
+      let mut bytes = bytes.to_vec();
+      bytes.extend([0x81, 0xf6]);
+      let error = DagCborCodec.decode::<Ipld>(cursor_of(&bytes)).unwrap_err();
+      assert_eq!(error.kind(), &ErrorKind::InvalidSyntax);
+      assert_eq!(error.offset(), Some(1));
+      assert_eq!(DagCborCodec.decode_borrowed(&bytes), Err(error.clone()));
+      let skip = DagCborCodec.skip(&mut SliceCursor::new(&bytes));
+      assert_eq!(skip.unwrap_err().into_kind(), ErrorKind::InvalidSyntax);
+      let mut set = Vec::new();
+      let refs =
+        DagCborCodec.references::<Ipld, _>(cursor_of(&bytes), &mut set);
+      assert_eq!(refs.unwrap_err().into_kind(), ErrorKind::InvalidSyntax);
+      let path = IpldPath::from("0/0");
+      let value = DagCborCodec::decode_path::<Ipld>(cursor_of(&bytes), &path);
+      assert_eq!(value.unwrap_err().into_kind(), ErrorKind::InvalidSyntax);
+    }
+  }
+
+  #[quickcheck]
+  fn decode_borrowed(x: Ipld) -> bool {
+    let bytes = DagCborCodec.encode(&x).unwrap().into_inner();
+    DagCborCodec.decode_borrowed(&bytes).map(|x| x.to_ipld()) == Ok(x)
+  }
+
+  #[test]
+  fn decode_borrowed_borrows() {
+    let bytes = DagCborCodec
+      .encode(&ipld!({ "key": ["value", b"\x01\x02"] }))
+      .unwrap()
+      .into_inner();
+    let range = bytes.as_ptr_range();
+    match DagCborCodec.decode_borrowed(&bytes).unwrap() {
+      IpldRef::StringMap(map) => {
+        let (key, value) = map.into_iter().next().unwrap();
+        assert!(range.contains(&key.as_ptr()));
+        match value {
+          IpldRef::List(list) => match list.as_slice() {
+            [IpldRef::String(s), IpldRef::Bytes(b)] => {
+              assert!(range.contains(&s.as_ptr()));
+              assert!(range.contains(&b.as_ptr()));
+              assert_eq!(b, &[1, 2]);
+            }
+            list => panic!("Unexpected list {:?}", list),
+          },
+          value => panic!("Unexpected value {:?}", value),
+        }
+      }
+      ipld => panic!("Unexpected value {:?}", ipld),
+    }
+    assert!(DagCborCodec.decode_borrowed(&bytes[..bytes.len() - 1]).is_err());
+  }
+
+  #[quickcheck]
+  fn encode_into(x: Ipld) -> bool {
+    let bytes = DagCborCodec.encode(&x).unwrap().into_inner();
+    let mut buf = vec![0xff];
+    DagCborCodec.encode_into(&x, &mut buf).unwrap();
+    let mut slice = vec![0; bytes.len() + 1];
+    let written = DagCborCodec.encode_to_slice(&x, &mut slice).unwrap();
+    buf[1..] == bytes[..] && slice[..written] == bytes[..]
+  }
+
+  #[test]
+  fn encode_to_small_slice() {
+    let ipld = ipld!({ "a": "hello" });
+    let mut buf = [0; 7];
+    let error = DagCborCodec.encode_to_slice(&ipld, &mut buf).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::BufferTooSmall);
+    assert_eq!(error.offset(), Some(4));
+    let mut buf = [0; 9];
+    assert_eq!(DagCborCodec.encode_to_slice(&ipld, &mut buf), Ok(9));
+  }
+
+  fn encoded_len_exact<T: Encode<DagCborCodec> + EncodedLen<DagCborCodec>>(
+    value: T,
+  ) -> bool {
+    let len = DagCborCodec.encode(&value).unwrap().into_inner().len();
+    value.encoded_len(DagCborCodec) == Ok(len)
+  }
+
+  #[quickcheck]
+  fn encoded_len(x: Ipld) -> bool { encoded_len_exact(x) }
+
+  #[quickcheck]
+  fn encoded_len_primitives(
+    a: (i64, u32, bool),
+    b: Option<f64>,
+    c: f32,
+  ) -> bool {
+    encoded_len_exact(a) && encoded_len_exact(b) && encoded_len_exact(c)
+  }
+
+  #[test]
+  fn encoded_len_edges() {
+    for x in [0x17, 0x18, 0xff, 0x100, 0xffff, 0x1_0000, u64::MAX] {
+      assert!(encoded_len_exact(x));
+      assert!(encoded_len_exact(Ipld::Integer(-i128::from(x) - 1)));
+    }
+    for x in [0.5, 1.1, f64::NAN, f64::INFINITY] {
+      assert!(encoded_len_exact(x));
+    }
+    let mut g = Gen::new(10);
+    assert!(encoded_len_exact(crate::ipld::tests::arbitrary_cid(&mut g)));
+    assert!(Ipld::Integer(i128::MAX).encoded_len(DagCborCodec).is_err());
+  }
+
+  #[test]
+  fn decode_limits_depth() {
+    let mut deep = vec![0x81; 200];
+    deep.push(0xf6);
+    let depth = ErrorKind::LimitExceeded(LimitExceeded::Depth(128));
+    let ipld = DagCborCodec.decode::<Ipld>(cursor_of(&deep));
+    assert_eq!(ipld.unwrap_err().into_kind(), depth);
+    let skip = DagCborCodec.skip(&mut SliceCursor::new(&deep));
+    assert_eq!(skip.unwrap_err().into_kind(), depth);
+    let mut set = Vec::new();
+    let refs = DagCborCodec.references::<Ipld, _>(cursor_of(&deep), &mut set);
+    assert_eq!(refs.unwrap_err().into_kind(), depth);
+    let borrowed = DagCborCodec.decode_borrowed(&deep);
+    assert_eq!(borrowed.unwrap_err().into_kind(), depth);
+    let path = IpldPath::from("0");
+    let value = DagCborCodec::decode_path::<Ipld>(cursor_of(&deep), &path);
+    assert_eq!(value.unwrap_err().into_kind(), depth);
+    let limits = DecodeLimits { max_depth: 200, ..DecodeLimits::default() };
+    assert!(DagCborCodec
+      .decode_with_limits::<Ipld>(cursor_of(&deep), limits)
+      .is_ok());
+    assert!(DagCborCodec.decode_borrowed_with_limits(&deep, limits).is_ok());
+    assert!(DagCborCodec::decode_path_with_limits::<Ipld>(
+      cursor_of(&deep),
+      &path,
+      limits
+    )
+    .is_ok());
+  }
+
+  fn cursor_of(bytes: &[u8]) -> ByteCursor { ByteCursor::new(bytes.to_vec()) }
+
+  #[test]
+  fn decode_limits() {
+    let bytes = DagCborCodec
+      .encode(&ipld!({ "a": [1, 2, 3], "b": "hello" }))
+      .unwrap()
+      .into_inner();
+    let decode = |limits| {
+      DagCborCodec
+        .decode_with_limits::<Ipld>(cursor_of(&bytes), limits)
+        .map_err(Error::into_kind)
+    };
+    let limits = DecodeLimits::default();
+    assert!(decode(limits).is_ok());
+    assert_eq!(
+      decode(DecodeLimits { max_len: 2, ..limits }),
+      Err(ErrorKind::LimitExceeded(LimitExceeded::Len(2)))
+    );
+    assert_eq!(
+      decode(DecodeLimits { max_bytes_len: 4, ..limits }),
+      Err(ErrorKind::LimitExceeded(LimitExceeded::BytesLen(4)))
+    );
+    assert_eq!(
+      decode(DecodeLimits { max_alloc: 64, ..limits }),
+      Err(ErrorKind::LimitExceeded(LimitExceeded::Alloc(64)))
+    );
+    let borrowed = |bytes: &[u8], limits| {
+      DagCborCodec
+        .decode_borrowed_with_limits(bytes, limits)
+        .map(|_| ())
+        .map_err(Error::into_kind)
+    };
+    let bytes_len = Err(ErrorKind::LimitExceeded(LimitExceeded::BytesLen(4)));
+    let short = DecodeLimits { max_bytes_len: 4, ..limits };
+    assert_eq!(borrowed(&bytes, short), bytes_len);
+    let blob = DagCborCodec.encode(&Ipld::Bytes(vec![0; 5])).unwrap();
+    assert_eq!(borrowed(blob.get_ref(), short), bytes_len);
+    let key = DagCborCodec.encode(&ipld!({ "hello": 1 })).unwrap();
+    assert_eq!(borrowed(key.get_ref(), short), bytes_len);
+    // Repeated keys count towards the length of a map as often as they occur.
+    let repeated = [
+      0xbf, 0x61, 0x61, 0x01, 0x61, 0x61, 0x02, 0x61, 0x61, 0x03, 0xff,
+    ];
+    let len = ErrorKind::LimitExceeded(LimitExceeded::Len(2));
+    let two = DecodeLimits { max_len: 2, ..limits };
+    assert_eq!(borrowed(&repeated, two), Err(len.clone()));
+    let ipld =
+      DagCborCodec.decode_with_limits::<Ipld>(cursor_of(&repeated), two);
+    assert_eq!(ipld.unwrap_err().into_kind(), len);
+    let mut set = Vec::new();
+    let limits = DecodeLimits { max_len: 2, ..limits };
+    assert!(DagCborCodec
+      .references_with_limits::<Ipld, _>(cursor_of(&bytes), &mut set, limits)
+      .is_err());
+    // Lengths far beyond the input fail without allocating them first.
+    for header in [0x5b, 0x7b, 0x9b, 0xbb] {
+      let bytes = [header, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+      assert!(DagCborCodec.decode::<Ipld>(cursor_of(&bytes)).is_err());
+      assert!(DagCborCodec.decode_borrowed(&bytes).is_err());
+    }
+  }
+
+  #[test]
+  fn decode_errors() {
+    let bytes = DagCborCodec.encode(&ipld!({ "a": "hello" })).unwrap();
+    let bytes = bytes.into_inner();
+    let error = DagCborCodec
+      .decode::<Ipld>(cursor_of(&bytes[..bytes.len() - 2]))
+      .unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::UnexpectedEof);
+    assert_eq!(error.offset(), Some(4));
+    assert_eq!(error.path(), &IpldPath::from("a"));
+
+    let error = DagCborCodec.decode::<Ipld>(cursor_of(&[0x1c])).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::InvalidMajorType(0x1c));
+    assert_eq!(error.offset(), Some(0));
+
+    let bytes = [0x82, 0x01, 0x61, 0xff];
+    let error = DagCborCodec.decode::<Ipld>(cursor_of(&bytes)).unwrap_err();
+    assert_eq!(error.to_string(), "Invalid UTF-8 at byte 3 in `1`");
+    assert_eq!(DagCborCodec.decode_borrowed(&bytes), Err(error));
+
+    let bytes = [0x19, 0x01, 0x00];
+    let error = DagCborCodec.decode::<i8>(cursor_of(&bytes)).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::OutOfRange);
+  }
+
+  #[quickcheck]
+  pub fn decode_path_matches_get(x: Ipld) -> bool {
+    let bytes = DagCborCodec.encode(&x).unwrap().into_inner();
+    x.walk(WalkOrder::DepthFirst).all(|(path, ipld)| {
+      let decoded = DagCborCodec::decode_path(cursor_of(&bytes), &path);
+      decoded.as_ref() == Ok(ipld)
+    })
+  }
+
+  #[test]
+  fn decode_path() {
+    let link = cid(&ipld!("leaf"));
+    let ipld = ipld!({ "a": [1, { "b": "c" }, link], "d": [true] });
+    let bytes = DagCborCodec.encode(&ipld).unwrap().into_inner();
+    let decode = |path: &str| {
+      DagCborCodec::decode_path::<Ipld>(cursor_of(&bytes), &path.into())
+    };
+    assert_eq!(decode("a/1/b"), Ok(ipld!("c")));
+    assert_eq!(decode("a/2"), Ok(Ipld::Link(link)));
+    let path = IpldPath::from("a/1/b");
+    let string = DagCborCodec::decode_path::<String>(cursor_of(&bytes), &path);
+    assert_eq!(string, Ok("c".to_owned()));
+    let path = IpldPath::from("d/0");
+    let value = DagCborCodec::decode_path::<bool>(cursor_of(&bytes), &path);
+    assert_eq!(value, Ok(true));
+
+    let kind = |path: &str| match decode(path).unwrap_err().into_kind() {
+      ErrorKind::Path(e) => (e.position, e.kind),
+      kind => panic!("Unexpected error {}", kind),
+    };
+    assert_eq!(kind("e"), (0, PathErrorKind::MissingKey));
+    assert_eq!(kind("a/x"), (1, PathErrorKind::InvalidIndex));
+    assert_eq!(kind("a/3"), (1, PathErrorKind::IndexOutOfRange { len: 3 }));
+    assert_eq!(kind("a/0/b"), (2, PathErrorKind::NotContainer));
+    assert_eq!(kind("a/2/b"), (2, PathErrorKind::NotContainer));
+    let error = decode("a/1/e").unwrap_err();
+    assert_eq!(error.path(), &IpldPath::from("a/1"));
+    assert_eq!(error.offset(), Some(5));
+
+    // An indefinite list, whose length is only known once it was skipped.
+    let bytes = [0x9f, 0x01, 0x82, 0x02, 0x03, 0xff];
+    let path = IpldPath::from("1/1");
+    let value = DagCborCodec::decode_path::<u8>(cursor_of(&bytes), &path);
+    assert_eq!(value, Ok(3));
+    let path = IpldPath::from("2");
+    let error = DagCborCodec::decode_path::<u8>(cursor_of(&bytes), &path);
+    let len = PathErrorKind::IndexOutOfRange { len: 2 };
+    let error = error.unwrap_err().into_kind();
+    assert_eq!(error, ErrorKind::Path(PathError::new(&path, 0, len)));
+
+    // Integer map keys and tags are followed like `Ipld::get_path` does.
+    #[cfg(feature = "unleashed")]
+    {
+      let mut map = BTreeMap::new();
+      map.insert(-2, Ipld::Tag(7, Box::new(ipld!(["a", "b"]))));
+      let ipld = Ipld::IntegerMap(map);
+      let bytes = DagCborCodec.encode(&ipld).unwrap().into_inner();
+      let path = IpldPath::from("-2/1");
+      let value = DagCborCodec::decode_path(cursor_of(&bytes), &path);
+      assert_eq!(value, Ok("b".to_owned()));
+    }
+  }
+
+
+  #[derive(Clone, Debug, PartialEq, DagCbor)]
+  struct Post {
+    #[ipld(rename = "t")]
+    title: String,
+    tags: Vec<String>,
+    #[ipld(optional)]
+    parent: Option<Cid>,
+    #[ipld(default = 1)]
+    version: u32,
+    score: i32,
+  }
+
+  #[derive(Clone, Debug, PartialEq, DagCbor)]
+  #[ipld(repr = "tuple")]
+  struct Point {
+    x: i64,
+    y: i64,
+  }
+
+  #[derive(Clone, Debug, PartialEq, DagCbor)]
+  struct Pair<T>(T, String);
+
+  #[derive(Clone, Debug, PartialEq, DagCbor)]
+  enum Shape {
+    Circle { radius: u32 },
+    Square(u32),
+    Line(Point, Point),
+    Empty,
+  }
+
+  #[derive(Clone, Debug, PartialEq, DagCbor)]
+  #[ipld(repr = "kinded")]
+  enum Value {
+    Int(i64),
+    Text(String),
+    Nothing,
+  }
+
+  #[derive(Clone, Debug, PartialEq, DagCbor)]
+  #[ipld(repr = "int")]
+  enum Level {
+    Low = -1,
+    Mid,
+    High = 10,
+  }
+
+  #[derive(Clone, Debug, PartialEq, DagCbor)]
+  #[ipld(repr = "string")]
+  enum Color {
+    #[ipld(rename = "r")]
+    Red,
+    Green,
+  }
+
+  #[derive(Clone, Debug, PartialEq, DagCbor)]
+  struct Tree {
+    children: Vec<Tree>,
+  }
+
+  fn encodes_as<T: DagCbor + EncodedLen<DagCborCodec>>(value: &T, ipld: Ipld) {
+    let bytes = DagCborCodec.encode(value).unwrap().into_inner();
+    assert_eq!(bytes, DagCborCodec.encode(&ipld).unwrap().into_inner());
+    assert_eq!(value.encoded_len(DagCborCodec), Ok(bytes.len()));
+    let decoded: Ipld = DagCborCodec.decode(ByteCursor::new(bytes)).unwrap();
+    assert_eq!(decoded, ipld);
+  }
+
+  fn cursor(ipld: &Ipld) -> ByteCursor {
+    ByteCursor::new(DagCborCodec.encode(ipld).unwrap().into_inner())
+  }
+
+  #[test]
+  fn derive_map() {
+    let post = Post {
+      title: "a".to_owned(),
+      tags: vec!["b".to_owned()],
+      parent: None,
+      version: 1,
+      score: 3,
+    };
+    encodes_as(&post, ipld!({ "t": "a", "tags": ["b"], "score": 3 }));
+    let cid = cid(&Ipld::Null);
+    let post = Post { parent: Some(cid.to_owned()), version: 2, ..post };
+    encodes_as(
+      &post,
+      ipld!({
+        "t": "a",
+        "tags": ["b"],
+        "score": 3,
+        "parent": cid,
+        "version": 2,
+      }),
+    );
+    assert!(encode_decode_id(post));
+  }
+
+  #[test]
+  fn derive_map_decode() {
+    let ipld = ipld!({ "t": "a", "tags": [], "score": -3 });
+    let bytes = cursor(&ipld);
+    let post: Post = DagCborCodec.decode(bytes).unwrap();
+    assert_eq!(post.version, 1);
+    assert_eq!(post.score, -3);
+    let bytes = cursor(&ipld!({ "t": "a" }));
+    let error = DagCborCodec.decode::<Post>(bytes).unwrap_err();
+    assert_eq!(error.into_kind(), ErrorKind::MissingKey("tags".to_owned()));
+
+    let ipld = ipld!({ "t": "a", "tags": [], "score": -3, "other": [1, 2] });
+    let error = DagCborCodec.decode::<Post>(cursor(&ipld)).unwrap_err();
+    let unexpected = ErrorKind::UnexpectedKey("other".to_owned());
+    assert_eq!(error.into_kind(), unexpected);
+    // {"t": "a", "t": "b"}
+    let bytes = [0xa2, 0x61, 0x74, 0x61, 0x61, 0x61, 0x74, 0x61, 0x62];
+    let error = DagCborCodec.decode::<Post>(cursor_of(&bytes)).unwrap_err();
+    assert_eq!(error.offset(), Some(7));
+    assert_eq!(error.into_kind(), ErrorKind::DuplicateKey("t".to_owned()));
+  }
+
+  #[test]
+  fn derive_tuple() {
+    encodes_as(&Point { x: 1, y: -1 }, ipld!([1, -1]));
+    encodes_as(&Pair(Value::Nothing, "a".to_owned()), ipld!([null, "a"]));
+    assert!(encode_decode_id(Pair(Point { x: 2, y: 3 }, "b".to_owned())));
+    let bytes = cursor(&ipld!([1]));
+    let error = DagCborCodec.decode::<Point>(bytes).unwrap_err();
+    assert_eq!(error.into_kind(), ErrorKind::WrongLength(2));
+    let bytes = cursor(&ipld!([1, 2, 3]));
+    let error = DagCborCodec.decode::<Point>(bytes).unwrap_err();
+    assert_eq!(error.into_kind(), ErrorKind::WrongLength(2));
+  }
+
+  #[test]
+  fn derive_keyed() {
+    let point = Point { x: 0, y: 0 };
+    encodes_as(
+      &Shape::Circle { radius: 2 },
+      ipld!({ "Circle": { "radius": 2 } }),
+    );
+    encodes_as(&Shape::Square(2), ipld!({ "Square": 2 }));
+    encodes_as(
+      &Shape::Line(point.clone(), point.clone()),
+      ipld!({ "Line": [[0, 0], [0, 0]] }),
+    );
+    encodes_as(&Shape::Empty, ipld!({ "Empty": null }));
+    assert!(encode_decode_id(Shape::Line(point, Point { x: 1, y: 2 })));
+    let bytes = cursor(&ipld!({ "Oval": null }));
+    let error = DagCborCodec.decode::<Shape>(bytes).unwrap_err();
+    assert_eq!(error.into_kind(), ErrorKind::UnknownVariant("Oval".to_owned()));
+    let bytes = cursor(&ipld!({}));
+    let error = DagCborCodec.decode::<Shape>(bytes).unwrap_err();
+    assert_eq!(error.into_kind(), ErrorKind::WrongLength(1));
+  }
+
+  #[test]
+  fn derive_kinded() {
+    encodes_as(&Value::Int(-5), ipld!(-5));
+    encodes_as(&Value::Text("a".to_owned()), ipld!("a"));
+    encodes_as(&Value::Nothing, ipld!(null));
+    let bytes = cursor(&ipld!(true));
+    let error = DagCborCodec.decode::<Value>(bytes).unwrap_err();
+    assert_eq!(error.into_kind(), ErrorKind::NoMatchingVariant);
+  }
+
+  #[test]
+  fn derive_limits() {
+    let mut tree = Tree { children: vec![] };
+    for _ in 0..3 {
+      tree = Tree { children: vec![tree] };
+    }
+    let decode = |max_depth| {
+      let limits = DecodeLimits { max_depth, ..DecodeLimits::default() };
+      let bytes = DagCborCodec.encode(&tree).unwrap().into_inner();
+      DagCborCodec.decode_with_limits::<Tree>(cursor_of(&bytes), limits)
+    };
+    let error = decode(3).unwrap_err();
+    let depth = ErrorKind::LimitExceeded(LimitExceeded::Depth(3));
+    assert_eq!(error.kind(), &depth);
+    let path = IpldPath::from("children/0/children/0/children/0/children");
+    assert_eq!(error.path(), &path);
+    assert_eq!(decode(4), Ok(tree.clone()));
+  }
+
+  #[test]
+  fn derive_int_string() {
+    encodes_as(&Level::Low, ipld!(-1));
+    encodes_as(&Level::Mid, ipld!(0));
+    encodes_as(&Level::High, ipld!(10));
+    encodes_as(&Color::Red, ipld!("r"));
+    encodes_as(&Color::Green, ipld!("Green"));
+    let bytes = cursor(&ipld!(1));
+    let error = DagCborCodec.decode::<Level>(bytes).unwrap_err();
+    assert_eq!(error.into_kind(), ErrorKind::UnknownVariant("1".to_owned()));
+  }
 }
@@ -1,15 +1,31 @@
 use crate::{
   codec::{
     Decode,
+    Error,
+    ErrorKind,
     References,
     SkipOne,
+    SliceCursor,
   },
   dag_cbor::DagCborCodec,
   ipld::Ipld,
+  ipld_ref::IpldRef,
+  limits::{
+    DecodeBudget,
+    DecodeLimits,
+    LimitExceeded,
+  },
+  path::{
+    parse_index,
+    IpldPath,
+    PathError,
+    PathErrorKind,
+  },
 };
+#[cfg(feature = "unleashed")]
+use crate::ipld::IpldKind;
 
 use alloc::{
-  borrow::ToOwned,
   boxed::Box,
   collections::btree_map::BTreeMap,
   string::{
@@ -25,396 +41,571 @@ use byteorder::{
 };
 
 use core::{
-  any::type_name,
   convert::TryFrom,
+  mem,
 };
 
-use bytecursor::{
-  ByteCursor,
-  SeekFrom,
-};
 use sp_cid::Cid;
 
-/// # Errors
-///
-/// Will return `Err` if the `ByteCursor` has less than 1 available bytes to
-/// read
-fn read_u8(r: &mut ByteCursor) -> Result<u8, String> {
-  let mut buf = [0; 1];
-  r.read_exact(&mut buf)?;
-  Ok(buf[0])
+/// The dag-cbor specific reads, on top of the generic ones of `SliceCursor`.
+impl<'a> SliceCursor<'a> {
+  /// Returns an error of the given kind at the current position.
+  pub(crate) fn error(&self, kind: ErrorKind) -> Error {
+    Error::new(kind).at(self.pos as u64)
+  }
+
+  /// Returns the error for exceeding a limit at the current position.
+  pub(crate) fn limit(&self, e: LimitExceeded) -> Error { self.error(e.into()) }
+
+  /// Returns an `ErrorKind::InvalidMajorType` error for the byte `major`,
+  /// which was just read.
+  pub(crate) fn unexpected(&self, major: u8) -> Error {
+    Error::new(ErrorKind::InvalidMajorType(major))
+      .at(self.pos.saturating_sub(1) as u64)
+  }
+
+  /// Reads the argument of a header whose additional information is `info`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `info` is unknown or the slice is not long enough
+  pub(crate) fn read_uint(&mut self, info: u8) -> Result<u64, Error> {
+    let len = match info {
+      0x00..=0x17 => return Ok(u64::from(info)),
+      0x18 => 1,
+      0x19 => 2,
+      0x1a => 4,
+      0x1b => 8,
+      _ => {
+        return Err(self.unexpected(self.bytes[self.pos - 1]));
+      }
+    };
+    Ok(BigEndian::read_uint(self.take(len)?, len))
+  }
+
+  /// Reads the tag of a header whose initial byte `major` was just read.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the slice is not long enough or the tag is not
+  /// encoded in its shortest form, since it would not encode back to the same
+  /// bytes
+  pub(crate) fn read_tag(&mut self, major: u8) -> Result<u64, Error> {
+    let start = self.pos.saturating_sub(1);
+    let tag = self.read_uint(major - 0xc0)?;
+    let min = match major {
+      0xd8 => 0x18,
+      0xd9 => 0x100,
+      0xda => 0x1_0000,
+      0xdb => 0x1_0000_0000,
+      _ => 0,
+    };
+    if tag < min {
+      return Err(Error::new(ErrorKind::InvalidSyntax).at(start as u64));
+    }
+    Ok(tag)
+  }
+
+  /// # Errors
+  ///
+  /// Will return `Err` if `info` is unknown, the slice is not long enough or
+  /// the length does not fit into a `usize`
+  pub(crate) fn read_len(&mut self, info: u8) -> Result<usize, Error> {
+    usize::try_from(self.read_uint(info)?)
+      .map_err(|_| self.error(ErrorKind::OutOfRange))
+  }
+
+  /// Reads the `len` bytes of a text string.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the slice is not long enough or the string is not
+  /// valid UTF-8
+  pub(crate) fn read_str(&mut self, len: usize) -> Result<&'a str, Error> {
+    let start = self.pos;
+    core::str::from_utf8(self.take(len)?)
+      .map_err(|_| Error::new(ErrorKind::InvalidUtf8).at(start as u64))
+  }
+
+  /// Reads a link, after its tag.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the slice is not long enough, the cbor tag is not
+  /// `0x58`, the len is `0`, `bytes[0]` is not `0`, or if the bytes are not
+  /// a valid Cid
+  pub(crate) fn read_link(&mut self) -> Result<Cid, Error> {
+    let ty = self.read_u8()?;
+    if ty != 0x58 {
+      return Err(self.unexpected(ty));
+    }
+    let len = self.read_u8()?;
+    let start = self.pos as u64;
+    if len == 0 {
+      return Err(invalid_cid("empty bytes").at(start));
+    }
+    let bytes = self.take(len as usize)?;
+    if bytes[0] != 0 {
+      return Err(invalid_cid(format!("prefix {}", bytes[0])).at(start));
+    }
+
+    // skip the first byte per
+    // https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links
+    Cid::try_from(&bytes[1..])
+      .map_err(|x| invalid_cid(x.to_string()).at(start))
+  }
+
+  /// Enters a list or map of `len` elements, or of indefinite length if
+  /// `len` is `None`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if this exceeds the limits of `budget`
+  fn enter(
+    &self,
+    len: Option<usize>,
+    budget: &mut DecodeBudget,
+  ) -> Result<(), Error> {
+    budget.enter().map_err(|e| self.limit(e))?;
+    budget.check_len(len.unwrap_or(0)).map_err(|e| self.limit(e))
+  }
+
+  /// Returns whether another element follows, like `has_next`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the end of an indefinite length container could not
+  /// be found
+  fn has_next(&mut self, len: &mut Option<usize>) -> Result<bool, Error> {
+    match len {
+      Some(0) => Ok(false),
+      Some(len) => {
+        *len -= 1;
+        Ok(true)
+      }
+      None if self.peek_u8()? == 0xff => {
+        self.pos += 1;
+        Ok(false)
+      }
+      None => Ok(true),
+    }
+  }
 }
 
 /// # Errors
 ///
-/// Will return `Err` if the `ByteCursor` has less than 2 available bytes to
-/// read
-fn read_u16(r: &mut ByteCursor) -> Result<u16, String> {
-  let mut buf = [0; 2];
-  r.read_exact(&mut buf)?;
-  Ok(BigEndian::read_u16(&buf))
+/// Will return `Err` if the slice has less than 2 available bytes to read
+fn read_u16(r: &mut SliceCursor) -> Result<u16, Error> {
+  Ok(BigEndian::read_u16(r.take(2)?))
 }
 
 /// # Errors
 ///
-/// Will return `Err` if the `ByteCursor` has less than 4 available bytes to
-/// read
-fn read_u32(r: &mut ByteCursor) -> Result<u32, String> {
-  let mut buf = [0; 4];
-  r.read_exact(&mut buf)?;
-  Ok(BigEndian::read_u32(&buf))
+/// Will return `Err` if the slice has less than 4 available bytes to read
+fn read_u32(r: &mut SliceCursor) -> Result<u32, Error> {
+  Ok(BigEndian::read_u32(r.take(4)?))
 }
 
 /// # Errors
 ///
-/// Will return `Err` if the `ByteCursor` has less than 8 available bytes to
-/// read
-fn read_u64(r: &mut ByteCursor) -> Result<u64, String> {
-  let mut buf = [0; 8];
-  r.read_exact(&mut buf)?;
-  Ok(BigEndian::read_u64(&buf))
+/// Will return `Err` if the slice has less than 8 available bytes to read
+fn read_u64(r: &mut SliceCursor) -> Result<u64, Error> {
+  Ok(BigEndian::read_u64(r.take(8)?))
 }
 
 /// # Errors
 ///
-/// Will return `Err` if the `ByteCursor` has less than 4 available bytes to
-/// read
-fn read_f32(r: &mut ByteCursor) -> Result<f32, String> {
-  let mut buf = [0; 4];
-  r.read_exact(&mut buf)?;
-  Ok(BigEndian::read_f32(&buf))
+/// Will return `Err` if the slice has less than 4 available bytes to read
+fn read_f32(r: &mut SliceCursor) -> Result<f32, Error> {
+  Ok(BigEndian::read_f32(r.take(4)?))
 }
 
 /// # Errors
 ///
-/// Will return `Err` if the `ByteCursor` has less than 8 available bytes to
-/// read
-fn read_f64(r: &mut ByteCursor) -> Result<f64, String> {
-  let mut buf = [0; 8];
-  r.read_exact(&mut buf)?;
-  Ok(BigEndian::read_f64(&buf))
+/// Will return `Err` if the slice has less than 8 available bytes to read
+fn read_f64(r: &mut SliceCursor) -> Result<f64, Error> {
+  Ok(BigEndian::read_f64(r.take(8)?))
 }
 
+/// Reads `len` bytes into a new vector. The bytes are taken before anything
+/// is allocated, so that a bogus length cannot make us allocate more than
+/// the input could contain.
+///
 /// # Errors
 ///
-/// Will return `Err` if the `ByteCursor` has less than `len` available bytes
-/// to read
-fn read_bytes(r: &mut ByteCursor, len: usize) -> Result<Vec<u8>, String> {
-  let mut buf = vec![0; len];
-  r.read_exact(&mut buf)?;
-  Ok(buf)
+/// Will return `Err` if the slice has less than `len` available bytes to
+/// read
+fn read_bytes(r: &mut SliceCursor, len: usize) -> Result<Vec<u8>, Error> {
+  Ok(r.take(len)?.to_vec())
 }
 
 /// # Errors
 ///
-/// Will return `Err` if the `ByteCursor` has less than `len` available bytes
-/// to read or the bytes read are not valid UTF-8
-fn read_str(r: &mut ByteCursor, len: usize) -> Result<String, String> {
-  let bytes = read_bytes(r, len)?;
-  String::from_utf8(bytes).map_err(|_| "Error converting to UTF-8".to_owned())
-}
-
-/// Will return `Err` if there were any errors decoding `len` objects
-fn read_list<T: Decode<DagCborCodec>>(
-  r: &mut ByteCursor,
-  len: usize,
-) -> Result<Vec<T>, String> {
-  let mut list: Vec<T> = Vec::with_capacity(len);
-  for _ in 0..len {
-    list.push(T::decode(DagCborCodec, r)?);
-  }
-  Ok(list)
+/// Will return `Err` if the slice has less than `len` available bytes to
+/// read or the bytes read are not valid UTF-8
+fn read_str(r: &mut SliceCursor, len: usize) -> Result<String, Error> {
+  r.read_str(len).map(String::from)
 }
 
+/// Reads a list with `len` elements, or of indefinite length if `len` is
+/// `None`.
+///
 /// # Errors
 ///
-/// Will return `Err` if there were errors reading the major value, seeking
-/// back, or decoding the component objects
-fn read_list_il<T: Decode<DagCborCodec>>(
-  r: &mut ByteCursor,
-) -> Result<Vec<T>, String> {
-  let mut list: Vec<T> = Vec::new();
-  loop {
-    let major = read_u8(r)?;
-    if major == 0xff {
-      break;
-    }
-    r.seek(&SeekFrom::Current(-1))?;
-    let value = T::decode(DagCborCodec, r)?;
+/// Will return `Err` if there were any errors decoding the elements, if the
+/// end of an indefinite length list could not be found, or if decoding
+/// exceeded the limits of `budget`
+fn read_list<T: Decode<DagCborCodec>>(
+  r: &mut SliceCursor,
+  mut len: Option<usize>,
+  budget: &mut DecodeBudget,
+) -> Result<Vec<T>, Error> {
+  r.enter(len, budget)?;
+  // Every element takes at least one byte of input.
+  let mut list: Vec<T> =
+    Vec::with_capacity(len.unwrap_or(0).min(r.remaining()));
+  while has_next(r, &mut len)? {
+    budget.check_len(list.len() + 1).map_err(|e| r.limit(e))?;
+    budget.alloc(mem::size_of::<T>()).map_err(|e| r.limit(e))?;
+    let value = T::decode_limited(DagCborCodec, r, budget)
+      .map_err(|e| e.within(list.len().to_string()))?;
     list.push(value);
   }
+  budget.exit();
   Ok(list)
 }
 
+/// Reads a map with `len` entries, or of indefinite length if `len` is
+/// `None`. `segment` returns the path segment of a key, if it has one.
+///
 /// # Errors
 ///
-/// Will return `Err` if there were any errors decoding `len` key-value pairs
-/// of objects
+/// Will return `Err` if there were any errors decoding the key-value pairs,
+/// if the end of an indefinite length map could not be found, or if decoding
+/// exceeded the limits of `budget`
 fn read_map<K: Decode<DagCborCodec> + Ord, T: Decode<DagCborCodec>>(
-  r: &mut ByteCursor,
-  len: usize,
-) -> Result<BTreeMap<K, T>, String> {
+  r: &mut SliceCursor,
+  mut len: Option<usize>,
+  budget: &mut DecodeBudget,
+  segment: impl Fn(&K) -> Option<String>,
+) -> Result<BTreeMap<K, T>, Error> {
+  r.enter(len, budget)?;
   let mut map: BTreeMap<K, T> = BTreeMap::new();
-  for _ in 0..len {
-    let key = K::decode(DagCborCodec, r)?;
-    let value = T::decode(DagCborCodec, r)?;
+  let mut read = 0;
+  while has_next(r, &mut len)? {
+    read += 1;
+    budget.check_len(read).map_err(|e| r.limit(e))?;
+    budget.alloc(mem::size_of::<(K, T)>()).map_err(|e| r.limit(e))?;
+    let key = K::decode_limited(DagCborCodec, r, budget)?;
+    let value = T::decode_limited(DagCborCodec, r, budget).map_err(|e| {
+      match segment(&key) {
+        Some(segment) => e.within(segment),
+        None => e,
+      }
+    })?;
     map.insert(key, value);
   }
+  budget.exit();
   Ok(map)
 }
 
+/// Returns an `ErrorKind::InvalidCid` error with the given reason.
+fn invalid_cid<S: Into<String>>(reason: S) -> Error {
+  Error::new(ErrorKind::InvalidCid(reason.into()))
+}
+
+/// Reads an integer of either sign.
+///
 /// # Errors
 ///
-/// Will return `Err` if there was an error reading the major value, seeking
-/// backward, or decoding the component key-value pairs of objects
-fn read_map_il<K: Decode<DagCborCodec> + Ord, T: Decode<DagCborCodec>>(
-  r: &mut ByteCursor,
-) -> Result<BTreeMap<K, T>, String> {
-  let mut map: BTreeMap<K, T> = BTreeMap::new();
-  loop {
-    let major = read_u8(r)?;
-    if major == 0xff {
-      break;
-    }
-    r.seek(&SeekFrom::Current(-1))?;
-    let key = K::decode(DagCborCodec, r)?;
-    let value = T::decode(DagCborCodec, r)?;
-    map.insert(key, value);
+/// Will return `Err` if the next value is not an integer or the slice is not
+/// long enough
+#[doc(hidden)]
+pub fn read_int(r: &mut SliceCursor) -> Result<i128, Error> {
+  let major = r.read_u8()?;
+  match major {
+    0x00..=0x1b => Ok(i128::from(r.read_uint(major)?)),
+    0x20..=0x3b => Ok(-1 - i128::from(r.read_uint(major - 0x20)?)),
+    _ => Err(r.unexpected(major)),
   }
-  Ok(map)
 }
 
+/// Reads a null.
+///
 /// # Errors
 ///
-/// Will return `Err` if the `ByteCursor` is not long enough, the cbor tag is
-/// not `0x58`, the len is `0`, `bytes[0]` is not `0`, or if the bytes are not
-/// a valid Cid
-fn read_link(r: &mut ByteCursor) -> Result<Cid, String> {
-  let ty = read_u8(r)?;
-  if ty != 0x58 {
-    return Err(format!("Unknown cbor tag `{}`", ty));
+/// Will return `Err` if the next value is not null
+#[doc(hidden)]
+pub fn read_null(r: &mut SliceCursor) -> Result<(), Error> {
+  let major = r.read_u8()?;
+  match major {
+    0xf6 | 0xf7 => Ok(()),
+    _ => Err(r.unexpected(major)),
   }
-  let len = read_u8(r)?;
-  if len == 0 {
-    return Err("Length out of range when decoding Cid.".to_owned());
+}
+
+/// Reads the header of a list, returning its length or `None` if it has
+/// indefinite length.
+///
+/// # Errors
+///
+/// Will return `Err` if the next value is not a list
+#[doc(hidden)]
+pub fn read_list_len(r: &mut SliceCursor) -> Result<Option<usize>, Error> {
+  let major = r.read_u8()?;
+  match major {
+    0x80..=0x9b => Ok(Some(r.read_len(major - 0x80)?)),
+    0x9f => Ok(None),
+    _ => Err(r.unexpected(major)),
   }
-  let bytes = read_bytes(r, len as usize)?;
-  if bytes[0] != 0 {
-    return Err(format!("Invalid Cid prefix: {}", bytes[0]));
+}
+
+/// Reads the header of a map, returning its length or `None` if it has
+/// indefinite length.
+///
+/// # Errors
+///
+/// Will return `Err` if the next value is not a map
+#[doc(hidden)]
+pub fn read_map_len(r: &mut SliceCursor) -> Result<Option<usize>, Error> {
+  let major = r.read_u8()?;
+  match major {
+    0xa0..=0xbb => Ok(Some(r.read_len(major - 0xa0)?)),
+    0xbf => Ok(None),
+    _ => Err(r.unexpected(major)),
   }
+}
 
-  // skip the first byte per
-  // https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links
-  Cid::try_from(&bytes[1..]).map_err(|x| x.to_string())
+/// Returns whether another element follows in a list or map whose header was
+/// read with `read_list_len` or `read_map_len`, consuming the break code at
+/// the end of an indefinite length container.
+///
+/// # Errors
+///
+/// Will return `Err` if the end of an indefinite length container could not
+/// be found
+#[doc(hidden)]
+pub fn has_next(
+  r: &mut SliceCursor,
+  len: &mut Option<usize>,
+) -> Result<bool, Error> {
+  r.has_next(len)
 }
 
 /// # Errors
 ///
-/// Will return `Err` if the major value is unknown or decoding a usize which
-/// is greater than `u64::MAX`
-fn read_len(r: &mut ByteCursor, major: u8) -> Result<usize, String> {
-  Ok(match major {
-    0x00..=0x17 => major as usize,
-    0x18 => read_u8(r)? as usize,
-    0x19 => read_u16(r)? as usize,
-    0x1a => read_u32(r)? as usize,
-    0x1b => {
-      let len = read_u64(r)?;
-      if len > usize::max_value() as u64 {
-        return Err("Length out of range when decoding usize.".to_owned());
-      }
-      len as usize // may truncate
-    }
-    major => {
-      return Err(format!(
-        "Unexpected cbor code `0x{}` when decoding usize.",
-        major
-      ));
-    }
-  })
+/// Will return `Err` if there were any errors decoding the key-value pairs of
+/// the map, or if `len` is `None` and the end of the map could not be found
+#[cfg(not(feature = "unleashed"))]
+fn read_ipld_map(
+  r: &mut SliceCursor,
+  len: Option<usize>,
+  budget: &mut DecodeBudget,
+) -> Result<Ipld, Error> {
+  let map = read_map(r, len, budget, |key: &String| Some(key.clone()))?;
+  Ok(Ipld::StringMap(map))
+}
+
+/// Reads an `Ipld::IntegerMap` if the first key is an integer, or an
+/// `Ipld::StringMap` otherwise.
+///
+/// # Errors
+///
+/// Will return `Err` if there were any errors decoding the key-value pairs of
+/// the map, if the keys are of mixed types, or if `len` is `None` and the end
+/// of the map could not be found
+#[cfg(feature = "unleashed")]
+fn read_ipld_map(
+  r: &mut SliceCursor,
+  mut len: Option<usize>,
+  budget: &mut DecodeBudget,
+) -> Result<Ipld, Error> {
+  if len == Some(0) {
+    return Ok(Ipld::StringMap(BTreeMap::new()));
+  }
+  if !matches!(r.peek_u8()?, 0x00..=0x1b | 0x20..=0x3b) {
+    let map = read_map(r, len, budget, |key: &String| Some(key.clone()))?;
+    return Ok(Ipld::StringMap(map));
+  }
+  r.enter(len, budget)?;
+  let mut map = BTreeMap::new();
+  let mut read = 0;
+  while has_next(r, &mut len)? {
+    read += 1;
+    budget.check_len(read).map_err(|e| r.limit(e))?;
+    budget.alloc(mem::size_of::<(i64, Ipld)>()).map_err(|e| r.limit(e))?;
+    let key = match Ipld::decode_limited(DagCborCodec, r, budget)? {
+      Ipld::Integer(key) => i64::try_from(key)
+        .map_err(|_| r.error(ErrorKind::OutOfRange))?,
+      key => {
+        return Err(r.error(ErrorKind::WrongKind {
+          expected: IpldKind::Integer,
+          found: key.kind(),
+        }));
+      }
+    };
+    let value = Ipld::decode_limited(DagCborCodec, r, budget)
+      .map_err(|e| e.within(key.to_string()))?;
+    map.insert(key, value);
+  }
+  budget.exit();
+  Ok(Ipld::IntegerMap(map))
+}
+
+/// Reads the value tagged with the CBOR semantic tag `tag`.
+///
+/// # Errors
+///
+/// Will return `Err` if the tagged value could not be decoded
+#[cfg(feature = "unleashed")]
+fn read_tag(
+  r: &mut SliceCursor,
+  tag: u64,
+  budget: &mut DecodeBudget,
+) -> Result<Ipld, Error> {
+  budget.enter().map_err(|e| r.limit(e))?;
+  budget.alloc(mem::size_of::<Ipld>()).map_err(|e| r.limit(e))?;
+  let ipld = Ipld::decode_limited(DagCborCodec, r, budget)?;
+  budget.exit();
+  Ok(Ipld::Tag(tag, Box::new(ipld)))
+}
+
+/// # Errors
+///
+/// Will always return `Err`, since tags other than links can only be decoded
+/// with the `unleashed` feature
+#[cfg(not(feature = "unleashed"))]
+fn read_tag(
+  r: &mut SliceCursor,
+  tag: u64,
+  _: &mut DecodeBudget,
+) -> Result<Ipld, Error> {
+  Err(r.error(ErrorKind::UnknownTag(tag)))
 }
 impl Decode<DagCborCodec> for bool {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0xf4 => false,
       0xf5 => true,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding bool.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl Decode<DagCborCodec> for u8 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0x00..=0x17 => major,
-      0x18 => read_u8(r)?,
+      0x18 => r.read_u8()?,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding u8.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl Decode<DagCborCodec> for u16 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0x00..=0x17 => Self::from(major),
-      0x18 => Self::from(read_u8(r)?),
+      0x18 => Self::from(r.read_u8()?),
       0x19 => read_u16(r)?,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding u16.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl Decode<DagCborCodec> for u32 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0x00..=0x17 => Self::from(major),
-      0x18 => Self::from(read_u8(r)?),
+      0x18 => Self::from(r.read_u8()?),
       0x19 => Self::from(read_u16(r)?),
       0x1a => read_u32(r)?,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding u32.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl Decode<DagCborCodec> for u64 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0x00..=0x17 => Self::from(major),
-      0x18 => Self::from(read_u8(r)?),
+      0x18 => Self::from(r.read_u8()?),
       0x19 => Self::from(read_u16(r)?),
       0x1a => Self::from(read_u32(r)?),
       0x1b => read_u64(r)?,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding u64.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl Decode<DagCborCodec> for i8 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
-    let result = match major {
-      0x20..=0x37 => -1 - (major - 0x20) as Self, // may wrap
-      0x38 => -1 - read_u8(r)? as Self,           // may wrap
-      _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding i8.",
-          major
-        ));
-      }
-    };
-    Ok(result)
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let int = read_int(r)?;
+    Self::try_from(int)
+      .map_err(|_| r.error(ErrorKind::OutOfRange))
   }
 }
 impl Decode<DagCborCodec> for i16 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
-    let result = match major {
-      0x20..=0x37 => -1 - Self::from(major - 0x20),
-      0x38 => -1 - Self::from(read_u8(r)?),
-      0x39 => -1 - read_u16(r)? as Self, // may wrap
-      _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding i16.",
-          major
-        ));
-      }
-    };
-    Ok(result)
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let int = read_int(r)?;
+    Self::try_from(int)
+      .map_err(|_| r.error(ErrorKind::OutOfRange))
   }
 }
 impl Decode<DagCborCodec> for i32 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
-    let result = match major {
-      0x20..=0x37 => -1 - Self::from(major - 0x20),
-      0x38 => -1 - Self::from(read_u8(r)?),
-      0x39 => -1 - Self::from(read_u16(r)?),
-      0x3a => -1 - read_u32(r)? as Self, // may wrap
-      _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding i32.",
-          major
-        ));
-      }
-    };
-    Ok(result)
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let int = read_int(r)?;
+    Self::try_from(int)
+      .map_err(|_| r.error(ErrorKind::OutOfRange))
   }
 }
 impl Decode<DagCborCodec> for i64 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
-    let result = match major {
-      0x20..=0x37 => -1 - Self::from(major - 0x20),
-      0x38 => -1 - Self::from(read_u8(r)?),
-      0x39 => -1 - Self::from(read_u16(r)?),
-      0x3a => -1 - Self::from(read_u32(r)?),
-      0x3b => -1 - read_u64(r)? as Self, // may wrap
-      _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding i64.",
-          major
-        ));
-      }
-    };
-    Ok(result)
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let int = read_int(r)?;
+    Self::try_from(int)
+      .map_err(|_| r.error(ErrorKind::OutOfRange))
+  }
+}
+impl Decode<DagCborCodec> for i128 {
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    read_int(r)
   }
 }
 impl Decode<DagCborCodec> for f32 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0xfa => read_f32(r)?,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding f32.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl Decode<DagCborCodec> for f64 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0xfa => Self::from(read_f32(r)?),
       0xfb => read_f64(r)?,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding f64.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
@@ -422,82 +613,101 @@ impl Decode<DagCborCodec> for f64 {
 }
 
 impl Decode<DagCborCodec> for String {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    _: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0x60..=0x7b => {
-        let len = read_len(r, major - 0x60)?;
+        let len = r.read_len(major - 0x60)?;
+        budget.alloc_bytes(len).map_err(|e| r.limit(e))?;
         read_str(r, len)?
       }
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding String.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl Decode<DagCborCodec> for Cid {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
-    if major == 0xd8 {
-      if let Ok(tag) = read_u8(r) {
-        if tag == 42 {
-          return read_link(r);
-        }
-      }
+  fn decode(_: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
+    if major == 0xd8 && r.read_tag(major)? == 42 {
+      return r.read_link();
     }
-    Err(format!("Unexpected cbor code `0x{}` when decoding Cid.", major))
+    Err(r.unexpected(major))
   }
 }
 impl Decode<DagCborCodec> for Box<[u8]> {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    _: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0x40..=0x5b => {
-        let len = read_len(r, major - 0x40)?;
+        let len = r.read_len(major - 0x40)?;
+        budget.alloc_bytes(len).map_err(|e| r.limit(e))?;
         read_bytes(r, len)?.into_boxed_slice()
       }
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding Box<[u8]>.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl<T: Decode<DagCborCodec>> Decode<DagCborCodec> for Option<T> {
-  fn decode(c: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
-    let result = match major {
-      0xf6 | 0xf7 => None,
-      _ => {
-        r.seek(&SeekFrom::Current(-1))?;
-        Some(T::decode(c, r)?)
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    c: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let result = match r.peek_u8()? {
+      0xf6 | 0xf7 => {
+        r.take(1)?;
+        None
       }
+      _ => Some(T::decode_limited(c, r, budget)?),
     };
     Ok(result)
   }
 }
 impl<T: Decode<DagCborCodec>> Decode<DagCborCodec> for Vec<T> {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    _: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0x80..=0x9b => {
-        let len = read_len(r, major - 0x80)?;
-        read_list(r, len)?
+        let len = r.read_len(major - 0x80)?;
+        read_list(r, Some(len), budget)?
       }
-      0x9f => read_list_il(r)?,
+      0x9f => read_list(r, None, budget)?,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding Vec<{}>.",
-          major,
-          type_name::<T>()
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
@@ -506,92 +716,97 @@ impl<T: Decode<DagCborCodec>> Decode<DagCborCodec> for Vec<T> {
 impl<K: Decode<DagCborCodec> + Ord, T: Decode<DagCborCodec>>
   Decode<DagCborCodec> for BTreeMap<K, T>
 {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    _: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
       0xa0..=0xbb => {
-        let len = read_len(r, major - 0xa0)?;
-        read_map(r, len)?
+        let len = r.read_len(major - 0xa0)?;
+        read_map(r, Some(len), budget, |_| None)?
       }
-      0xbf => read_map_il(r)?,
+      0xbf => read_map(r, None, budget, |_| None)?,
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding BTreeMap<{}, {}>.",
-          major,
-          type_name::<K>(),
-          type_name::<T>()
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl Decode<DagCborCodec> for Ipld {
-  fn decode(_: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    _: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let ipld = match major {
       // Major type 0: an unsigned integer
       0x00..=0x17 => Self::Integer(i128::from(major)),
-      0x18 => Self::Integer(i128::from(read_u8(r)?)),
+      0x18 => Self::Integer(i128::from(r.read_u8()?)),
       0x19 => Self::Integer(i128::from(read_u16(r)?)),
       0x1a => Self::Integer(i128::from(read_u32(r)?)),
       0x1b => Self::Integer(i128::from(read_u64(r)?)),
 
       // Major type 1: a negative integer
       0x20..=0x37 => Self::Integer(-1 - i128::from(major - 0x20)),
-      0x38 => Self::Integer(-1 - i128::from(read_u8(r)?)),
+      0x38 => Self::Integer(-1 - i128::from(r.read_u8()?)),
       0x39 => Self::Integer(-1 - i128::from(read_u16(r)?)),
       0x3a => Self::Integer(-1 - i128::from(read_u32(r)?)),
       0x3b => Self::Integer(-1 - i128::from(read_u64(r)?)),
 
       // Major type 2: a byte string
       0x40..=0x5b => {
-        let len = read_len(r, major - 0x40)?;
-        let bytes = read_bytes(r, len as usize)?;
+        let len = r.read_len(major - 0x40)?;
+        budget.alloc_bytes(len).map_err(|e| r.limit(e))?;
+        let bytes = read_bytes(r, len)?;
         Self::Bytes(bytes)
       }
 
       // Major type 3: a text string
       0x60..=0x7b => {
-        let len = read_len(r, major - 0x60)?;
-        let string = read_str(r, len as usize)?;
+        let len = r.read_len(major - 0x60)?;
+        budget.alloc_bytes(len).map_err(|e| r.limit(e))?;
+        let string = read_str(r, len)?;
         Self::String(string)
       }
 
       // Major type 4: an array of data items
       0x80..=0x9b => {
-        let len = read_len(r, major - 0x80)?;
-        let list = read_list(r, len as usize)?;
-        Self::List(list)
+        let len = r.read_len(major - 0x80)?;
+        Self::List(read_list(r, Some(len), budget)?)
       }
 
       // Major type 4: an array of data items (indefinite length)
-      0x9f => {
-        let list = read_list_il(r)?;
-        Self::List(list)
-      }
+      0x9f => Self::List(read_list(r, None, budget)?),
 
       // Major type 5: a map of pairs of data items
       0xa0..=0xbb => {
-        let len = read_len(r, major - 0xa0)?;
-        Self::StringMap(read_map(r, len as usize)?)
+        let len = r.read_len(major - 0xa0)?;
+        read_ipld_map(r, Some(len), budget)?
       }
 
       // Major type 5: a map of pairs of data items (indefinite length)
-      0xbf => {
-        let pos = r.seek(&SeekFrom::Current(0))?;
-        r.seek(&SeekFrom::Start(pos))?;
-        Self::StringMap(read_map_il(r)?)
-      }
+      0xbf => read_ipld_map(r, None, budget)?,
 
       // Major type 6: optional semantic tagging of other major types
-      0xd8 => {
-        let tag = read_u8(r)?;
+      0xc0..=0xdb => {
+        let tag = r.read_tag(major)?;
         if tag == 42 {
-          Self::Link(read_link(r)?)
+          Self::Link(r.read_link()?)
         }
         else {
-          return Err(format!("Unknown cbor tag `{}`", tag));
+          read_tag(r, tag, budget)?
         }
       }
 
@@ -603,141 +818,176 @@ impl Decode<DagCborCodec> for Ipld {
       0xfa => Self::Float(f64::from(read_f32(r)?)),
       0xfb => Self::Float(read_f64(r)?),
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding Ipld.",
-          major,
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(ipld)
   }
 }
 
+/// Calls `visit` `width` times for every element of a list or entry of a map
+/// with `len` elements, or of indefinite length if `len` is `None`, whose
+/// header was just read. A tagged value is visited as a single element.
+///
+/// # Errors
+///
+/// Will return `Err` if `visit` does, if the end of an indefinite length
+/// container could not be found, or if visiting exceeded the limits of
+/// `budget`
+fn visit_items(
+  r: &mut SliceCursor,
+  mut len: Option<usize>,
+  width: usize,
+  budget: &mut DecodeBudget,
+  mut visit: impl FnMut(
+    &mut SliceCursor,
+    &mut DecodeBudget,
+  ) -> Result<(), Error>,
+) -> Result<(), Error> {
+  r.enter(len, budget)?;
+  let mut read = 0;
+  while has_next(r, &mut len)? {
+    read += 1;
+    budget.check_len(read).map_err(|e| r.limit(e))?;
+    for _ in 0..width {
+      visit(r, budget)?;
+    }
+  }
+  budget.exit();
+  Ok(())
+}
+
 impl References<DagCborCodec> for Ipld {
   fn references<E: Extend<Cid>>(
     c: DagCborCodec,
-    r: &mut ByteCursor,
+    r: &mut SliceCursor,
+    set: &mut E,
+  ) -> Result<(), Error> {
+    Self::references_limited(c, r, set, &mut DecodeBudget::default())
+  }
+
+  fn references_limited<E: Extend<Cid>>(
+    c: DagCborCodec,
+    r: &mut SliceCursor,
     set: &mut E,
-  ) -> Result<(), String> {
-    let major = read_u8(r)?;
+    budget: &mut DecodeBudget,
+  ) -> Result<(), Error> {
+    let major = r.read_u8()?;
     match major {
       0x00..=0x17 | 0x20..=0x37 | 0xf4..=0xf7 => {}
 
       0x18 | 0x38 | 0xf8 => {
-        r.seek(&SeekFrom::Current(1))?;
+        r.take(1)?;
       }
       0x19 | 0x39 | 0xf9 => {
-        r.seek(&SeekFrom::Current(2))?;
+        r.take(2)?;
       }
       0x1a | 0x3a | 0xfa => {
-        r.seek(&SeekFrom::Current(4))?;
+        r.take(4)?;
       }
       0x1b | 0x3b | 0xfb => {
-        r.seek(&SeekFrom::Current(8))?;
+        r.take(8)?;
       }
 
       // Major type 2: a byte string
       0x40..=0x5b => {
-        let len = read_len(r, major - 0x40)?;
-        r.seek(&SeekFrom::Current(len as _))?;
+        let len = r.read_len(major - 0x40)?;
+        budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+        r.take(len)?;
       }
 
       // Major type 3: a text string
       0x60..=0x7b => {
-        let len = read_len(r, major - 0x60)?;
-        r.seek(&SeekFrom::Current(len as _))?;
+        let len = r.read_len(major - 0x60)?;
+        budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+        r.take(len)?;
       }
 
       // Major type 4: an array of data items
       0x80..=0x9b => {
-        let len = read_len(r, major - 0x80)?;
-        for _ in 0..len {
-          <Self as References<DagCborCodec>>::references(c, r, set)?;
-        }
+        let len = r.read_len(major - 0x80)?;
+        visit_items(r, Some(len), 1, budget, |r, budget| {
+          Self::references_limited(c, r, set, budget)
+        })?;
       }
 
       // Major type 4: an array of data items (indefinite length)
-      0x9f => loop {
-        let major = read_u8(r)?;
-        if major == 0xff {
-          break;
-        }
-        r.seek(&SeekFrom::Current(-1))?;
-        <Self as References<DagCborCodec>>::references(c, r, set)?;
-      },
+      0x9f => visit_items(r, None, 1, budget, |r, budget| {
+          Self::references_limited(c, r, set, budget)
+        })?,
 
       // Major type 5: a map of pairs of data items
       0xa0..=0xbb => {
-        let len = read_len(r, major - 0xa0)?;
-        for _ in 0..len {
-          <Self as References<DagCborCodec>>::references(c, r, set)?;
-          <Self as References<DagCborCodec>>::references(c, r, set)?;
-        }
+        let len = r.read_len(major - 0xa0)?;
+        visit_items(r, Some(len), 2, budget, |r, budget| {
+          Self::references_limited(c, r, set, budget)
+        })?;
       }
 
       // Major type 5: a map of pairs of data items (indefinite length)
-      0xbf => loop {
-        let major = read_u8(r)?;
-        if major == 0xff {
-          break;
-        }
-        r.seek(&SeekFrom::Current(-1))?;
-        <Self as References<DagCborCodec>>::references(c, r, set)?;
-        <Self as References<DagCborCodec>>::references(c, r, set)?;
-      },
+      0xbf => visit_items(r, None, 2, budget, |r, budget| {
+          Self::references_limited(c, r, set, budget)
+        })?,
 
       // Major type 6: optional semantic tagging of other major types
-      0xd8 => {
-        let tag = read_u8(r)?;
-        if tag == 42 {
-          set.extend(core::iter::once(read_link(r)?));
+      0xc0..=0xdb => {
+        if r.read_tag(major)? == 42 {
+          set.extend(core::iter::once(r.read_link()?));
         }
         else {
-          <Self as References<DagCborCodec>>::references(c, r, set)?;
+          visit_items(r, Some(1), 1, budget, |r, budget| {
+            Self::references_limited(c, r, set, budget)
+          })?;
         }
       }
 
       major => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding Ipld.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(())
   }
 }
 impl<T: Decode<DagCborCodec>> Decode<DagCborCodec> for Arc<T> {
-  fn decode(c: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    Ok(Self::new(T::decode(c, r)?))
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    c: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    Ok(Self::new(T::decode_limited(c, r, budget)?))
   }
 }
 impl Decode<DagCborCodec> for () {
-  fn decode(_c: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(_c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     match major {
       0x80 => {}
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding ().",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(())
   }
 }
 impl<A: Decode<DagCborCodec>> Decode<DagCborCodec> for (A,) {
-  fn decode(c: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    c: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
-      0x81 => (A::decode(c, r)?,),
+      0x81 => (A::decode_limited(c, r, budget)?,),
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding {}.",
-          major,
-          type_name::<Self>()
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
@@ -746,16 +996,22 @@ impl<A: Decode<DagCborCodec>> Decode<DagCborCodec> for (A,) {
 impl<A: Decode<DagCborCodec>, B: Decode<DagCborCodec>> Decode<DagCborCodec>
   for (A, B)
 {
-  fn decode(c: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    c: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
-      0x82 => (A::decode(c, r)?, B::decode(c, r)?),
+      0x82 => {
+        (A::decode_limited(c, r, budget)?, B::decode_limited(c, r, budget)?)
+      }
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding {}.",
-          major,
-          type_name::<Self>()
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
@@ -764,16 +1020,24 @@ impl<A: Decode<DagCborCodec>, B: Decode<DagCborCodec>> Decode<DagCborCodec>
 impl<A: Decode<DagCborCodec>, B: Decode<DagCborCodec>, C: Decode<DagCborCodec>>
   Decode<DagCborCodec> for (A, B, C)
 {
-  fn decode(c: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    c: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
-      0x83 => (A::decode(c, r)?, B::decode(c, r)?, C::decode(c, r)?),
+      0x83 => (
+        A::decode_limited(c, r, budget)?,
+        B::decode_limited(c, r, budget)?,
+        C::decode_limited(c, r, budget)?,
+      ),
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding {}.",
-          major,
-          type_name::<Self>()
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
@@ -786,105 +1050,423 @@ impl<
   D: Decode<DagCborCodec>,
 > Decode<DagCborCodec> for (A, B, C, D)
 {
-  fn decode(c: DagCborCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    let major = read_u8(r)?;
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    c: DagCborCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    let major = r.read_u8()?;
     let result = match major {
-      0x84 => {
-        (A::decode(c, r)?, B::decode(c, r)?, C::decode(c, r)?, D::decode(c, r)?)
-      }
+      0x84 => (
+        A::decode_limited(c, r, budget)?,
+        B::decode_limited(c, r, budget)?,
+        C::decode_limited(c, r, budget)?,
+        D::decode_limited(c, r, budget)?,
+      ),
       _ => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding {}.",
-          major,
-          type_name::<Self>()
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(result)
   }
 }
 impl SkipOne for DagCborCodec {
-  fn skip(&self, r: &mut ByteCursor) -> Result<(), String> {
-    let major = read_u8(r)?;
+  fn skip(&self, r: &mut SliceCursor) -> Result<(), Error> {
+    self.skip_limited(r, &mut DecodeBudget::default())
+  }
+
+  fn skip_limited(
+    &self,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<(), Error> {
+    let major = r.read_u8()?;
     match major {
       // Major type 0: an unsigned integer
       0x00..=0x17 | 0x20..=0x37 | 0xf4..=0xf7 => {}
       0x18 | 0x38 | 0xf8 => {
-        r.seek(&SeekFrom::Current(1))?;
+        r.take(1)?;
       }
       0x19 | 0x39 | 0xf9 => {
-        r.seek(&SeekFrom::Current(2))?;
+        r.take(2)?;
       }
       0x1a | 0x3a | 0xfa => {
-        r.seek(&SeekFrom::Current(4))?;
+        r.take(4)?;
       }
       0x1b | 0x3b | 0xfb => {
-        r.seek(&SeekFrom::Current(8))?;
+        r.take(8)?;
       }
 
       // Major type 2: a byte string
       0x40..=0x5b => {
-        let len = read_len(r, major - 0x40)?;
-        r.seek(&SeekFrom::Current(len as _))?;
+        let len = r.read_len(major - 0x40)?;
+        budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+        r.take(len)?;
       }
 
       // Major type 3: a text string
       0x60..=0x7b => {
-        let len = read_len(r, major - 0x60)?;
-        r.seek(&SeekFrom::Current(len as _))?;
+        let len = r.read_len(major - 0x60)?;
+        budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+        r.take(len)?;
       }
 
       // Major type 4: an array of data items
       0x80..=0x9b => {
-        let len = read_len(r, major - 0x80)?;
-        for _ in 0..len {
-          self.skip(r)?;
-        }
+        let len = r.read_len(major - 0x80)?;
+        visit_items(r, Some(len), 1, budget, |r, budget| {
+          self.skip_limited(r, budget)
+        })?;
       }
 
       // Major type 4: an array of data items (indefinite length)
-      0x9f => loop {
-        let major = read_u8(r)?;
-        if major == 0xff {
-          break;
-        }
-        r.seek(&SeekFrom::Current(-1))?;
-        self.skip(r)?;
-      },
+      0x9f => visit_items(r, None, 1, budget, |r, budget| {
+          self.skip_limited(r, budget)
+        })?,
 
       // Major type 5: a map of pairs of data items
       0xa0..=0xbb => {
-        let len = read_len(r, major - 0xa0)?;
-        for _ in 0..len {
-          self.skip(r)?;
-          self.skip(r)?;
-        }
+        let len = r.read_len(major - 0xa0)?;
+        visit_items(r, Some(len), 2, budget, |r, budget| {
+          self.skip_limited(r, budget)
+        })?;
       }
 
       // Major type 5: a map of pairs of data items (indefinite length)
-      0xbf => loop {
-        let major = read_u8(r)?;
-        if major == 0xff {
-          break;
-        }
-        r.seek(&SeekFrom::Current(-1))?;
-        self.skip(r)?;
-        self.skip(r)?;
-      },
+      0xbf => visit_items(r, None, 2, budget, |r, budget| {
+          self.skip_limited(r, budget)
+        })?,
 
       // Major type 6: optional semantic tagging of other major types
-      0xd8 => {
-        let _tag = read_u8(r)?;
-        self.skip(r)?;
+      0xc0..=0xdb => {
+        r.read_tag(major)?;
+        visit_items(r, Some(1), 1, budget, |r, budget| {
+          self.skip_limited(r, budget)
+        })?;
       }
 
       major => {
-        return Err(format!(
-          "Unexpected cbor code `0x{}` when decoding Ipld.",
-          major
-        ));
+        return Err(r.unexpected(major));
       }
     };
     Ok(())
   }
 }
+
+/// Reads a map key and returns whether it is `segment`, without copying it.
+///
+/// # Errors
+///
+/// Will return `Err` if the next value is not a valid map key
+fn read_key(r: &mut SliceCursor, segment: &str) -> Result<bool, Error> {
+  let major = r.read_u8()?;
+  match major {
+    0x60..=0x7b => {
+      let len = r.read_len(major - 0x60)?;
+      Ok(r.take(len)? == segment.as_bytes())
+    }
+    #[cfg(feature = "unleashed")]
+    0x00..=0x1b | 0x20..=0x3b => {
+      let key = match major {
+        0x00..=0x1b => i128::from(r.read_uint(major)?),
+        _ => -1 - i128::from(r.read_uint(major - 0x20)?),
+      };
+      Ok(segment.parse::<i64>().ok().map(i128::from) == Some(key))
+    }
+    _ => Err(r.unexpected(major)),
+  }
+}
+
+/// Moves `r` from the start of a list or map to the start of its child
+/// `segment`, the segment at `position` in `path`, skipping the children
+/// before it.
+///
+/// # Errors
+///
+/// Will return `Err` if the value is not a list or map, it has no child
+/// `segment`, or a child before it is not valid dag-cbor
+fn follow(
+  r: &mut SliceCursor,
+  path: &IpldPath,
+  position: usize,
+  segment: &str,
+  budget: &mut DecodeBudget,
+) -> Result<(), Error> {
+  let start = r.position();
+  let fail = |kind| {
+    Error::new(ErrorKind::Path(PathError::new(path, position, kind))).at(start)
+  };
+  let major = r.read_u8()?;
+  match major {
+    // Major type 4: an array of data items
+    0x80..=0x9b | 0x9f => {
+      let mut len = match major {
+        0x9f => None,
+        _ => Some(r.read_len(major - 0x80)?),
+      };
+      let index = parse_index(segment).map_err(fail)?;
+      if let Some(len) = len.filter(|len| index >= *len) {
+        return Err(fail(PathErrorKind::IndexOutOfRange { len }));
+      }
+      let mut read = 0;
+      while has_next(r, &mut len)? {
+        if read == index {
+          return Ok(());
+        }
+        DagCborCodec.skip_limited(r, budget)?;
+        read += 1;
+      }
+      Err(fail(PathErrorKind::IndexOutOfRange { len: read }))
+    }
+
+    // Major type 5: a map of pairs of data items
+    0xa0..=0xbb | 0xbf => {
+      let mut len = match major {
+        0xbf => None,
+        _ => Some(r.read_len(major - 0xa0)?),
+      };
+      while has_next(r, &mut len)? {
+        if read_key(r, segment)? {
+          return Ok(());
+        }
+        DagCborCodec.skip_limited(r, budget)?;
+      }
+      Err(fail(PathErrorKind::MissingKey))
+    }
+
+    // Major type 6: a tag is followed through to the value it tags
+    #[cfg(feature = "unleashed")]
+    0xc0..=0xdb => match r.read_tag(major)? {
+      42 => Err(fail(PathErrorKind::NotContainer)),
+      _ => follow(r, path, position, segment, budget),
+    },
+
+    _ => Err(fail(PathErrorKind::NotContainer)),
+  }
+}
+
+/// Decodes the value at `path` within the value at the position of `r`,
+/// within `limits`. Only the target value is decoded: the values beside the
+/// path are skipped without allocating.
+///
+/// # Errors
+///
+/// Will return `Err` if a segment of `path` cannot be followed, a value
+/// before the target is not valid dag-cbor, the target cannot be decoded
+/// into a `T`, or if decoding exceeded `limits`
+pub fn decode_path<T: Decode<DagCborCodec>>(
+  r: &mut SliceCursor,
+  path: &IpldPath,
+  limits: DecodeLimits,
+) -> Result<T, Error> {
+  let segments = path.segments();
+  let within = |e: Error, depth: usize| {
+    let parents = segments[..depth].iter().rev();
+    parents.fold(e, |e, segment| e.within(segment.as_str()))
+  };
+  let mut budget = DecodeBudget::new(limits);
+  for (position, segment) in segments.iter().enumerate() {
+    follow(r, path, position, segment, &mut budget)
+      .map_err(|e| within(e, position))?;
+  }
+  T::decode_limited(DagCborCodec, r, &mut budget)
+    .map_err(|e| within(e, segments.len()))
+}
+
+/// # Errors
+///
+/// Will return `Err` if there were any errors decoding the key-value pairs of
+/// the map, or if `len` is `None` and the end of the map could not be found
+fn read_map_ref<'a>(
+  r: &mut SliceCursor<'a>,
+  mut len: Option<usize>,
+  budget: &mut DecodeBudget,
+) -> Result<IpldRef<'a>, Error> {
+  r.enter(len, budget)?;
+  #[cfg(feature = "unleashed")]
+  {
+    if len != Some(0) && matches!(r.peek_u8()?, 0x00..=0x1b | 0x20..=0x3b) {
+      let mut map = BTreeMap::new();
+      let mut read = 0;
+      while r.has_next(&mut len)? {
+        read += 1;
+        budget.check_len(read).map_err(|e| r.limit(e))?;
+        budget
+          .alloc(mem::size_of::<(i64, IpldRef)>())
+          .map_err(|e| r.limit(e))?;
+        let key = match read_ipld_ref(r, budget)? {
+          IpldRef::Integer(key) => i64::try_from(key)
+            .map_err(|_| r.error(ErrorKind::OutOfRange))?,
+          key => {
+            return Err(r.error(ErrorKind::WrongKind {
+              expected: IpldKind::Integer,
+              found: key.kind(),
+            }));
+          }
+        };
+        let value =
+          read_ipld_ref(r, budget).map_err(|e| e.within(key.to_string()))?;
+        map.insert(key, value);
+      }
+      budget.exit();
+      return Ok(IpldRef::IntegerMap(map));
+    }
+  }
+  let mut map = BTreeMap::new();
+  let mut read = 0;
+  while r.has_next(&mut len)? {
+    read += 1;
+    budget.check_len(read).map_err(|e| r.limit(e))?;
+    budget
+      .alloc(mem::size_of::<(&str, IpldRef)>())
+      .map_err(|e| r.limit(e))?;
+    let major = r.read_u8()?;
+    let key = match major {
+      0x60..=0x7b => {
+        let len = r.read_len(major - 0x60)?;
+        budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+        r.read_str(len)?
+      }
+      _ => {
+        return Err(r.unexpected(major));
+      }
+    };
+    let value = read_ipld_ref(r, budget).map_err(|e| e.within(key))?;
+    map.insert(key, value);
+  }
+  budget.exit();
+  Ok(IpldRef::StringMap(map))
+}
+
+/// Reads the value tagged with the CBOR semantic tag `tag`.
+///
+/// # Errors
+///
+/// Will return `Err` if the tagged value could not be decoded
+#[cfg(feature = "unleashed")]
+fn read_tag_ref<'a>(
+  r: &mut SliceCursor<'a>,
+  tag: u64,
+  budget: &mut DecodeBudget,
+) -> Result<IpldRef<'a>, Error> {
+  budget.enter().map_err(|e| r.limit(e))?;
+  budget.alloc(mem::size_of::<IpldRef>()).map_err(|e| r.limit(e))?;
+  let ipld = read_ipld_ref(r, budget)?;
+  budget.exit();
+  Ok(IpldRef::Tag(tag, Box::new(ipld)))
+}
+
+/// # Errors
+///
+/// Will always return `Err`, since tags other than links can only be decoded
+/// with the `unleashed` feature
+#[cfg(not(feature = "unleashed"))]
+fn read_tag_ref<'a>(
+  r: &mut SliceCursor<'a>,
+  tag: u64,
+  _: &mut DecodeBudget,
+) -> Result<IpldRef<'a>, Error> {
+  Err(r.error(ErrorKind::UnknownTag(tag)))
+}
+
+/// # Errors
+///
+/// Will return `Err` if the slice does not start with a valid dag-cbor value
+fn read_ipld_ref<'a>(
+  r: &mut SliceCursor<'a>,
+  budget: &mut DecodeBudget,
+) -> Result<IpldRef<'a>, Error> {
+  let major = r.read_u8()?;
+  let ipld = match major {
+    // Major type 0: an unsigned integer
+    0x00..=0x1b => IpldRef::Integer(i128::from(r.read_uint(major)?)),
+
+    // Major type 1: a negative integer
+    0x20..=0x3b => {
+      IpldRef::Integer(-1 - i128::from(r.read_uint(major - 0x20)?))
+    }
+
+    // Major type 2: a byte string
+    0x40..=0x5b => {
+      let len = r.read_len(major - 0x40)?;
+      budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+      IpldRef::Bytes(r.take(len)?)
+    }
+
+    // Major type 3: a text string
+    0x60..=0x7b => {
+      let len = r.read_len(major - 0x60)?;
+      budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+      IpldRef::String(r.read_str(len)?)
+    }
+
+    // Major type 4: an array of data items
+    0x80..=0x9b | 0x9f => {
+      let mut len = match major {
+        0x9f => None,
+        _ => Some(r.read_len(major - 0x80)?),
+      };
+      r.enter(len, budget)?;
+      let remaining = r.bytes.len() - r.pos;
+      let mut list = Vec::with_capacity(len.unwrap_or(0).min(remaining));
+      while r.has_next(&mut len)? {
+        budget.check_len(list.len() + 1).map_err(|e| r.limit(e))?;
+        budget.alloc(mem::size_of::<IpldRef>()).map_err(|e| r.limit(e))?;
+        let value = read_ipld_ref(r, budget)
+          .map_err(|e| e.within(list.len().to_string()))?;
+        list.push(value);
+      }
+      budget.exit();
+      IpldRef::List(list)
+    }
+
+    // Major type 5: a map of pairs of data items
+    0xa0..=0xbb => {
+      let len = r.read_len(major - 0xa0)?;
+      read_map_ref(r, Some(len), budget)?
+    }
+    0xbf => read_map_ref(r, None, budget)?,
+
+    // Major type 6: optional semantic tagging of other major types
+    0xc0..=0xdb => {
+      let tag = r.read_tag(major)?;
+      if tag == 42 {
+        IpldRef::Link(r.read_link()?)
+      }
+      else {
+        read_tag_ref(r, tag, budget)?
+      }
+    }
+
+    // Major type 7: floating-point numbers and other simple data types that
+    // need no content
+    0xf4 => IpldRef::Bool(false),
+    0xf5 => IpldRef::Bool(true),
+    0xf6 | 0xf7 => IpldRef::Null,
+    0xfa => IpldRef::Float(f64::from(BigEndian::read_f32(r.take(4)?))),
+    0xfb => IpldRef::Float(BigEndian::read_f64(r.take(8)?)),
+    _ => {
+      return Err(r.unexpected(major));
+    }
+  };
+  Ok(ipld)
+}
+
+/// Decodes the value at the start of `bytes` within `limits`, borrowing its
+/// strings and bytes from `bytes`.
+///
+/// # Errors
+///
+/// Will return `Err` if `bytes` does not start with a valid dag-cbor value,
+/// or if decoding exceeded `limits`
+pub fn decode_borrowed(
+  bytes: &[u8],
+  limits: DecodeLimits,
+) -> Result<IpldRef<'_>, Error> {
+  let mut budget = DecodeBudget::new(limits);
+  read_ipld_ref(&mut SliceCursor::new(bytes), &mut budget)
+}
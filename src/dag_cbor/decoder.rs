@@ -0,0 +1,617 @@
+use crate::{
+  codec::{
+    Error,
+    ErrorKind,
+    SliceCursor,
+  },
+  ipld::{
+    Ipld,
+    IpldKind,
+  },
+  limits::{
+    DecodeBudget,
+    DecodeLimits,
+  },
+};
+
+#[cfg(feature = "unleashed")]
+use alloc::boxed::Box;
+use alloc::{
+  borrow::ToOwned,
+  collections::btree_map::BTreeMap,
+  string::{
+    String,
+    ToString,
+  },
+  vec::Vec,
+};
+use byteorder::{
+  BigEndian,
+  ByteOrder,
+};
+#[cfg(feature = "unleashed")]
+use core::convert::TryFrom;
+use core::mem;
+
+/// The outcome of `DagCborDecoder::decode`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Decoded {
+  /// A complete value was decoded.
+  Value(Ipld),
+  /// The input fed so far ends before the next value is complete.
+  NeedsInput,
+}
+
+/// A complete item of the input: either a value without children, or the
+/// head of a list, map or tag whose children follow it.
+enum Token {
+  Value(Ipld),
+  List(Option<usize>),
+  Map(Option<usize>),
+  #[cfg(feature = "unleashed")]
+  Tag(u64),
+  Break,
+}
+
+/// A list, map or tag whose children are still being decoded.
+#[derive(Clone, Debug)]
+enum Frame {
+  List {
+    list: Vec<Ipld>,
+    len: Option<usize>,
+  },
+  Map {
+    map: BTreeMap<String, Ipld>,
+    read: usize,
+    len: Option<usize>,
+    key: Option<String>,
+  },
+  #[cfg(feature = "unleashed")]
+  IntegerMap {
+    map: BTreeMap<i64, Ipld>,
+    read: usize,
+    len: Option<usize>,
+    key: Option<i64>,
+  },
+  #[cfg(feature = "unleashed")]
+  Tag(u64, Option<Ipld>),
+}
+
+impl Frame {
+  /// Returns the path segment of the child being decoded, if it has one.
+  fn segment(&self) -> Option<String> {
+    match self {
+      Self::List { list, .. } => Some(list.len().to_string()),
+      Self::Map { key, .. } => key.clone(),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap { key, .. } => key.map(|key| key.to_string()),
+      #[cfg(feature = "unleashed")]
+      Self::Tag(..) => None,
+    }
+  }
+
+  /// Returns whether the frame is of indefinite length and may be ended by
+  /// a break.
+  fn awaits_break(&self) -> bool {
+    match self {
+      Self::List { len, .. } => len.is_none(),
+      Self::Map { len, key, .. } => len.is_none() && key.is_none(),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap { len, key, .. } => len.is_none() && key.is_none(),
+      #[cfg(feature = "unleashed")]
+      Self::Tag(..) => false,
+    }
+  }
+
+  /// Returns whether the item starting with `major` may come next. Turns a
+  /// map into an integer map if its first key is an integer.
+  fn accepts(&mut self, major: u8) -> bool {
+    let is_key = match self {
+      Self::Map { key, .. } => key.is_none(),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap { key, .. } => key.is_none(),
+      _ => false,
+    };
+    if major == 0xff {
+      return self.awaits_break();
+    }
+    if !is_key {
+      return true;
+    }
+    #[cfg(feature = "unleashed")]
+    let integer = matches!(major, 0x00..=0x1b | 0x20..=0x3b);
+    match self {
+      #[cfg(feature = "unleashed")]
+      Self::Map { read: 0, len, .. } if integer => {
+        let len = *len;
+        *self =
+          Self::IntegerMap { map: BTreeMap::new(), read: 0, len, key: None };
+        true
+      }
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap { .. } => integer,
+      _ => matches!(major, 0x60..=0x7b),
+    }
+  }
+
+  /// Adds the child `value` to the frame, and returns whether the frame is
+  /// complete.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if a limit of `budget` is exceeded or `value` is not
+  /// a valid map key
+  fn push(
+    &mut self,
+    value: Ipld,
+    budget: &mut DecodeBudget,
+  ) -> Result<bool, Error> {
+    match self {
+      Self::List { list, len } => {
+        budget.check_len(list.len() + 1)?;
+        budget.alloc(mem::size_of::<Ipld>())?;
+        list.push(value);
+        Ok(*len == Some(list.len()))
+      }
+      Self::Map { map, read, len, key } => match (key.take(), value) {
+        (Some(key), value) => {
+          map.insert(key, value);
+          Ok(*len == Some(*read))
+        }
+        (None, Ipld::String(value)) => {
+          *read += 1;
+          budget.check_len(*read)?;
+          budget.alloc(mem::size_of::<(String, Ipld)>())?;
+          *key = Some(value);
+          Ok(false)
+        }
+        (None, value) => Err(Error::new(ErrorKind::WrongKind {
+          expected: IpldKind::String,
+          found: value.kind(),
+        })),
+      },
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap { map, read, len, key } => match (key.take(), value) {
+        (Some(key), value) => {
+          map.insert(key, value);
+          Ok(*len == Some(*read))
+        }
+        (None, Ipld::Integer(value)) => {
+          *read += 1;
+          budget.check_len(*read)?;
+          budget.alloc(mem::size_of::<(i64, Ipld)>())?;
+          let value = i64::try_from(value)
+            .map_err(|_| Error::new(ErrorKind::OutOfRange))?;
+          *key = Some(value);
+          Ok(false)
+        }
+        (None, value) => Err(Error::new(ErrorKind::WrongKind {
+          expected: IpldKind::Integer,
+          found: value.kind(),
+        })),
+      },
+      #[cfg(feature = "unleashed")]
+      Self::Tag(_, tagged) => {
+        *tagged = Some(value);
+        Ok(true)
+      }
+    }
+  }
+
+  /// Returns the value of the complete frame.
+  fn finish(self) -> Ipld {
+    match self {
+      Self::List { list, .. } => Ipld::List(list),
+      Self::Map { map, .. } => Ipld::StringMap(map),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap { map, .. } => Ipld::IntegerMap(map),
+      // A tag frame is only complete once its value was pushed.
+      #[cfg(feature = "unleashed")]
+      Self::Tag(tag, value) => {
+        Ipld::Tag(tag, Box::new(value.unwrap_or(Ipld::Null)))
+      }
+    }
+  }
+}
+
+/// A resumable dag-cbor decoder for input that arrives in chunks, such as
+/// from a network stream.
+///
+/// Bytes are fed in with `feed`, and each call to `decode` returns the next
+/// complete value or reports that more input is needed. Values may span any
+/// number of chunks, and a chunk may hold any number of values. Only the
+/// bytes of the value being decoded are buffered, and no `std::io` is
+/// needed.
+///
+/// Each value is decoded against its own budget of the decoder's limits.
+/// After an error the decoder should not be used anymore.
+#[derive(Clone, Debug, Default)]
+pub struct DagCborDecoder {
+  buf: Vec<u8>,
+  pos: usize,
+  offset: u64,
+  stack: Vec<Frame>,
+  budget: DecodeBudget,
+}
+
+impl DagCborDecoder {
+  /// Returns a decoder bounded by the default limits.
+  pub fn new() -> Self { Self::default() }
+
+  /// Returns a decoder bounded by `limits`.
+  pub fn with_limits(limits: DecodeLimits) -> Self {
+    Self { budget: DecodeBudget::new(limits), ..Self::default() }
+  }
+
+  /// Appends `bytes` to the input.
+  pub fn feed(&mut self, bytes: &[u8]) {
+    self.buf.drain(..self.pos);
+    self.offset += self.pos as u64;
+    self.pos = 0;
+    self.buf.extend_from_slice(bytes);
+  }
+
+  /// Returns the number of bytes fed but not consumed yet, because they
+  /// belong to an incomplete item.
+  pub fn buffered(&self) -> usize { self.buf.len() - self.pos }
+
+  /// Returns the offset in the input of the next byte to be consumed.
+  pub fn position(&self) -> u64 { self.offset + self.pos as u64 }
+
+  /// Decodes the next value from the input fed so far.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the input is not valid dag-cbor, or if decoding a
+  /// value exceeded the limits of the decoder
+  pub fn decode(&mut self) -> Result<Decoded, Error> {
+    loop {
+      let start = self.pos;
+      let major = match self.buf.get(start) {
+        Some(major) => *major,
+        None => return Ok(Decoded::NeedsInput),
+      };
+      if let Some(frame) = self.stack.last_mut() {
+        if !frame.accepts(major) {
+          let e = Error::new(ErrorKind::InvalidMajorType(major));
+          return Err(self.fail(e.at(start as u64)));
+        }
+      }
+      let mut r = SliceCursor { bytes: &self.buf, pos: start };
+      let token = match read_token(&mut r, &mut self.budget) {
+        Ok(token) => token,
+        Err(e) if e.kind() == &ErrorKind::UnexpectedEof => {
+          return Ok(Decoded::NeedsInput);
+        }
+        Err(e) => return Err(self.fail(e)),
+      };
+      self.pos = r.pos;
+      let at = |e: Error| e.at(start as u64);
+      let mut value = match token {
+        Token::Value(value) => value,
+        Token::List(len) => {
+          match self.open(Frame::List { list: Vec::new(), len }, len) {
+            Ok(Some(value)) => value,
+            Ok(None) => continue,
+            Err(e) => return Err(self.fail(at(e))),
+          }
+        }
+        Token::Map(len) => {
+          let frame =
+            Frame::Map { map: BTreeMap::new(), read: 0, len, key: None };
+          match self.open(frame, len) {
+            Ok(Some(value)) => value,
+            Ok(None) => continue,
+            Err(e) => return Err(self.fail(at(e))),
+          }
+        }
+        #[cfg(feature = "unleashed")]
+        Token::Tag(tag) => {
+          let result = self
+            .budget
+            .enter()
+            .and_then(|()| self.budget.alloc(mem::size_of::<Ipld>()));
+          if let Err(e) = result {
+            return Err(self.fail(at(e.into())));
+          }
+          self.stack.push(Frame::Tag(tag, None));
+          continue;
+        }
+        // `accepts` only lets a break through to a frame awaiting one.
+        Token::Break => match self.stack.pop() {
+          Some(frame) => {
+            self.budget.exit();
+            frame.finish()
+          }
+          None => {
+            let e = Error::new(ErrorKind::InvalidMajorType(major));
+            return Err(at(e));
+          }
+        },
+      };
+      loop {
+        let frame = match self.stack.last_mut() {
+          Some(frame) => frame,
+          None => {
+            self.budget = DecodeBudget::new(*self.budget.limits());
+            return Ok(Decoded::Value(value));
+          }
+        };
+        match frame.push(value, &mut self.budget) {
+          Ok(true) => {}
+          Ok(false) => break,
+          Err(e) => return Err(self.fail(at(e))),
+        }
+        match self.stack.pop() {
+          Some(frame) => {
+            self.budget.exit();
+            value = frame.finish();
+          }
+          None => break,
+        }
+      }
+    }
+  }
+
+  /// Checks that the input ended after a complete value.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the input ends within a value
+  pub fn finish(&self) -> Result<(), Error> {
+    if self.stack.is_empty() && self.buffered() == 0 {
+      return Ok(());
+    }
+    let e = Error::new(ErrorKind::UnexpectedEof).at(self.buf.len() as u64);
+    Err(self.fail(e))
+  }
+
+  /// Enters a list or map, and returns its value right away if it is empty.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the list or map exceeds the limits of the decoder
+  fn open(
+    &mut self,
+    frame: Frame,
+    len: Option<usize>,
+  ) -> Result<Option<Ipld>, Error> {
+    self.budget.enter()?;
+    self.budget.check_len(len.unwrap_or(0))?;
+    if len == Some(0) {
+      self.budget.exit();
+      return Ok(Some(frame.finish()));
+    }
+    self.stack.push(frame);
+    Ok(None)
+  }
+
+  /// Moves the offset of `e` from the buffer to the whole input, and adds
+  /// the path of the value being decoded.
+  fn fail(&self, e: Error) -> Error {
+    let e = e.offset_by(self.offset);
+    self.stack.iter().rev().fold(e, |e, frame| match frame.segment() {
+      Some(segment) => e.within(segment),
+      None => e,
+    })
+  }
+}
+
+/// Reads the next item, which must be complete in `r`.
+///
+/// # Errors
+///
+/// Will return `Err` with `ErrorKind::UnexpectedEof` if the item is not
+/// complete yet, or any other kind if it is invalid or exceeds a limit of
+/// `budget`
+fn read_token(
+  r: &mut SliceCursor,
+  budget: &mut DecodeBudget,
+) -> Result<Token, Error> {
+  let major = r.read_u8()?;
+  let token = match major {
+    // Major type 0: an unsigned integer
+    0x00..=0x1b => Token::Value(Ipld::Integer(i128::from(r.read_uint(major)?))),
+
+    // Major type 1: a negative integer
+    0x20..=0x3b => {
+      let value = -1 - i128::from(r.read_uint(major - 0x20)?);
+      Token::Value(Ipld::Integer(value))
+    }
+
+    // Major type 2: a byte string
+    0x40..=0x5b => {
+      let len = r.read_len(major - 0x40)?;
+      budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+      let bytes = r.take(len)?;
+      budget.alloc(len).map_err(|e| r.limit(e))?;
+      Token::Value(Ipld::Bytes(bytes.to_vec()))
+    }
+
+    // Major type 3: a text string
+    0x60..=0x7b => {
+      let len = r.read_len(major - 0x60)?;
+      budget.check_bytes_len(len).map_err(|e| r.limit(e))?;
+      let string = r.read_str(len)?;
+      budget.alloc(len).map_err(|e| r.limit(e))?;
+      Token::Value(Ipld::String(string.to_owned()))
+    }
+
+    // Major type 4: an array of data items
+    0x80..=0x9b => Token::List(Some(r.read_len(major - 0x80)?)),
+    0x9f => Token::List(None),
+
+    // Major type 5: a map of pairs of data items
+    0xa0..=0xbb => Token::Map(Some(r.read_len(major - 0xa0)?)),
+    0xbf => Token::Map(None),
+
+    // Major type 6: optional semantic tagging of other major types
+    0xc0..=0xdb => {
+      let tag = r.read_tag(major)?;
+      if tag == 42 {
+        Token::Value(Ipld::Link(r.read_link()?))
+      }
+      else {
+        read_tag(r, tag)?
+      }
+    }
+
+    // Major type 7: floating-point numbers and other simple data types that
+    // need no content
+    0xf4 => Token::Value(Ipld::Bool(false)),
+    0xf5 => Token::Value(Ipld::Bool(true)),
+    0xf6 | 0xf7 => Token::Value(Ipld::Null),
+    0xfa => {
+      Token::Value(Ipld::Float(f64::from(BigEndian::read_f32(r.take(4)?))))
+    }
+    0xfb => Token::Value(Ipld::Float(BigEndian::read_f64(r.take(8)?))),
+    0xff => Token::Break,
+    _ => {
+      return Err(r.unexpected(major));
+    }
+  };
+  Ok(token)
+}
+
+/// # Errors
+///
+/// Will return `Err` without the `unleashed` feature, since tags other than
+/// links cannot be decoded then
+#[cfg(feature = "unleashed")]
+fn read_tag(_: &SliceCursor, tag: u64) -> Result<Token, Error> {
+  Ok(Token::Tag(tag))
+}
+
+/// # Errors
+///
+/// Will always return `Err`, since tags other than links can only be decoded
+/// with the `unleashed` feature
+#[cfg(not(feature = "unleashed"))]
+fn read_tag(r: &SliceCursor, tag: u64) -> Result<Token, Error> {
+  Err(r.error(ErrorKind::UnknownTag(tag)))
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::{
+    codec::Codec,
+    dag_cbor::DagCborCodec,
+    limits::LimitExceeded,
+  };
+
+  /// Feeds `bytes` to `decoder` in chunks of `chunk` bytes, and collects the
+  /// values decoded.
+  fn decode_chunks(
+    decoder: &mut DagCborDecoder,
+    bytes: &[u8],
+    chunk: usize,
+  ) -> Result<Vec<Ipld>, Error> {
+    let mut values = Vec::new();
+    for chunk in bytes.chunks(chunk) {
+      decoder.feed(chunk);
+      while let Decoded::Value(value) = decoder.decode()? {
+        values.push(value);
+      }
+    }
+    Ok(values)
+  }
+
+  #[quickcheck]
+  pub fn chunked(ipld: Ipld, chunk: usize) -> bool {
+    let bytes = DagCborCodec.encode(&ipld).unwrap().into_inner();
+    let mut decoder = DagCborDecoder::new();
+    let values = decode_chunks(&mut decoder, &bytes, chunk % 16 + 1);
+    values == Ok(vec![ipld]) && decoder.finish().is_ok()
+  }
+
+  #[test]
+  fn values_across_chunks() {
+    let a = ipld!({ "a": [1, "b", [], {}], "c": { "d": null } });
+    let b = ipld!([true, -300, 1.5, "e"]);
+    let mut bytes = DagCborCodec.encode(&a).unwrap().into_inner();
+    bytes.extend(DagCborCodec.encode(&b).unwrap().into_inner());
+    // An indefinite list and an indefinite map, as one chunk.
+    bytes.extend([0x9f, 0x01, 0x9f, 0xff, 0xff, 0xbf, 0x61, 0x61, 0x02, 0xff]);
+    let mut decoder = DagCborDecoder::new();
+    assert_eq!(decoder.decode(), Ok(Decoded::NeedsInput));
+    let values = decode_chunks(&mut decoder, &bytes, 3).unwrap();
+    assert_eq!(values, vec![a, b, ipld!([1, []]), ipld!({ "a": 2 })]);
+    assert_eq!(decoder.position(), bytes.len() as u64);
+    assert_eq!(decoder.buffered(), 0);
+    assert_eq!(decoder.finish(), Ok(()));
+  }
+
+  #[cfg(feature = "unleashed")]
+  #[test]
+  fn unleashed_values() {
+    let mut map = BTreeMap::new();
+    map.insert(-2, Ipld::Tag(7, Box::new(ipld!(["a"]))));
+    map.insert(3, ipld!(null));
+    let ipld = Ipld::List(vec![Ipld::IntegerMap(map), ipld!({ "b": 1 })]);
+    let bytes = DagCborCodec.encode(&ipld).unwrap().into_inner();
+    let mut decoder = DagCborDecoder::new();
+    assert_eq!(decode_chunks(&mut decoder, &bytes, 1), Ok(vec![ipld]));
+    // 42(1), with a tag that is not in its shortest form
+    let mut decoder = DagCborDecoder::new();
+    decoder.feed(&[0xd9, 0x00, 0x2a, 0x01]);
+    let e = decoder.decode().unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::InvalidSyntax);
+    assert_eq!(e.offset(), Some(0));
+  }
+
+  #[test]
+  fn decoder_errors() {
+    // Truncated within the string of the list in a map.
+    let mut decoder = DagCborDecoder::new();
+    decoder.feed(&[0xa1, 0x61, 0x61, 0x82, 0x01, 0x63, 0x62]);
+    assert_eq!(decoder.decode(), Ok(Decoded::NeedsInput));
+    assert_eq!(decoder.buffered(), 2);
+    let e = decoder.finish().unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::UnexpectedEof);
+    assert_eq!(e.offset(), Some(7));
+    assert_eq!(e.path().to_string(), "a/1");
+
+    // An invalid item after a complete value, fed in separate chunks.
+    let mut decoder = DagCborDecoder::new();
+    decoder.feed(&[0x01, 0x82]);
+    assert_eq!(decoder.decode(), Ok(Decoded::Value(ipld!(1))));
+    decoder.feed(&[0xf6]);
+    decoder.feed(&[0x1c]);
+    let e = decoder.decode().unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::InvalidMajorType(0x1c));
+    assert_eq!(e.offset(), Some(3));
+    assert_eq!(e.path().to_string(), "1");
+
+    // A map key that is not a string, and an unexpected break.
+    let mut decoder = DagCborDecoder::new();
+    decoder.feed(&[0xa1, 0xf6]);
+    let e = decoder.decode().unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::InvalidMajorType(0xf6));
+    assert_eq!(e.offset(), Some(1));
+    let mut decoder = DagCborDecoder::new();
+    decoder.feed(&[0x81, 0xff]);
+    let e = decoder.decode().unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::InvalidMajorType(0xff));
+  }
+
+  #[test]
+  fn decoder_limits() {
+    let limits = DecodeLimits { max_depth: 2, ..DecodeLimits::default() };
+    let mut decoder = DagCborDecoder::with_limits(limits);
+    decoder.feed(&[0x81, 0x80, 0x81]);
+    assert_eq!(decoder.decode(), Ok(Decoded::Value(ipld!([[]]))));
+    decoder.feed(&[0x81, 0x80]);
+    let e = decoder.decode().unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::LimitExceeded(LimitExceeded::Depth(2)));
+    assert_eq!(e.offset(), Some(4));
+    assert_eq!(e.path().to_string(), "0/0");
+
+    // A string longer than the limit fails before it is buffered.
+    let limits = DecodeLimits { max_bytes_len: 4, ..DecodeLimits::default() };
+    let mut decoder = DagCborDecoder::with_limits(limits);
+    decoder.feed(&[0x65]);
+    let e = decoder.decode().unwrap_err();
+    assert_eq!(e.kind(), &ErrorKind::LimitExceeded(LimitExceeded::BytesLen(4)));
+  }
+}
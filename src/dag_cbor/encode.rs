@@ -1,21 +1,29 @@
 use crate::{
-  codec::Encode,
+  codec::{
+    Encode,
+    EncodedLen,
+    Error,
+    ErrorKind,
+    Writer,
+  },
   dag_cbor::DagCborCodec,
   ipld::Ipld,
 };
 
 use alloc::{
-  borrow::ToOwned,
   boxed::Box,
   string::String,
   sync::Arc,
 };
-use bytecursor::ByteCursor;
 use byteorder::{
   BigEndian,
   ByteOrder,
 };
-use sp_cid::Cid;
+use sp_cid::{
+  Cid,
+  Version,
+};
+use unsigned_varint::encode as varint_encode;
 use alloc::{
   collections::btree_map::BTreeMap,
   vec::Vec,
@@ -27,20 +35,21 @@ use core::{
   ops::Deref,
 }; 
 
+/// Writes a CBOR null.
+///
 /// # Errors
 ///
-/// Will return `Err` if the cursor position exceeds maximum possible vector
-/// length or we failed to write whole buffer
-fn write_null(w: &mut ByteCursor) -> Result<(), String> {
+/// Will return `Err` if writing to `w` failed
+#[doc(hidden)]
+pub fn write_null<W: Writer>(w: &mut W) -> Result<(), Error> {
   w.write_all(&[0xf6])?;
   Ok(())
 }
 
 /// # Errors
 ///
-/// Will return `Err` if the cursor position exceeds maximum possible vector
-/// length or we failed to write whole buffer
-fn write_u8(w: &mut ByteCursor, major: u8, value: u8) -> Result<(), String> {
+/// Will return `Err` if writing to `w` failed
+fn write_u8<W: Writer>(w: &mut W, major: u8, value: u8) -> Result<(), Error> {
   if value <= 0x17 {
     let buf = [major << 5 | value];
     w.write_all(&buf)?;
@@ -54,9 +63,12 @@ fn write_u8(w: &mut ByteCursor, major: u8, value: u8) -> Result<(), String> {
 
 /// # Errors
 ///
-/// Will return `Err` if the cursor position exceeds maximum possible vector
-/// length or we failed to write whole buffer
-fn write_u16(w: &mut ByteCursor, major: u8, value: u16) -> Result<(), String> {
+/// Will return `Err` if writing to `w` failed
+fn write_u16<W: Writer>(
+  w: &mut W,
+  major: u8,
+  value: u16,
+) -> Result<(), Error> {
   if let Ok(small) = u8::try_from(value) {
     write_u8(w, major, small)?;
   }
@@ -70,9 +82,12 @@ fn write_u16(w: &mut ByteCursor, major: u8, value: u16) -> Result<(), String> {
 
 /// # Errors
 ///
-/// Will return `Err` if the cursor position exceeds maximum possible vector
-/// length or we failed to write whole buffer
-fn write_u32(w: &mut ByteCursor, major: u8, value: u32) -> Result<(), String> {
+/// Will return `Err` if writing to `w` failed
+fn write_u32<W: Writer>(
+  w: &mut W,
+  major: u8,
+  value: u32,
+) -> Result<(), Error> {
   if let Ok(small) = u16::try_from(value) {
     write_u16(w, major, small)?;
   }
@@ -84,11 +99,18 @@ fn write_u32(w: &mut ByteCursor, major: u8, value: u32) -> Result<(), String> {
   Ok(())
 }
 
+/// Writes the header of a value of the CBOR major type `major` with the
+/// argument `value`, using the shortest possible encoding.
+///
 /// # Errors
 ///
-/// Will return `Err` if the cursor position exceeds maximum possible vector
-/// length or we failed to write whole buffer
-fn write_u64(w: &mut ByteCursor, major: u8, value: u64) -> Result<(), String> {
+/// Will return `Err` if writing to `w` failed
+#[doc(hidden)]
+pub fn write_u64<W: Writer>(
+  w: &mut W,
+  major: u8,
+  value: u64,
+) -> Result<(), Error> {
   if let Ok(small) = u32::try_from(value) {
     write_u32(w, major, small)?;
   }
@@ -102,61 +124,102 @@ fn write_u64(w: &mut ByteCursor, major: u8, value: u64) -> Result<(), String> {
 
 /// # Errors
 ///
-/// Will return `Err` if the cursor position exceeds maximum possible vector
-/// length or we failed to write whole buffer
-fn write_tag(w: &mut ByteCursor, tag: u64) -> Result<(), String> {
+/// Will return `Err` if writing to `w` failed
+fn write_tag<W: Writer>(w: &mut W, tag: u64) -> Result<(), Error> {
   write_u64(w, 6, tag)
 }
 impl Encode<DagCborCodec> for bool {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     let buf = if *self { [0xf5] } else { [0xf4] };
     w.write_all(&buf)?;
     Ok(())
   }
 }
 impl Encode<DagCborCodec> for u8 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u8(w, 0, *self)
   }
 }
 impl Encode<DagCborCodec> for u16 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u16(w, 0, *self)
   }
 }
 impl Encode<DagCborCodec> for u32 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u32(w, 0, *self)
   }
 }
 impl Encode<DagCborCodec> for u64 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u64(w, 0, *self)
   }
 }
+// Signed integers are encoded like an `Ipld::Integer` of the same value:
+// non-negative values with major type 0 and negative ones with major type 1.
 impl Encode<DagCborCodec> for i8 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
-    write_u8(w, 1, -(*self + 1) as u8) // may lose sign
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
+    i128::from(*self).encode(c, w)
   }
 }
 impl Encode<DagCborCodec> for i16 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
-    write_u16(w, 1, -(*self + 1) as u16) // may lose sign
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
+    i128::from(*self).encode(c, w)
   }
 }
 impl Encode<DagCborCodec> for i32 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
-    write_u32(w, 1, -(*self + 1) as u32) // may lose sign
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
+    i128::from(*self).encode(c, w)
   }
 }
 impl Encode<DagCborCodec> for i64 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
-    write_u64(w, 1, -(*self + 1) as u64) // may lose sign
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
+    i128::from(*self).encode(c, w)
   }
 }
 impl Encode<DagCborCodec> for f32 {
   #[allow(clippy::float_cmp)]
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     if self.is_infinite() {
       if self.is_sign_positive() {
         w.write_all(&[0xf9, 0x7c, 0x00])?;
@@ -178,7 +241,11 @@ impl Encode<DagCborCodec> for f32 {
 }
 impl Encode<DagCborCodec> for f64 {
   #[allow(clippy::float_cmp)]
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     if !self.is_finite() || Self::from(*self as f32) == *self {
       // conversion to `f32` is lossless
       let value = *self as f32;
@@ -194,40 +261,60 @@ impl Encode<DagCborCodec> for f64 {
   }
 }
 impl Encode<DagCborCodec> for [u8] {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u64(w, 2, self.len() as u64)?;
     w.write_all(self)?;
     Ok(())
   }
 }
 impl Encode<DagCborCodec> for Box<[u8]> {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     self[..].encode(c, w)
   }
 }
 impl Encode<DagCborCodec> for str {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u64(w, 3, self.len() as u64)?;
     w.write_all(self.as_bytes())?;
     Ok(())
   }
 }
 impl Encode<DagCborCodec> for String {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     self.as_str().encode(c, w)
   }
 }
 impl Encode<DagCborCodec> for i128 {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     if *self < 0 {
       if -(*self + 1) > u64::max_value() as i128 {
-        return Err("Number out of range.".to_owned());
+        return Err(Error::new(ErrorKind::OutOfRange).at(w.position()));
       }
       write_u64(w, 1, -(*self + 1) as u64)?;
     }
     else {
       if *self > u64::max_value() as i128 {
-        return Err("Number out of range.".to_owned());
+        return Err(Error::new(ErrorKind::OutOfRange).at(w.position()));
       }
       write_u64(w, 0, *self as u64)?;
     }
@@ -235,7 +322,11 @@ impl Encode<DagCborCodec> for i128 {
   }
 }
 impl Encode<DagCborCodec> for Cid {
-  fn encode(&self, _: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_tag(w, 42)?;
     // insert zero byte per https://github.com/ipld/specs/blob/master/block-layer/codecs/dag-cbor.md#links
     // TODO: don't allocate
@@ -248,7 +339,11 @@ impl Encode<DagCborCodec> for Cid {
   }
 }
 impl<T: Encode<DagCborCodec>> Encode<DagCborCodec> for Option<T> {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     if let Some(value) = self {
       value.encode(c, w)?;
     }
@@ -259,7 +354,11 @@ impl<T: Encode<DagCborCodec>> Encode<DagCborCodec> for Option<T> {
   }
 }
 impl<T: Encode<DagCborCodec>> Encode<DagCborCodec> for Vec<T> {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u64(w, 4, self.len() as u64)?;
     for value in self {
       value.encode(c, w)?;
@@ -270,15 +369,19 @@ impl<T: Encode<DagCborCodec>> Encode<DagCborCodec> for Vec<T> {
 impl<K: Encode<DagCborCodec>, T: Encode<DagCborCodec> + 'static>
   Encode<DagCborCodec> for BTreeMap<K, T>
 {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u64(w, 5, self.len() as u64)?;
     let mut vec: Vec<_> = self.iter().collect();
     vec.sort_unstable_by(|&(k1, _), &(k2, _)| {
-      let mut bc1 = ByteCursor::new(Vec::new());
+      let mut bc1 = Vec::new();
       mem::drop(k1.encode(c, &mut bc1));
-      let mut bc2 = ByteCursor::new(Vec::new());
+      let mut bc2 = Vec::new();
       mem::drop(k2.encode(c, &mut bc2));
-      bc1.into_inner().cmp(&bc2.into_inner())
+      bc1.cmp(&bc2)
     });
     for (k, v) in vec {
       k.encode(c, w)?;
@@ -288,7 +391,11 @@ impl<K: Encode<DagCborCodec>, T: Encode<DagCborCodec> + 'static>
   }
 }
 impl Encode<DagCborCodec> for Ipld {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     match self {
       Self::Null => write_null(w),
       Self::Bool(b) => b.encode(c, w),
@@ -299,22 +406,53 @@ impl Encode<DagCborCodec> for Ipld {
       Self::List(l) => l.encode(c, w),
       Self::StringMap(m) => m.encode(c, w),
       Self::Link(cid) => cid.encode(c, w),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(m) => {
+        write_u64(w, 5, m.len() as u64)?;
+        // non-negative keys sort before negative ones in their encoded form
+        for (k, v) in m.range(0..).chain(m.range(..0).rev()) {
+          i128::from(*k).encode(c, w)?;
+          v.encode(c, w)?;
+        }
+        Ok(())
+      }
+      #[cfg(feature = "unleashed")]
+      Self::Tag(tag, ipld) => {
+        // tag 42 is reserved for links, which are encoded from `Self::Link`
+        if *tag == 42 {
+          return Err(Error::new(ErrorKind::UnknownTag(42)).at(w.position()));
+        }
+        write_tag(w, *tag)?;
+        ipld.encode(c, w)
+      }
     }
   }
 }
 impl<T: Encode<DagCborCodec>> Encode<DagCborCodec> for Arc<T> {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     self.deref().encode(c, w)
   }
 }
 impl Encode<DagCborCodec> for () {
-  fn encode(&self, _c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    _c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u8(w, 4, 0)?;
     Ok(())
   }
 }
 impl<A: Encode<DagCborCodec>> Encode<DagCborCodec> for (A,) {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u8(w, 4, 1)?;
     self.0.encode(c, w)?;
     Ok(())
@@ -323,7 +461,11 @@ impl<A: Encode<DagCborCodec>> Encode<DagCborCodec> for (A,) {
 impl<A: Encode<DagCborCodec>, B: Encode<DagCborCodec>> Encode<DagCborCodec>
   for (A, B)
 {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u8(w, 4, 2)?;
     self.0.encode(c, w)?;
     self.1.encode(c, w)?;
@@ -333,7 +475,11 @@ impl<A: Encode<DagCborCodec>, B: Encode<DagCborCodec>> Encode<DagCborCodec>
 impl<A: Encode<DagCborCodec>, B: Encode<DagCborCodec>, C: Encode<DagCborCodec>>
   Encode<DagCborCodec> for (A, B, C)
 {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u8(w, 4, 3)?;
     self.0.encode(c, w)?;
     self.1.encode(c, w)?;
@@ -348,7 +494,11 @@ impl<
   D: Encode<DagCborCodec>,
 > Encode<DagCborCodec> for (A, B, C, D)
 {
-  fn encode(&self, c: DagCborCodec, w: &mut ByteCursor) -> Result<(), String> {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
     write_u8(w, 4, 4)?;
     self.0.encode(c, w)?;
     self.1.encode(c, w)?;
@@ -357,3 +507,208 @@ impl<
     Ok(())
   }
 }
+
+/// Returns the length of the header of a value with the argument `value`.
+pub fn header_len(value: u64) -> usize {
+  match value {
+    0x00..=0x17 => 1,
+    0x18..=0xff => 2,
+    0x100..=0xffff => 3,
+    0x1_0000..=0xffff_ffff => 5,
+    _ => 9,
+  }
+}
+
+/// Returns the length of `value` encoded as an unsigned varint.
+fn varint_len(value: u64) -> usize {
+  varint_encode::u64(value, &mut varint_encode::u64_buffer()).len()
+}
+
+/// Returns the length of the binary form of `cid`, as written by
+/// `Cid::write_bytes`.
+fn cid_len(cid: &Cid) -> usize {
+  let hash = cid.hash();
+  let hash_len = varint_len(hash.code())
+    + varint_len(u64::from(hash.size()))
+    + hash.digest().len();
+  match cid.version() {
+    Version::V0 => hash_len,
+    Version::V1 => {
+      varint_len(u64::from(Version::V1)) + varint_len(cid.codec()) + hash_len
+    }
+  }
+}
+
+impl EncodedLen<DagCborCodec> for bool {
+  fn encoded_len(&self, _: DagCborCodec) -> Result<usize, Error> { Ok(1) }
+}
+macro_rules! impl_encoded_len_uint {
+  ($($ty:ty),*) => {
+    $(
+      impl EncodedLen<DagCborCodec> for $ty {
+        fn encoded_len(&self, _: DagCborCodec) -> Result<usize, Error> {
+          Ok(header_len(u64::from(*self)))
+        }
+      }
+    )*
+  };
+}
+impl_encoded_len_uint!(u8, u16, u32, u64);
+macro_rules! impl_encoded_len_int {
+  ($($ty:ty),*) => {
+    $(
+      impl EncodedLen<DagCborCodec> for $ty {
+        fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+          i128::from(*self).encoded_len(c)
+        }
+      }
+    )*
+  };
+}
+impl_encoded_len_int!(i8, i16, i32, i64);
+impl EncodedLen<DagCborCodec> for i128 {
+  fn encoded_len(&self, _: DagCborCodec) -> Result<usize, Error> {
+    let value = if *self < 0 { -(*self + 1) } else { *self };
+    u64::try_from(value)
+      .map(header_len)
+      .map_err(|_| ErrorKind::OutOfRange.into())
+  }
+}
+impl EncodedLen<DagCborCodec> for f32 {
+  fn encoded_len(&self, _: DagCborCodec) -> Result<usize, Error> {
+    Ok(if self.is_finite() { 5 } else { 3 })
+  }
+}
+impl EncodedLen<DagCborCodec> for f64 {
+  #[allow(clippy::float_cmp)]
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    if !self.is_finite() || Self::from(*self as f32) == *self {
+      (*self as f32).encoded_len(c)
+    }
+    else {
+      Ok(9)
+    }
+  }
+}
+impl EncodedLen<DagCborCodec> for [u8] {
+  fn encoded_len(&self, _: DagCborCodec) -> Result<usize, Error> {
+    Ok(header_len(self.len() as u64) + self.len())
+  }
+}
+impl EncodedLen<DagCborCodec> for Box<[u8]> {
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    self[..].encoded_len(c)
+  }
+}
+impl EncodedLen<DagCborCodec> for str {
+  fn encoded_len(&self, _: DagCborCodec) -> Result<usize, Error> {
+    Ok(header_len(self.len() as u64) + self.len())
+  }
+}
+impl EncodedLen<DagCborCodec> for String {
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    self.as_str().encoded_len(c)
+  }
+}
+impl EncodedLen<DagCborCodec> for Cid {
+  fn encoded_len(&self, _: DagCborCodec) -> Result<usize, Error> {
+    let len = cid_len(self) + 1;
+    Ok(header_len(42) + header_len(len as u64) + len)
+  }
+}
+impl<T: EncodedLen<DagCborCodec>> EncodedLen<DagCborCodec> for Option<T> {
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    self.as_ref().map_or(Ok(1), |value| value.encoded_len(c))
+  }
+}
+impl<T: EncodedLen<DagCborCodec>> EncodedLen<DagCborCodec> for Vec<T> {
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    let mut len = header_len(self.len() as u64);
+    for value in self {
+      len += value.encoded_len(c)?;
+    }
+    Ok(len)
+  }
+}
+impl<K: EncodedLen<DagCborCodec>, T: EncodedLen<DagCborCodec>>
+  EncodedLen<DagCborCodec> for BTreeMap<K, T>
+{
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    let mut len = header_len(self.len() as u64);
+    for (k, v) in self {
+      len += k.encoded_len(c)? + v.encoded_len(c)?;
+    }
+    Ok(len)
+  }
+}
+impl EncodedLen<DagCborCodec> for Ipld {
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    match self {
+      Self::Null => Ok(1),
+      Self::Bool(b) => b.encoded_len(c),
+      Self::Integer(i) => i.encoded_len(c),
+      Self::Float(f) => f.encoded_len(c),
+      Self::Bytes(b) => b.as_slice().encoded_len(c),
+      Self::String(s) => s.encoded_len(c),
+      Self::List(l) => l.encoded_len(c),
+      Self::StringMap(m) => m.encoded_len(c),
+      Self::Link(cid) => cid.encoded_len(c),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(m) => m.encoded_len(c),
+      #[cfg(feature = "unleashed")]
+      Self::Tag(42, _) => Err(Error::new(ErrorKind::UnknownTag(42))),
+      #[cfg(feature = "unleashed")]
+      Self::Tag(tag, ipld) => Ok(header_len(*tag) + ipld.encoded_len(c)?),
+    }
+  }
+}
+impl<T: EncodedLen<DagCborCodec>> EncodedLen<DagCborCodec> for Arc<T> {
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    self.deref().encoded_len(c)
+  }
+}
+impl EncodedLen<DagCborCodec> for () {
+  fn encoded_len(&self, _: DagCborCodec) -> Result<usize, Error> { Ok(1) }
+}
+impl<A: EncodedLen<DagCborCodec>> EncodedLen<DagCborCodec> for (A,) {
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    Ok(1 + self.0.encoded_len(c)?)
+  }
+}
+impl<A: EncodedLen<DagCborCodec>, B: EncodedLen<DagCborCodec>>
+  EncodedLen<DagCborCodec> for (A, B)
+{
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    Ok(1 + self.0.encoded_len(c)? + self.1.encoded_len(c)?)
+  }
+}
+impl<
+  A: EncodedLen<DagCborCodec>,
+  B: EncodedLen<DagCborCodec>,
+  C: EncodedLen<DagCborCodec>,
+> EncodedLen<DagCborCodec> for (A, B, C)
+{
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    Ok(
+      1 + self.0.encoded_len(c)?
+        + self.1.encoded_len(c)?
+        + self.2.encoded_len(c)?,
+    )
+  }
+}
+impl<
+  A: EncodedLen<DagCborCodec>,
+  B: EncodedLen<DagCborCodec>,
+  C: EncodedLen<DagCborCodec>,
+  D: EncodedLen<DagCborCodec>,
+> EncodedLen<DagCborCodec> for (A, B, C, D)
+{
+  fn encoded_len(&self, c: DagCborCodec) -> Result<usize, Error> {
+    Ok(
+      1 + self.0.encoded_len(c)?
+        + self.1.encoded_len(c)?
+        + self.2.encoded_len(c)?
+        + self.3.encoded_len(c)?,
+    )
+  }
+}
@@ -1,12 +1,10 @@
 use crate::{
   codec::*,
+  limits::DecodeBudget,
   Ipld,
   References,
 };
-use alloc::string::{
-  String,
-  ToString,
-};
+use alloc::string::String;
 use bytecursor::ByteCursor;
 use core::convert::TryFrom;
 use sp_cid::Cid;
@@ -16,6 +14,7 @@ use sp_multihash::{
 };
 
 mod codec;
+mod scan;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct DagJsonCodec;
@@ -29,34 +28,76 @@ impl From<DagJsonCodec> for u64 {
 impl TryFrom<u64> for DagJsonCodec {
   type Error = UnsupportedCodec;
 
-  fn try_from(_: u64) -> core::result::Result<Self, Self::Error> { Ok(Self) }
+  fn try_from(code: u64) -> core::result::Result<Self, Self::Error> {
+    if code == u64::from(Self) {
+      Ok(Self)
+    }
+    else {
+      Err(UnsupportedCodec(code))
+    }
+  }
 }
 
 impl Encode<DagJsonCodec> for Ipld {
-  fn encode(&self, _: DagJsonCodec, w: &mut ByteCursor) -> Result<(), String> {
-    codec::encode(self, w).map_err(|x| x.to_string())
+  fn encode<W: Writer>(
+    &self,
+    _: DagJsonCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
+    codec::encode(self, w)
   }
 }
 
 impl Decode<DagJsonCodec> for Ipld {
-  fn decode(_: DagJsonCodec, r: &mut ByteCursor) -> Result<Self, String> {
-    codec::decode(r).map_err(|e| e.to_string())
+  fn decode(c: DagJsonCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    _: DagJsonCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    codec::decode(r, budget)
   }
 }
 
 impl References<DagJsonCodec> for Ipld {
   fn references<E: Extend<Cid>>(
     c: DagJsonCodec,
-    r: &mut ByteCursor,
+    r: &mut SliceCursor,
     set: &mut E,
-  ) -> Result<(), String> {
-    Ipld::decode(c, r)?.references(set);
-    Ok(())
+  ) -> Result<(), Error> {
+    Self::references_limited(c, r, set, &mut DecodeBudget::default())
+  }
+
+  fn references_limited<E: Extend<Cid>>(
+    _: DagJsonCodec,
+    r: &mut SliceCursor,
+    set: &mut E,
+    budget: &mut DecodeBudget,
+  ) -> Result<(), Error> {
+    scan::references(r, set, budget)
+  }
+}
+
+impl SkipOne for DagJsonCodec {
+  fn skip(&self, r: &mut SliceCursor) -> Result<(), Error> {
+    self.skip_limited(r, &mut DecodeBudget::default())
+  }
+
+  fn skip_limited(
+    &self,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<(), Error> {
+    scan::skip(r, budget)
   }
 }
 
 /// Returns the corresponding dag-json v1 Cid
-/// to the passed IPLD
+/// to the passed IPLD, hashed with blake2b-256.
+/// Use `CidBuilder` for other hash functions.
 /// # Panics
 /// Panics if dag could not be encoded into a
 /// dag-json bytecursor.
@@ -73,25 +114,28 @@ pub fn cid(dag: &Ipld) -> Cid {
 /// # Errors
 /// Will return `Err` if `s` is not valid dag JSON, with a description
 /// of the error.
-pub fn from_dag_json_string(s: String) -> Result<Ipld, String> {
-  let mut r = ByteCursor::new(s.into_bytes());
-  codec::decode(&mut r).map_err(|e| e.to_string())
+pub fn from_dag_json_string(s: String) -> Result<Ipld, Error> {
+  let mut r = SliceCursor::new(s.as_bytes());
+  codec::decode(&mut r, &mut DecodeBudget::default())
 }
 
 /// This function takes an IPLD structure and returns the corresponding
 /// JSON serialized into a String.
 /// # Errors
 /// Will return `Err` if there was an error converting the IPLD to JSON.
-pub fn to_dag_json_string(ipld: Ipld) -> Result<String, String> {
+pub fn to_dag_json_string(ipld: Ipld) -> Result<String, Error> {
   let mut w = ByteCursor::new(vec![]);
-  codec::encode(&ipld, &mut w).map_err(|e| e.to_string())?;
+  codec::encode(&ipld, &mut w)?;
   Ok(String::from(String::from_utf8_lossy(&w.into_inner())))
 }
 
 #[cfg(test)]
 pub mod tests {
   use super::*;
-  use crate::ipld::*;
+  use crate::{
+    ipld::*,
+    path::IpldPath,
+  };
   use bytecursor::ByteCursor;
   use quickcheck::{
     quickcheck,
@@ -109,8 +153,8 @@ pub mod tests {
     let mut bc = ByteCursor::new(Vec::new());
     match Encode::encode(&value, DagJsonCodec, &mut bc) {
       Ok(()) => {
-        bc.set_position(0);
-        match Decode::decode(DagJsonCodec, &mut bc) {
+        let mut r = SliceCursor::new(bc.get_ref());
+        match Decode::decode(DagJsonCodec, &mut r) {
           Ok(new_value) => return value == new_value,
           Err(e) => println!("Error occurred during decoding: {}", e),
         }
@@ -157,4 +201,129 @@ pub mod tests {
 
   #[quickcheck]
   pub fn edid_link(x: ACid) -> bool { encode_decode_id(Ipld::Link(x.0)) }
+
+  #[quickcheck]
+  pub fn references_match_decode(x: Ipld) -> bool {
+    let bytes = DagJsonCodec.encode(&x).unwrap().into_inner();
+    let mut set = Vec::new();
+    let mut r = SliceCursor::new(&bytes);
+    let scanned =
+      <Ipld as References<_>>::references(DagJsonCodec, &mut r, &mut set);
+    let decoded = Ipld::decode(DagJsonCodec, &mut SliceCursor::new(&bytes));
+    let decoded = match decoded {
+      Ok(decoded) => decoded,
+      Err(_) => return scanned.is_err(),
+    };
+    let mut expected = Vec::new();
+    decoded.references(&mut expected);
+    set.sort();
+    expected.sort();
+    #[cfg(feature = "dag-cbor")]
+    if decoded == x {
+      use crate::dag_cbor::DagCborCodec;
+      let bytes = DagCborCodec.encode(&x).unwrap().into_inner();
+      let mut cbor = Vec::new();
+      let r = ByteCursor::new(bytes);
+      DagCborCodec.references::<Ipld, _>(r, &mut cbor).unwrap();
+      cbor.sort();
+      if cbor != set {
+        return false;
+      }
+    }
+    scanned.is_ok()
+      && set == expected
+      && r.position() == r.get_ref().len() as u64
+  }
+
+  #[test]
+  fn skip() {
+    let json = r#" [1, {"a": [true, null], "b": -5e+3}, "\u00e9\n"] {"c": 2}"#;
+    let mut r = SliceCursor::new(json.as_bytes());
+    DagJsonCodec.skip(&mut r).unwrap();
+    assert_eq!(r.position(), json.rfind(" {").unwrap() as u64);
+    DagJsonCodec.skip(&mut r).unwrap();
+    assert_eq!(r.position(), json.len() as u64);
+    let skip = |json: &str| {
+      DagJsonCodec.skip(&mut SliceCursor::new(json.as_bytes()))
+    };
+    assert_eq!(skip("[1, {").unwrap_err().kind(), &ErrorKind::UnexpectedEof);
+    let error = skip("[1,]").unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::InvalidSyntax);
+    assert_eq!(error.offset(), Some(3));
+    // Skipping and decoding agree on the kind of syntax errors.
+    for json in &["[1,]", "[01]", r#"{"a" 1}"#, r#""\x""#, "x"] {
+      assert_eq!(skip(json).unwrap_err().kind(), &ErrorKind::InvalidSyntax);
+      let error = from_dag_json_string((*json).to_owned()).unwrap_err();
+      assert_eq!(error.kind(), &ErrorKind::InvalidSyntax);
+    }
+  }
+
+  #[test]
+  fn references_links() {
+    let link = cid(&ipld!("leaf"));
+    let ipld = ipld!({ "a": [link, { "/": "b", "c": 1 }], "d": { "/": link } });
+    let bytes = DagJsonCodec.encode(&ipld).unwrap().into_inner();
+    let mut set = Vec::new();
+    let r = ByteCursor::new(bytes);
+    DagJsonCodec.references::<Ipld, _>(r, &mut set).unwrap();
+    assert_eq!(set, vec![link, link]);
+    let json = r#"[{"/": "AAAA"}]"#;
+    let r = ByteCursor::new(json.as_bytes().to_vec());
+    let error = DagJsonCodec.references::<Ipld, _>(r, &mut set).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidCid(_)));
+    assert_eq!(error.offset(), Some(7));
+  }
+
+  #[test]
+  fn decode_limits() {
+    use crate::limits::{
+      DecodeLimits,
+      LimitExceeded,
+    };
+    let deep = format!("{}{}", "[".repeat(40), "]".repeat(40));
+    let decode = |json: &str, limits| {
+      let bytes = ByteCursor::new(json.as_bytes().to_vec());
+      DagJsonCodec.decode_with_limits::<Ipld>(bytes, limits)
+    };
+    let limits = DecodeLimits::default();
+    assert!(decode(&deep, limits).is_ok());
+    let limits = DecodeLimits { max_depth: 20, ..limits };
+    assert_eq!(
+      decode(&deep, limits).unwrap_err().into_kind(),
+      ErrorKind::LimitExceeded(LimitExceeded::Depth(20))
+    );
+    // Only the budget bounds the depth, not the recursion limit of serde_json.
+    let deeper = format!("{}{}", "[".repeat(150), "]".repeat(150));
+    let limits = DecodeLimits { max_depth: 200, ..DecodeLimits::default() };
+    assert!(decode(&deeper, limits).is_ok());
+    let json = r#"{"a": [1, 2, 3], "b": "hello"}"#;
+    let limits = DecodeLimits { max_len: 2, ..limits };
+    let error = decode(json, limits).unwrap_err();
+    let len = ErrorKind::LimitExceeded(LimitExceeded::Len(2));
+    assert_eq!(error.kind(), &len);
+    assert_eq!(error.path(), &IpldPath::from("a"));
+    let limits = DecodeLimits { max_len: 3, max_bytes_len: 4, ..limits };
+    assert_eq!(
+      decode(json, limits).unwrap_err().into_kind(),
+      ErrorKind::LimitExceeded(LimitExceeded::BytesLen(4))
+    );
+  }
+
+  #[test]
+  fn decode_errors() {
+    let error = from_dag_json_string("[1, {\"a\": tru".to_owned()).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::UnexpectedEof);
+    assert_eq!(error.path(), &IpldPath::from("1/a"));
+    let error = from_dag_json_string("{\n \"a\": x}".to_owned()).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::InvalidSyntax);
+    assert_eq!(error.offset(), Some(8));
+    let error =
+      from_dag_json_string(r#"{"/": "AAAA"}"#.to_owned()).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidCid(_)));
+    let error = from_dag_json_string(r#"{"/": "!"}"#.to_owned()).unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::InvalidCid(_)));
+    let bytes = r#"{"/": {"bytes": "!"}}"#;
+    let error = from_dag_json_string(bytes.to_owned()).unwrap_err();
+    assert_eq!(error.kind(), &ErrorKind::InvalidSyntax);
+  }
 }
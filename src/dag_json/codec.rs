@@ -1,74 +1,82 @@
-use crate::Ipld;
+use crate::{
+  codec::{
+    Error,
+    ErrorKind,
+    SliceCursor,
+    Writer,
+  },
+  limits::DecodeBudget,
+  Ipld,
+};
 use alloc::{
   borrow::ToOwned,
-  string::String,
+  string::{
+    String,
+    ToString,
+  },
 };
-use bytecursor::ByteCursor;
 use core::convert::TryFrom;
 use serde::{
   de,
-  de::Error as SerdeError,
   ser,
-  Deserialize,
   Serialize,
-  Serializer,
 };
-use serde_json::Error;
+use serde_json::error::Category;
 use sp_cid::Cid;
 use alloc::{
   collections::btree_map::BTreeMap,
   vec::Vec,
 };
-use core::fmt;
+use core::{
+  fmt,
+  mem,
+};
 
-const SPECIAL_KEY: &str = "/";
+pub(crate) const SPECIAL_KEY: &str = "/";
 
-pub fn encode(ipld: &Ipld, writer: &mut ByteCursor) -> Result<(), Error> {
-  let ipld_json = serde_json::to_string(&ipld).unwrap();
-  writer.write(ipld_json.as_bytes()).unwrap();
-  Ok(())
+pub fn encode<W: Writer>(ipld: &Ipld, writer: &mut W) -> Result<(), Error> {
+  let ipld_json = serde_json::to_string(&Wrapper(ipld))
+    .map_err(|e| Error::message(e.to_string()))?;
+  writer.write_all(ipld_json.as_bytes())
 }
 
-pub fn decode(r: &mut ByteCursor) -> Result<Ipld, Error> {
+pub fn decode(
+  r: &mut SliceCursor,
+  budget: &mut DecodeBudget,
+) -> Result<Ipld, Error> {
   let mut de = serde_json::Deserializer::from_slice(r.get_ref());
-  deserialize(&mut de)
+  // The depth limit of `budget` bounds the recursion instead.
+  de.disable_recursion_limit();
+  let mut state =
+    State { budget: mem::take(budget), kind: None, path: Vec::new() };
+  let result =
+    de::DeserializeSeed::deserialize(IpldSeed { state: &mut state }, &mut de);
+  let State { budget: used, kind, path } = state;
+  *budget = used;
+  result.map_err(|e| {
+    let kind = match (kind, e.classify()) {
+      (Some(kind), _) => kind,
+      (None, Category::Eof) => ErrorKind::UnexpectedEof,
+      (None, _) => ErrorKind::InvalidSyntax,
+    };
+    let mut error = Error::new(kind);
+    if e.line() > 0 {
+      error = error.at(offset(r.get_ref(), e.line(), e.column()));
+    }
+    path.into_iter().fold(error, Error::within)
+  })
 }
 
-impl Serialize for Ipld {
-  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-  where S: Serializer {
-    match &self {
-      Ipld::Null => serializer.serialize_none(),
-      Ipld::Bool(bool) => serializer.serialize_bool(*bool),
-      Ipld::Integer(i128) => serializer.serialize_i128(*i128),
-      Ipld::Float(f64) => serializer.serialize_f64(*f64),
-      Ipld::String(string) => serializer.serialize_str(string),
-      Ipld::Bytes(bytes) => {
-        let value = base64::encode(bytes);
-        let mut inner_map = BTreeMap::new();
-        inner_map.insert(String::from("bytes"), value);
-        let mut map = BTreeMap::new();
-        map.insert(SPECIAL_KEY, inner_map);
-
-        serializer.collect_map(map)
-      }
-      Ipld::List(list) => {
-        let wrapped = list.iter().map(|ipld| Wrapper(ipld));
-        serializer.collect_seq(wrapped)
-      }
-      Ipld::StringMap(map) => {
-        let wrapped = map.iter().map(|(key, ipld)| (key, Wrapper(ipld)));
-        serializer.collect_map(wrapped)
-      }
-      Ipld::Link(link) => {
-        let value = base64::encode(link.to_bytes());
-        let mut map = BTreeMap::new();
-        map.insert(SPECIAL_KEY, value);
-
-        serializer.collect_map(map)
-      }
-    }
-  }
+/// Returns the byte offset of the 1-based `line` and `column` that
+/// `serde_json` reports errors at.
+fn offset(bytes: &[u8], line: usize, column: usize) -> u64 {
+  let start = bytes
+    .iter()
+    .enumerate()
+    .filter(|(_, byte)| **byte == b'\n')
+    .nth(line.saturating_sub(2))
+    .map_or(0, |(pos, _)| if line > 1 { pos + 1 } else { 0 });
+  (start + column.saturating_sub(1)) as u64
 }
 
 fn serialize<S: ser::Serializer>(
@@ -105,17 +113,16 @@ fn serialize<S: ser::Serializer>(
 
       ser.collect_map(map)
     }
+    #[cfg(feature = "unleashed")]
+    Ipld::IntegerMap(_) | Ipld::Tag(..) => Err(ser::Error::custom(
+      "Integer maps and tags cannot be represented in dag-json",
+    )),
   }
 }
 
-fn deserialize<'de, D: de::Deserializer<'de>>(
-  deserializer: D,
-) -> Result<Ipld, D::Error> {
-  // Sadly such a PhantomData hack is needed
-  deserializer.deserialize_any(JsonVisitor)
-}
-
-// Needed for `collect_seq` and `collect_map` in Seserializer
+/// Serializes a value in its dag-json form, in which bytes and links are
+/// maps under the key `/`. The `Serialize` impl of `Ipld` itself writes the
+/// form that `to_ipld` turns back into the same value instead.
 struct Wrapper<'a>(&'a Ipld);
 
 impl<'a> Serialize for Wrapper<'a> {
@@ -126,9 +133,30 @@ impl<'a> Serialize for Wrapper<'a> {
 }
 
 // serde deserializer visitor that is used by Deseraliazer to decode
-// json into IPLD.
-struct JsonVisitor;
-impl<'de> de::Visitor<'de> for JsonVisitor {
+// json into IPLD, charging nested values and allocations to `budget`.
+struct JsonVisitor<'b> {
+  state: &'b mut State,
+}
+
+/// The state of decoding a single value, kept outside of the `serde_json`
+/// error so that the kind and path of a failure are not lost.
+struct State {
+  budget: DecodeBudget,
+  /// The kind of the failure, if it was not a syntax error.
+  kind: Option<ErrorKind>,
+  /// The path of the failure, innermost segment first.
+  path: Vec<String>,
+}
+
+impl State {
+  /// Records `kind` and returns a `serde` error to propagate.
+  fn fail<E: de::Error>(&mut self, kind: ErrorKind) -> E {
+    let error = E::custom(&kind);
+    self.kind = Some(kind);
+    error
+  }
+}
+impl<'de, 'b> de::Visitor<'de> for JsonVisitor<'b> {
   type Value = Ipld;
 
   fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -142,6 +170,11 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
 
   fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
   where E: de::Error {
+    let state = self.state;
+    state
+      .budget
+      .alloc_bytes(value.len())
+      .map_err(|e| state.fail(e.into()))?;
     Ok(Ipld::String(value))
   }
 
@@ -152,6 +185,8 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
 
   fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Self::Value, E>
   where E: de::Error {
+    let state = self.state;
+    state.budget.alloc_bytes(v.len()).map_err(|e| state.fail(e.into()))?;
     Ok(Ipld::Bytes(v))
   }
 
@@ -187,50 +222,75 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
 
   fn visit_seq<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
   where V: de::SeqAccess<'de> {
-    let mut vec: Vec<WrapperOwned> = Vec::new();
+    let state = self.state;
+    state.budget.enter().map_err(|e| state.fail(e.into()))?;
+    let mut vec: Vec<Ipld> = Vec::new();
 
-    while let Some(elem) = visitor.next_element()? {
+    while let Some(elem) = visitor
+      .next_element_seed(IpldSeed { state: &mut *state })
+      .inspect_err(|_| state.path.push(vec.len().to_string()))?
+    {
       vec.push(elem);
+      state.budget.check_len(vec.len()).map_err(|e| state.fail(e.into()))?;
+      state
+        .budget
+        .alloc(mem::size_of::<Ipld>())
+        .map_err(|e| state.fail(e.into()))?;
     }
 
-    let unwrapped = vec.into_iter().map(|WrapperOwned(ipld)| ipld).collect();
-    Ok(Ipld::List(unwrapped))
+    state.budget.exit();
+    Ok(Ipld::List(vec))
   }
 
   fn visit_map<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
   where V: de::MapAccess<'de> {
-    let mut values: Vec<(String, WrapperOwned)> = Vec::new();
-
-    while let Some((key, value)) = visitor.next_entry()? {
+    let state = self.state;
+    state.budget.enter().map_err(|e| state.fail(e.into()))?;
+    let mut values: Vec<(String, Ipld)> = Vec::new();
+
+    while let Some(key) = visitor.next_key::<String>()? {
+      state
+        .budget
+        .check_len(values.len() + 1)
+        .map_err(|e| state.fail(e.into()))?;
+      state
+        .budget
+        .alloc(key.len() + mem::size_of::<(String, Ipld)>())
+        .map_err(|e| state.fail(e.into()))?;
+      let value = visitor
+        .next_value_seed(IpldSeed { state: &mut *state })
+        .inspect_err(|_| state.path.push(key.clone()))?;
       values.push((key, value));
     }
+    state.budget.exit();
 
     // JSON Object represents IPLD Link if it is `{ "/": "...." }` therefor
     // we valiadet if that is the case here.
-    if let Some((key, WrapperOwned(Ipld::String(value)))) = values.first() {
+    if let Some((key, Ipld::String(value))) = values.first() {
       if key == SPECIAL_KEY && values.len() == 1 {
-        let link = base64::decode(&value).map_err(SerdeError::custom)?;
-        let cid = Cid::try_from(link).map_err(SerdeError::custom)?;
+        let link = base64::decode(&value)
+          .map_err(|e| state.fail(ErrorKind::InvalidCid(e.to_string())))?;
+        let cid = Cid::try_from(link)
+          .map_err(|e| state.fail(ErrorKind::InvalidCid(e.to_string())))?;
         return Ok(Ipld::Link(cid));
       }
     }
 
-    if let Some((first_key, WrapperOwned(Ipld::StringMap(map)))) =
-      values.first()
-    {
+    if let Some((first_key, Ipld::StringMap(map))) = values.first() {
       if let Some((key, Ipld::String(value))) = map.first_key_value() {
         if first_key == SPECIAL_KEY && key == "bytes" && values.len() == 1 {
-          let bytes = base64::decode(value).map_err(SerdeError::custom)?;
+          let bytes = base64::decode(value)
+            .map_err(|_| state.fail(ErrorKind::InvalidSyntax))?;
+          state
+            .budget
+            .alloc_bytes(bytes.len())
+            .map_err(|e| state.fail(e.into()))?;
           return Ok(Ipld::Bytes(bytes));
         }
       }
     }
 
-    let unwrapped = values
-      .into_iter()
-      .map(|(key, WrapperOwned(value))| (key, value))
-      .collect();
-    Ok(Ipld::StringMap(unwrapped))
+    Ok(Ipld::StringMap(values.into_iter().collect()))
   }
 
   fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E>
@@ -239,21 +299,18 @@ impl<'de> de::Visitor<'de> for JsonVisitor {
   }
 }
 
-// Needed for `visit_seq` and `visit_map` in Deserializer
-/// We cannot directly implement `serde::Deserializer` for `Ipld` as it is a
-/// remote type. Instead wrap it into a newtype struct and implement
-/// `serde::Deserialize` for that one. All the deserializer does is calling the
-/// `deserialize()` function we defined which returns an unwrapped `Ipld`
-/// instance. Wrap that `Ipld` instance in `Wrapper` and return it.
-/// Users of this wrapper will then unwrap it again so that they can return the
-/// expected `Ipld` instance.
-struct WrapperOwned(Ipld);
-
-impl<'de> Deserialize<'de> for WrapperOwned {
-  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+/// Deserializes an `Ipld` value, charging nested values and allocations to
+/// the budget of `state`. This is used instead of implementing
+/// `serde::Deserialize`, which cannot carry any state.
+struct IpldSeed<'b> {
+  state: &'b mut State,
+}
+
+impl<'de, 'b> de::DeserializeSeed<'de> for IpldSeed<'b> {
+  type Value = Ipld;
+
+  fn deserialize<D>(self, deserializer: D) -> Result<Ipld, D::Error>
   where D: de::Deserializer<'de> {
-    let deserialized = deserialize(deserializer);
-    // Better version of Ok(Wrapper(deserialized.unwrap()))
-    deserialized.map(Self)
+    deserializer.deserialize_any(JsonVisitor { state: self.state })
   }
 }
@@ -0,0 +1,376 @@
+use crate::{
+  codec::{
+    Error,
+    ErrorKind,
+    SliceCursor,
+  },
+  limits::DecodeBudget,
+};
+use alloc::{
+  borrow::Cow,
+  string::{
+    String,
+    ToString,
+  },
+};
+use core::{
+  convert::TryFrom,
+  iter,
+};
+use sp_cid::Cid;
+
+use super::codec::SPECIAL_KEY;
+
+/// Extends `set` with the links of the value at the position of `r`, and
+/// moves `r` past it, without decoding any other value.
+///
+/// # Errors
+///
+/// Will return `Err` if `r` does not hold a valid JSON value at its
+/// position, a link is not a valid cid, or scanning exceeded the limits of
+/// `budget`
+pub fn references<E: Extend<Cid>>(
+  r: &mut SliceCursor,
+  set: &mut E,
+  budget: &mut DecodeBudget,
+) -> Result<(), Error> {
+  scan(r, budget, &mut |offset, value| {
+    let cid = base64::decode(value)
+      .map_err(|e| e.to_string())
+      .and_then(|bytes| Cid::try_from(bytes).map_err(|e| e.to_string()))
+      .map_err(|e| Error::new(ErrorKind::InvalidCid(e)).at(offset))?;
+    set.extend(iter::once(cid));
+    Ok(())
+  })
+}
+
+/// Moves `r` past the value at its position, after checking its syntax.
+///
+/// # Errors
+///
+/// Will return `Err` if `r` does not hold a valid JSON value at its
+/// position, or scanning exceeded the limits of `budget`
+pub fn skip(
+  r: &mut SliceCursor,
+  budget: &mut DecodeBudget,
+) -> Result<(), Error> {
+  scan(r, budget, &mut |_, _| Ok(()))
+}
+
+/// Scans the value at the position of `r`, calling `link` with the offset
+/// and the string of every `{"/": "..."}` link.
+///
+/// # Errors
+///
+/// Will return `Err` if `r` does not hold a valid JSON value at its
+/// position, `link` fails, or scanning exceeded the limits of `budget`
+fn scan(
+  r: &mut SliceCursor,
+  budget: &mut DecodeBudget,
+  link: &mut impl FnMut(u64, &str) -> Result<(), Error>,
+) -> Result<(), Error> {
+  let mut scanner = Scanner { bytes: r.get_ref(), pos: r.pos };
+  scanner.value(budget, link)?;
+  r.pos = scanner.pos;
+  Ok(())
+}
+
+/// A JSON string as it appears in the input, quotes included.
+struct Str<'a> {
+  raw: &'a [u8],
+  escaped: bool,
+}
+
+impl<'a> Str<'a> {
+  /// Returns the unescaped string.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the string cannot be unescaped, which the scanner
+  /// already rules out
+  fn unescape(&self) -> Result<Cow<'a, str>, Error> {
+    if self.escaped {
+      let string = serde_json::from_slice::<String>(self.raw)
+        .map_err(|e| Error::message(e.to_string()))?;
+      Ok(Cow::Owned(string))
+    }
+    else {
+      let inner = &self.raw[1..self.raw.len() - 1];
+      let string =
+        core::str::from_utf8(inner).map_err(|_| ErrorKind::InvalidUtf8)?;
+      Ok(Cow::Borrowed(string))
+    }
+  }
+}
+
+/// A cursor over JSON input, which checks the syntax of values without
+/// decoding them.
+struct Scanner<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+  /// Returns an error of the given kind at the current position.
+  fn error(&self, kind: ErrorKind) -> Error {
+    Error::new(kind).at(self.pos as u64)
+  }
+
+  /// Returns the error for the byte at the current position, which cannot
+  /// come next.
+  fn unexpected(&self) -> Error {
+    match self.bytes.get(self.pos) {
+      Some(_) => self.error(ErrorKind::InvalidSyntax),
+      None => self.error(ErrorKind::UnexpectedEof),
+    }
+  }
+
+  /// Returns the error for the byte that was just read.
+  fn unexpected_last(&mut self) -> Error {
+    self.pos -= 1;
+    self.unexpected()
+  }
+
+  /// # Errors
+  ///
+  /// Will return `Err` if the input has ended
+  fn next(&mut self) -> Result<u8, Error> {
+    let byte =
+      self.bytes.get(self.pos).copied().ok_or_else(|| self.unexpected())?;
+    self.pos += 1;
+    Ok(byte)
+  }
+
+  /// Skips whitespace and returns the next byte without consuming it.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the input has ended
+  fn peek(&mut self) -> Result<u8, Error> {
+    while matches!(self.bytes.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r'))
+    {
+      self.pos += 1;
+    }
+    self.bytes.get(self.pos).copied().ok_or_else(|| self.unexpected())
+  }
+
+  /// Returns whether the next byte is `byte`, without skipping whitespace.
+  fn at(&self, byte: u8) -> bool { self.bytes.get(self.pos) == Some(&byte) }
+
+  /// # Errors
+  ///
+  /// Will return `Err` if the input does not continue with `literal`
+  fn literal(&mut self, literal: &[u8]) -> Result<(), Error> {
+    for expected in literal {
+      if self.next()? != *expected {
+        return Err(self.unexpected_last());
+      }
+    }
+    Ok(())
+  }
+
+  /// Skips a run of decimal digits.
+  fn digits(&mut self) {
+    while matches!(self.bytes.get(self.pos), Some(b'0'..=b'9')) {
+      self.pos += 1;
+    }
+  }
+
+  /// # Errors
+  ///
+  /// Will return `Err` if the next byte is not a decimal digit
+  fn digit(&mut self) -> Result<(), Error> {
+    match self.next()? {
+      b'0'..=b'9' => Ok(()),
+      _ => Err(self.unexpected_last()),
+    }
+  }
+
+  /// # Errors
+  ///
+  /// Will return `Err` if the input does not continue with a JSON number
+  fn number(&mut self) -> Result<(), Error> {
+    if self.at(b'-') {
+      self.pos += 1;
+    }
+    match self.next()? {
+      b'0' => {}
+      b'1'..=b'9' => self.digits(),
+      _ => return Err(self.unexpected_last()),
+    }
+    if self.at(b'.') {
+      self.pos += 1;
+      self.digit()?;
+      self.digits();
+    }
+    if self.at(b'e') || self.at(b'E') {
+      self.pos += 1;
+      if self.at(b'+') || self.at(b'-') {
+        self.pos += 1;
+      }
+      self.digit()?;
+      self.digits();
+    }
+    Ok(())
+  }
+
+  /// Reads the rest of an escape sequence, after its backslash, and returns
+  /// the length in bytes of the character it stands for.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the escape sequence is not valid
+  fn escape(&mut self) -> Result<usize, Error> {
+    match self.next()? {
+      b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => Ok(1),
+      b'u' => {
+        let mut code = 0;
+        for _ in 0..4 {
+          let digit = char::from(self.next()?).to_digit(16);
+          code = code * 16 + digit.ok_or_else(|| self.unexpected_last())?;
+        }
+        // Each half of a surrogate pair stands for half of a 4 byte
+        // character.
+        Ok(match code {
+          0..=0x7f => 1,
+          0x80..=0x7ff | 0xd800..=0xdfff => 2,
+          _ => 3,
+        })
+      }
+      _ => Err(self.unexpected_last()),
+    }
+  }
+
+  /// # Errors
+  ///
+  /// Will return `Err` if the input does not continue with a valid JSON
+  /// string, or the string is longer than the limit of `budget`
+  fn string(&mut self, budget: &DecodeBudget) -> Result<Str<'a>, Error> {
+    let start = self.pos;
+    self.pos += 1;
+    let mut len = 0;
+    let mut escaped = false;
+    loop {
+      match self.next()? {
+        b'"' => break,
+        b'\\' => {
+          escaped = true;
+          len += self.escape()?;
+        }
+        0x00..=0x1f => return Err(self.unexpected_last()),
+        _ => len += 1,
+      }
+    }
+    let at = |e: Error| e.at(start as u64);
+    budget.check_bytes_len(len).map_err(|e| at(e.into()))?;
+    let raw = &self.bytes[start..self.pos];
+    core::str::from_utf8(raw).map_err(|_| at(ErrorKind::InvalidUtf8.into()))?;
+    Ok(Str { raw, escaped })
+  }
+
+  /// # Errors
+  ///
+  /// Will return `Err` if the input does not continue with a valid JSON
+  /// value, `link` fails, or scanning exceeded the limits of `budget`
+  fn value(
+    &mut self,
+    budget: &mut DecodeBudget,
+    link: &mut impl FnMut(u64, &str) -> Result<(), Error>,
+  ) -> Result<(), Error> {
+    match self.peek()? {
+      b'[' => self.list(budget, link),
+      b'{' => self.map(budget, link),
+      b'"' => self.string(budget).map(|_| ()),
+      b't' => self.literal(b"true"),
+      b'f' => self.literal(b"false"),
+      b'n' => self.literal(b"null"),
+      b'-' | b'0'..=b'9' => self.number(),
+      _ => Err(self.unexpected()),
+    }
+  }
+
+  /// # Errors
+  ///
+  /// Will return `Err` if the input does not continue with a valid JSON
+  /// array, `link` fails, or scanning exceeded the limits of `budget`
+  fn list(
+    &mut self,
+    budget: &mut DecodeBudget,
+    link: &mut impl FnMut(u64, &str) -> Result<(), Error>,
+  ) -> Result<(), Error> {
+    budget.enter().map_err(|e| self.error(e.into()))?;
+    self.pos += 1;
+    if self.peek()? == b']' {
+      self.pos += 1;
+      budget.exit();
+      return Ok(());
+    }
+    let mut read = 0;
+    loop {
+      read += 1;
+      budget.check_len(read).map_err(|e| self.error(e.into()))?;
+      self.value(budget, link)?;
+      match self.peek()? {
+        b',' => self.pos += 1,
+        b']' => break,
+        _ => return Err(self.unexpected()),
+      }
+    }
+    self.pos += 1;
+    budget.exit();
+    Ok(())
+  }
+
+  /// Scans a JSON object, which is a link if its only key is `/` and its
+  /// value a string.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the input does not continue with a valid JSON
+  /// object, `link` fails, or scanning exceeded the limits of `budget`
+  fn map(
+    &mut self,
+    budget: &mut DecodeBudget,
+    link: &mut impl FnMut(u64, &str) -> Result<(), Error>,
+  ) -> Result<(), Error> {
+    budget.enter().map_err(|e| self.error(e.into()))?;
+    self.pos += 1;
+    if self.peek()? == b'}' {
+      self.pos += 1;
+      budget.exit();
+      return Ok(());
+    }
+    let mut read = 0;
+    let mut special = None;
+    loop {
+      read += 1;
+      budget.check_len(read).map_err(|e| self.error(e.into()))?;
+      if self.peek()? != b'"' {
+        return Err(self.unexpected());
+      }
+      let key = self.string(budget)?;
+      if self.peek()? != b':' {
+        return Err(self.unexpected());
+      }
+      self.pos += 1;
+      if read == 1 && self.peek()? == b'"' && key.unescape()? == SPECIAL_KEY {
+        let offset = self.pos as u64;
+        special = Some((offset, self.string(budget)?));
+      }
+      else {
+        self.value(budget, link)?;
+      }
+      match self.peek()? {
+        b',' => self.pos += 1,
+        b'}' => break,
+        _ => return Err(self.unexpected()),
+      }
+    }
+    self.pos += 1;
+    budget.exit();
+    match special {
+      Some((offset, value)) if read == 1 => link(offset, &value.unescape()?),
+      _ => Ok(()),
+    }
+  }
+}
@@ -0,0 +1,388 @@
+use crate::{
+  ipld::Ipld,
+  path::IpldPath,
+};
+
+use alloc::{
+  collections::btree_map::BTreeMap,
+  string::ToString,
+  vec::Vec,
+};
+use core::fmt::Display;
+
+/// A single difference between two IPLD values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpldChange {
+  /// A value present only in the new value.
+  Added {
+    /// Where the value was added.
+    path: IpldPath,
+    /// The added value.
+    value: Ipld,
+  },
+  /// A value present only in the old value.
+  Removed {
+    /// Where the value was removed.
+    path: IpldPath,
+    /// The removed value.
+    value: Ipld,
+  },
+  /// A value that was replaced by a different one.
+  Replaced {
+    /// Where the value was replaced.
+    path: IpldPath,
+    /// The old value.
+    old: Ipld,
+    /// The new value.
+    new: Ipld,
+  },
+}
+
+impl IpldChange {
+  /// Returns the path of the change.
+  pub fn path(&self) -> &IpldPath {
+    match self {
+      Self::Added { path, .. } => path,
+      Self::Removed { path, .. } => path,
+      Self::Replaced { path, .. } => path,
+    }
+  }
+}
+
+/// How `Ipld::diff_with` aligns the elements of two lists.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ListAlignment {
+  /// Compares elements at the same index. Extra elements at the end are
+  /// added, or removed from the last one down.
+  Index,
+  /// Matches equal elements along the longest common subsequence. Elements
+  /// outside of it are removed or added whole. Paths index into the list as
+  /// the changes before them left it, so that the changes apply in order.
+  /// Takes time proportional to the product of the lengths of the lists once
+  /// their common prefix and suffix are trimmed, and linear space.
+  Lcs,
+}
+
+impl Ipld {
+  /// Returns the changes that turn `self` into `other`, aligning lists by
+  /// index. Links are compared by cid without being loaded.
+  pub fn diff(&self, other: &Self) -> Vec<IpldChange> {
+    self.diff_with(other, ListAlignment::Index)
+  }
+
+  /// Returns the changes that turn `self` into `other`, aligning lists with
+  /// `lists`. Links are compared by cid without being loaded.
+  pub fn diff_with(
+    &self,
+    other: &Self,
+    lists: ListAlignment,
+  ) -> Vec<IpldChange> {
+    let mut changes = Vec::new();
+    diff(&mut changes, IpldPath::new(), self, other, lists);
+    changes
+  }
+}
+
+fn diff(
+  changes: &mut Vec<IpldChange>,
+  path: IpldPath,
+  old: &Ipld,
+  new: &Ipld,
+  lists: ListAlignment,
+) {
+  // Containers of the same kind are compared entry by entry, so that every
+  // leaf is compared only once.
+  match (old, new) {
+    (Ipld::StringMap(old), Ipld::StringMap(new)) => {
+      diff_map(changes, &path, old, new, lists)
+    }
+    #[cfg(feature = "unleashed")]
+    (Ipld::IntegerMap(old), Ipld::IntegerMap(new)) => {
+      diff_map(changes, &path, old, new, lists)
+    }
+    #[cfg(feature = "unleashed")]
+    (Ipld::Tag(old_tag, old), Ipld::Tag(new_tag, new))
+      if old_tag == new_tag =>
+    {
+      diff(changes, path, old, new, lists)
+    }
+    (Ipld::List(old), Ipld::List(new)) => match lists {
+      ListAlignment::Index => diff_list_index(changes, &path, old, new, lists),
+      ListAlignment::Lcs => diff_list_lcs(changes, &path, old, new),
+    },
+    _ => {
+      if old != new {
+        changes.push(IpldChange::Replaced {
+          path,
+          old: old.clone(),
+          new: new.clone(),
+        });
+      }
+    }
+  }
+}
+
+fn diff_map<K: Ord + Display>(
+  changes: &mut Vec<IpldChange>,
+  path: &IpldPath,
+  old: &BTreeMap<K, Ipld>,
+  new: &BTreeMap<K, Ipld>,
+  lists: ListAlignment,
+) {
+  let mut old_iter = old.iter().peekable();
+  let mut new_iter = new.iter().peekable();
+  loop {
+    match (old_iter.peek(), new_iter.peek()) {
+      (Some((old_key, old_value)), Some((new_key, new_value))) => {
+        if old_key < new_key {
+          changes.push(IpldChange::Removed {
+            path: path.join(old_key.to_string()),
+            value: (*old_value).clone(),
+          });
+          old_iter.next();
+        }
+        else if new_key < old_key {
+          changes.push(IpldChange::Added {
+            path: path.join(new_key.to_string()),
+            value: (*new_value).clone(),
+          });
+          new_iter.next();
+        }
+        else {
+          let key_path = path.join(old_key.to_string());
+          diff(changes, key_path, old_value, new_value, lists);
+          old_iter.next();
+          new_iter.next();
+        }
+      }
+      (Some((key, value)), None) => {
+        changes.push(IpldChange::Removed {
+          path: path.join(key.to_string()),
+          value: (*value).clone(),
+        });
+        old_iter.next();
+      }
+      (None, Some((key, value))) => {
+        changes.push(IpldChange::Added {
+          path: path.join(key.to_string()),
+          value: (*value).clone(),
+        });
+        new_iter.next();
+      }
+      (None, None) => return,
+    }
+  }
+}
+
+fn diff_list_index(
+  changes: &mut Vec<IpldChange>,
+  path: &IpldPath,
+  old: &[Ipld],
+  new: &[Ipld],
+  lists: ListAlignment,
+) {
+  for (i, (old, new)) in old.iter().zip(new).enumerate() {
+    diff(changes, path.join(i.to_string()), old, new, lists);
+  }
+  for (i, value) in old.iter().enumerate().skip(new.len()).rev() {
+    let path = path.join(i.to_string());
+    changes.push(IpldChange::Removed { path, value: value.clone() });
+  }
+  for (i, value) in new.iter().enumerate().skip(old.len()) {
+    let path = path.join(i.to_string());
+    changes.push(IpldChange::Added { path, value: value.clone() });
+  }
+}
+
+fn diff_list_lcs(
+  changes: &mut Vec<IpldChange>,
+  path: &IpldPath,
+  old: &[Ipld],
+  new: &[Ipld],
+) {
+  // Equal elements share an id, so that the search for the longest common
+  // subsequence compares integers instead of whole values.
+  let mut ids = BTreeMap::new();
+  let mut id = |ipld| {
+    let next = ids.len();
+    *ids.entry(ipld).or_insert(next)
+  };
+  let old_ids: Vec<usize> = old.iter().map(&mut id).collect();
+  let new_ids: Vec<usize> = new.iter().map(&mut id).collect();
+  let mut pairs = Vec::new();
+  lcs(&old_ids, &new_ids, (0, 0), &mut pairs);
+  // The elements before `j` already match the new list, so the old element
+  // `k` is at `j + k - i` until it is removed.
+  let (mut i, mut j) = (0, 0);
+  for (next_i, next_j) in pairs.into_iter().chain(Some((old.len(), new.len())))
+  {
+    for (k, value) in old.iter().enumerate().take(next_i).skip(i).rev() {
+      let path = path.join((j + k - i).to_string());
+      changes.push(IpldChange::Removed { path, value: value.clone() });
+    }
+    for (j, value) in new.iter().enumerate().take(next_j).skip(j) {
+      let path = path.join(j.to_string());
+      changes.push(IpldChange::Added { path, value: value.clone() });
+    }
+    i = next_i + 1;
+    j = next_j + 1;
+  }
+}
+
+/// Pushes to `pairs`, in order, the indices of the elements of a longest
+/// common subsequence of `old` and `new`, offset by `at`.
+///
+/// This is Hirschberg's algorithm, which takes time proportional to the
+/// product of the lengths but only linear space.
+fn lcs(
+  old: &[usize],
+  new: &[usize],
+  at: (usize, usize),
+  pairs: &mut Vec<(usize, usize)>,
+) {
+  let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+  pairs.extend((0..prefix).map(|k| (at.0 + k, at.1 + k)));
+  let (old, new) = (&old[prefix..], &new[prefix..]);
+  let suffix =
+    old.iter().rev().zip(new.iter().rev()).take_while(|(a, b)| a == b).count();
+  let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+  let at = (at.0 + prefix, at.1 + prefix);
+  if old.len() == 1 {
+    if let Some(k) = new.iter().position(|x| *x == old[0]) {
+      pairs.push((at.0, at.1 + k));
+    }
+  }
+  else if !old.is_empty() && !new.is_empty() {
+    // Split `old` in half and `new` where the subsequences of both halves
+    // add up to the longest one.
+    let mid = old.len() / 2;
+    let forward = lcs_lengths(old[..mid].iter(), new.iter());
+    let mut backward = lcs_lengths(old[mid..].iter().rev(), new.iter().rev());
+    backward.reverse();
+    let split = (0..=new.len())
+      .rev()
+      .max_by_key(|k| forward[*k] + backward[*k])
+      .unwrap_or(0);
+    lcs(&old[..mid], &new[..split], at, pairs);
+    lcs(&old[mid..], &new[split..], (at.0 + mid, at.1 + split), pairs);
+  }
+  let end = (at.0 + old.len(), at.1 + new.len());
+  pairs.extend((0..suffix).map(|k| (end.0 + k, end.1 + k)));
+}
+
+/// Returns the lengths of the longest common subsequences of `old` and every
+/// prefix of `new`, indexed by the length of the prefix.
+fn lcs_lengths<'a>(
+  old: impl Iterator<Item = &'a usize>,
+  new: impl Iterator<Item = &'a usize> + Clone,
+) -> Vec<usize> {
+  let mut lengths = vec![0; new.clone().count() + 1];
+  for a in old {
+    let mut diagonal = 0;
+    for (k, b) in new.clone().enumerate() {
+      let above = lengths[k + 1];
+      lengths[k + 1] =
+        if a == b { diagonal + 1 } else { above.max(lengths[k]) };
+      diagonal = above;
+    }
+  }
+  lengths
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::ipld::tests::arbitrary_cid;
+  use alloc::borrow::ToOwned;
+  use quickcheck::Gen;
+
+  fn map(entries: Vec<(&str, Ipld)>) -> Ipld {
+    Ipld::StringMap(
+      entries.into_iter().map(|(k, v)| (k.to_owned(), v)).collect(),
+    )
+  }
+
+  #[test]
+  fn diff_maps() {
+    let mut g = Gen::new(10);
+    let (a, b) = (arbitrary_cid(&mut g), arbitrary_cid(&mut g));
+    let old = map(vec![
+      ("keep", Ipld::Integer(1)),
+      ("gone", Ipld::Bool(true)),
+      ("link", Ipld::Link(a)),
+      ("nested", map(vec![("x", Ipld::Integer(1))])),
+    ]);
+    let new = map(vec![
+      ("keep", Ipld::Integer(1)),
+      ("link", Ipld::Link(b)),
+      ("nested", map(vec![("x", Ipld::Integer(2))])),
+      ("new", Ipld::Null),
+    ]);
+    assert_eq!(old.diff(&new), vec![
+      IpldChange::Removed { path: "gone".into(), value: Ipld::Bool(true) },
+      IpldChange::Replaced {
+        path: "link".into(),
+        old: Ipld::Link(a),
+        new: Ipld::Link(b),
+      },
+      IpldChange::Replaced {
+        path: "nested/x".into(),
+        old: Ipld::Integer(1),
+        new: Ipld::Integer(2),
+      },
+      IpldChange::Added { path: "new".into(), value: Ipld::Null },
+    ]);
+    assert!(old.diff(&old).is_empty());
+  }
+
+  #[test]
+  fn diff_lists() {
+    let old = Ipld::List(vec![Ipld::Integer(1), Ipld::Integer(2)]);
+    let new =
+      Ipld::List(vec![Ipld::Integer(0), Ipld::Integer(1), Ipld::Integer(2)]);
+    assert_eq!(old.diff(&new), vec![
+      IpldChange::Replaced {
+        path: "0".into(),
+        old: Ipld::Integer(1),
+        new: Ipld::Integer(0),
+      },
+      IpldChange::Replaced {
+        path: "1".into(),
+        old: Ipld::Integer(2),
+        new: Ipld::Integer(1),
+      },
+      IpldChange::Added { path: "2".into(), value: Ipld::Integer(2) },
+    ]);
+    assert_eq!(old.diff_with(&new, ListAlignment::Lcs), vec![
+      IpldChange::Added { path: "0".into(), value: Ipld::Integer(0) },
+    ]);
+    assert_eq!(new.diff_with(&old, ListAlignment::Lcs), vec![
+      IpldChange::Removed { path: "0".into(), value: Ipld::Integer(0) },
+    ]);
+    // Removals come last to first, so that each path is still valid.
+    let old = Ipld::List((0..4).map(Ipld::Integer).collect());
+    let new = Ipld::List(vec![Ipld::Integer(0), Ipld::Integer(3)]);
+    let removed = |i: usize| IpldChange::Removed {
+      path: i.to_string().as_str().into(),
+      value: Ipld::Integer(i as i128),
+    };
+    assert_eq!(old.diff_with(&new, ListAlignment::Lcs), vec![
+      removed(2),
+      removed(1),
+    ]);
+    let new = Ipld::List(vec![Ipld::Integer(0), Ipld::Integer(1)]);
+    assert_eq!(old.diff(&new), vec![removed(3), removed(2)]);
+  }
+
+  #[quickcheck]
+  fn lcs_is_longest(old: Vec<u8>, new: Vec<u8>) -> bool {
+    let old: Vec<usize> = old.into_iter().map(|x| usize::from(x % 4)).collect();
+    let new: Vec<usize> = new.into_iter().map(|x| usize::from(x % 4)).collect();
+    let mut pairs = Vec::new();
+    lcs(&old, &new, (0, 0), &mut pairs);
+    let ordered = pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1);
+    let common = pairs.iter().all(|(i, j)| old[*i] == new[*j]);
+    let longest = lcs_lengths(old.iter(), new.iter())[new.len()];
+    ordered && common && pairs.len() == longest
+  }
+}
@@ -1,16 +1,46 @@
-use alloc::{
-  string::String,
+use crate::{
+  convert::ConversionError,
+  path::{
+    parse_index,
+    IpldPath,
+    PathError,
+    PathErrorKind,
+  },
 };
-use sp_cid::Cid;
 use alloc::{
   borrow::ToOwned,
   boxed::Box,
-  collections::btree_map::BTreeMap,
+  collections::{
+    btree_map::BTreeMap,
+    vec_deque::VecDeque,
+  },
+  string::{
+    String,
+    ToString,
+  },
   vec::Vec,
 };
+use core::{
+  any::type_name,
+  cmp::Ordering,
+  convert::TryFrom,
+  hash::{
+    Hash,
+    Hasher,
+  },
+  ops::Index,
+};
+use sp_cid::Cid;
 
 /// IPLD data format
-#[derive(Clone, PartialEq)]
+///
+/// Values are totally ordered first by kind, in the order of the `IpldKind`
+/// variants, then by content: floats in IEEE total order, lists and maps
+/// lexicographically and links by cid. Two values are equal exactly when
+/// their dag-cbor encodings are equal, so all NaNs are equal, `0.0` differs
+/// from `-0.0`, and an empty `Ipld::IntegerMap` equals an empty
+/// `Ipld::StringMap`.
+#[derive(Clone)]
 pub enum Ipld {
   /// Represents the absence of a value or the value undefined.
   Null,
@@ -30,6 +60,64 @@ pub enum Ipld {
   StringMap(BTreeMap<String, Ipld>),
   /// Represents a link to an Ipld node.
   Link(Cid),
+  /// Represents a map of integers. Empty maps are always decoded as
+  /// `Ipld::StringMap`.
+  #[cfg(feature = "unleashed")]
+  IntegerMap(BTreeMap<i64, Ipld>),
+  /// Represents a value tagged with a CBOR semantic tag. Tag 42 is reserved
+  /// for links and cannot be encoded.
+  #[cfg(feature = "unleashed")]
+  Tag(u64, Box<Ipld>),
+}
+
+/// The kind of an IPLD value, as defined by the IPLD data model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IpldKind {
+  /// The kind of `Ipld::Null`.
+  Null,
+  /// The kind of `Ipld::Bool`.
+  Bool,
+  /// The kind of `Ipld::Integer`.
+  Integer,
+  /// The kind of `Ipld::Float`.
+  Float,
+  /// The kind of `Ipld::String`.
+  String,
+  /// The kind of `Ipld::Bytes`.
+  Bytes,
+  /// The kind of `Ipld::List`.
+  List,
+  /// The kind of `Ipld::StringMap`.
+  StringMap,
+  /// The kind of `Ipld::Link`.
+  Link,
+  /// The kind of `Ipld::IntegerMap`.
+  #[cfg(feature = "unleashed")]
+  IntegerMap,
+  /// The kind of `Ipld::Tag`.
+  #[cfg(feature = "unleashed")]
+  Tag,
+}
+
+impl core::fmt::Display for IpldKind {
+  fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+    let name = match self {
+      Self::Null => "null",
+      Self::Bool => "bool",
+      Self::Integer => "integer",
+      Self::Float => "float",
+      Self::String => "string",
+      Self::Bytes => "bytes",
+      Self::List => "list",
+      Self::StringMap => "string map",
+      Self::Link => "link",
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap => "integer map",
+      #[cfg(feature = "unleashed")]
+      Self::Tag => "tag",
+    };
+    write!(f, "{}", name)
+  }
 }
 
 impl core::fmt::Debug for Ipld {
@@ -45,11 +133,212 @@ impl core::fmt::Debug for Ipld {
       List(l) => write!(f, "{:?}", l),
       StringMap(m) => write!(f, "{:?}", m),
       Link(cid) => write!(f, "{}", cid),
+      #[cfg(feature = "unleashed")]
+      IntegerMap(m) => write!(f, "{:?}", m),
+      #[cfg(feature = "unleashed")]
+      Tag(tag, ipld) => write!(f, "{}({:?})", tag, ipld),
+    }
+  }
+}
+
+/// Returns `x`, with every NaN mapped to the same value since
+/// they share a single dag-cbor encoding.
+fn canonical_float(x: f64) -> f64 { if x.is_nan() { f64::NAN } else { x } }
+
+impl PartialEq for Ipld {
+  fn eq(&self, other: &Self) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for Ipld {}
+
+impl PartialOrd for Ipld {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl Ord for Ipld {
+  fn cmp(&self, other: &Self) -> Ordering {
+    use Ipld::*;
+    match (self, other) {
+      (Null, Null) => Ordering::Equal,
+      (Bool(a), Bool(b)) => a.cmp(b),
+      (Integer(a), Integer(b)) => a.cmp(b),
+      (Float(a), Float(b)) => {
+        canonical_float(*a).total_cmp(&canonical_float(*b))
+      }
+      (String(a), String(b)) => a.cmp(b),
+      (Bytes(a), Bytes(b)) => a.cmp(b),
+      (List(a), List(b)) => a.cmp(b),
+      (StringMap(a), StringMap(b)) => a.cmp(b),
+      (Link(a), Link(b)) => a.cmp(b),
+      #[cfg(feature = "unleashed")]
+      (IntegerMap(a), IntegerMap(b)) => a.cmp(b),
+      #[cfg(feature = "unleashed")]
+      (StringMap(a), IntegerMap(b)) if b.is_empty() => a.len().cmp(&0),
+      #[cfg(feature = "unleashed")]
+      (IntegerMap(a), StringMap(b)) if a.is_empty() => 0.cmp(&b.len()),
+      #[cfg(feature = "unleashed")]
+      (Tag(a, x), Tag(b, y)) => (a, x).cmp(&(b, y)),
+      _ => self.order_kind().cmp(&other.order_kind()),
+    }
+  }
+}
+
+impl Hash for Ipld {
+  fn hash<H: Hasher>(&self, state: &mut H) {
+    self.order_kind().hash(state);
+    match self {
+      Self::Null => {}
+      Self::Bool(b) => b.hash(state),
+      Self::Integer(i) => i.hash(state),
+      Self::Float(x) => canonical_float(*x).to_bits().hash(state),
+      Self::String(s) => s.hash(state),
+      Self::Bytes(b) => b.hash(state),
+      Self::List(l) => l.hash(state),
+      Self::StringMap(m) => m.hash(state),
+      Self::Link(cid) => Hash::hash(cid, state),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(m) if m.is_empty() => {
+        BTreeMap::<String, Self>::new().hash(state)
+      }
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(m) => m.hash(state),
+      #[cfg(feature = "unleashed")]
+      Self::Tag(tag, ipld) => (tag, ipld).hash(state),
     }
   }
 }
 
 impl Ipld {
+  /// Returns the kind the value is ordered and hashed as. Empty integer maps
+  /// are encoded like empty string maps, so they are ordered as one.
+  fn order_kind(&self) -> IpldKind {
+    match self {
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(m) if m.is_empty() => IpldKind::StringMap,
+      _ => self.kind(),
+    }
+  }
+
+  /// Returns the kind of the value.
+  pub fn kind(&self) -> IpldKind {
+    match self {
+      Self::Null => IpldKind::Null,
+      Self::Bool(_) => IpldKind::Bool,
+      Self::Integer(_) => IpldKind::Integer,
+      Self::Float(_) => IpldKind::Float,
+      Self::String(_) => IpldKind::String,
+      Self::Bytes(_) => IpldKind::Bytes,
+      Self::List(_) => IpldKind::List,
+      Self::StringMap(_) => IpldKind::StringMap,
+      Self::Link(_) => IpldKind::Link,
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(_) => IpldKind::IntegerMap,
+      #[cfg(feature = "unleashed")]
+      Self::Tag(..) => IpldKind::Tag,
+    }
+  }
+
+  /// Returns the value if it is a bool.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not an `Ipld::Bool`
+  pub fn as_bool(&self) -> Result<bool, ConversionError> {
+    match self {
+      Self::Bool(b) => Ok(*b),
+      _ => Err(ConversionError::wrong_kind(IpldKind::Bool, self)),
+    }
+  }
+
+  /// Returns the value as an integer of type `T`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not an `Ipld::Integer` or does not fit
+  /// into `T`
+  pub fn as_int<T: TryFrom<i128>>(&self) -> Result<T, ConversionError> {
+    match self {
+      Self::Integer(i) => T::try_from(*i).map_err(|_| {
+        ConversionError::OutOfRange { value: *i, target: type_name::<T>() }
+      }),
+      _ => Err(ConversionError::wrong_kind(IpldKind::Integer, self)),
+    }
+  }
+
+  /// Returns the value if it is a float.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not an `Ipld::Float`
+  pub fn as_float(&self) -> Result<f64, ConversionError> {
+    match self {
+      Self::Float(f) => Ok(*f),
+      _ => Err(ConversionError::wrong_kind(IpldKind::Float, self)),
+    }
+  }
+
+  /// Returns the value if it is a string.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not an `Ipld::String`
+  pub fn as_str(&self) -> Result<&str, ConversionError> {
+    match self {
+      Self::String(s) => Ok(s),
+      _ => Err(ConversionError::wrong_kind(IpldKind::String, self)),
+    }
+  }
+
+  /// Returns the value if it is a byte string.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not an `Ipld::Bytes`
+  pub fn as_bytes(&self) -> Result<&[u8], ConversionError> {
+    match self {
+      Self::Bytes(b) => Ok(b),
+      _ => Err(ConversionError::wrong_kind(IpldKind::Bytes, self)),
+    }
+  }
+
+  /// Returns the elements of the value if it is a list.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not an `Ipld::List`
+  pub fn as_list(&self) -> Result<&[Self], ConversionError> {
+    match self {
+      Self::List(l) => Ok(l),
+      _ => Err(ConversionError::wrong_kind(IpldKind::List, self)),
+    }
+  }
+
+  /// Returns the entries of the value if it is a string map.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not an `Ipld::StringMap`
+  pub fn as_map(&self) -> Result<&BTreeMap<String, Self>, ConversionError> {
+    match self {
+      Self::StringMap(m) => Ok(m),
+      _ => Err(ConversionError::wrong_kind(IpldKind::StringMap, self)),
+    }
+  }
+
+  /// Returns the value if it is a link.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not an `Ipld::Link`
+  pub fn as_link(&self) -> Result<&Cid, ConversionError> {
+    match self {
+      Self::Link(cid) => Ok(cid),
+      _ => Err(ConversionError::wrong_kind(IpldKind::Link, self)),
+    }
+  }
+
   /// Returns an iterator.
   pub fn iter(&self) -> IpldIter<'_> {
     IpldIter { stack: vec![Box::new(vec![self].into_iter())] }
@@ -63,6 +352,269 @@ impl Ipld {
       }
     }
   }
+
+  /// Returns an iterator over every value and its path, in the given order.
+  pub fn walk(&self, order: WalkOrder) -> IpldWalk<'_> {
+    let mut queue = VecDeque::new();
+    queue.push_back((IpldPath::new(), self));
+    IpldWalk { order, queue, expand: None }
+  }
+
+  /// Returns the references to other blocks, together with the path at which
+  /// each of them appears.
+  pub fn references_with_paths<E: Extend<(IpldPath, Cid)>>(
+    &self,
+    set: &mut E,
+  ) {
+    for (path, ipld) in self.walk(WalkOrder::DepthFirst) {
+      if let Ipld::Link(cid) = ipld {
+        set.extend(core::iter::once((path, cid.to_owned())));
+      }
+    }
+  }
+
+  /// Calls `f` on every value in depth-first order, parents before their
+  /// children. A value replaced by `f` is walked after the replacement.
+  pub fn walk_mut<F: FnMut(&mut Self)>(&mut self, mut f: F) {
+    let mut stack = vec![self];
+    while let Some(ipld) = stack.pop() {
+      f(ipld);
+      match ipld {
+        Self::List(list) => stack.extend(list.iter_mut().rev()),
+        Self::StringMap(map) => stack.extend(map.values_mut().rev()),
+        #[cfg(feature = "unleashed")]
+        Self::IntegerMap(map) => stack.extend(map.values_mut().rev()),
+        #[cfg(feature = "unleashed")]
+        Self::Tag(_, ipld) => stack.push(ipld),
+        _ => {}
+      }
+    }
+  }
+
+  /// Rebuilds the value bottom-up, replacing every value with the result of
+  /// `f`. Children are transformed before `f` is called on their parent.
+  pub fn transform<F: FnMut(Self) -> Self>(self, mut f: F) -> Self {
+    let mut tasks = vec![Transform::Visit(self)];
+    let mut done: Vec<Self> = Vec::new();
+    while let Some(task) = tasks.pop() {
+      let ipld = match task {
+        Transform::Visit(Self::List(list)) => {
+          tasks.push(Transform::List(list.len()));
+          tasks.extend(list.into_iter().rev().map(Transform::Visit));
+          continue;
+        }
+        Transform::Visit(Self::StringMap(map)) => {
+          let (keys, values): (Vec<_>, Vec<_>) = map.into_iter().unzip();
+          tasks.push(Transform::StringMap(keys));
+          tasks.extend(values.into_iter().rev().map(Transform::Visit));
+          continue;
+        }
+        #[cfg(feature = "unleashed")]
+        Transform::Visit(Self::IntegerMap(map)) => {
+          let (keys, values): (Vec<_>, Vec<_>) = map.into_iter().unzip();
+          tasks.push(Transform::IntegerMap(keys));
+          tasks.extend(values.into_iter().rev().map(Transform::Visit));
+          continue;
+        }
+        #[cfg(feature = "unleashed")]
+        Transform::Visit(Self::Tag(tag, ipld)) => {
+          tasks.push(Transform::Tag(tag));
+          tasks.push(Transform::Visit(*ipld));
+          continue;
+        }
+        Transform::Visit(ipld) => ipld,
+        Transform::List(len) => Self::List(done.split_off(done.len() - len)),
+        Transform::StringMap(keys) => {
+          let values = done.split_off(done.len() - keys.len());
+          Self::StringMap(keys.into_iter().zip(values).collect())
+        }
+        #[cfg(feature = "unleashed")]
+        Transform::IntegerMap(keys) => {
+          let values = done.split_off(done.len() - keys.len());
+          Self::IntegerMap(keys.into_iter().zip(values).collect())
+        }
+        #[cfg(feature = "unleashed")]
+        Transform::Tag(tag) => {
+          Self::Tag(tag, Box::new(done.pop().unwrap_or(Self::Null)))
+        }
+      };
+      done.push(f(ipld));
+    }
+    // The root is always the last value to be transformed.
+    done.pop().unwrap_or(Self::Null)
+  }
+
+  /// Replaces every link `cid` in the value with `f(cid)`.
+  pub fn map_links<F: FnMut(&Cid) -> Cid>(&mut self, mut f: F) {
+    self.walk_mut(|ipld| {
+      if let Self::Link(cid) = ipld {
+        *cid = f(cid);
+      }
+    });
+  }
+
+  /// Returns a reference to the value at `path`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if a segment of `path` names a missing map key, an
+  /// invalid or out of range list index, or is applied to a value that is not
+  /// a container
+  pub fn get_path(&self, path: &IpldPath) -> Result<&Self, PathError> {
+    let mut ipld = self;
+    for (i, segment) in path.segments().iter().enumerate() {
+      ipld = ipld
+        .get_segment(segment)
+        .map_err(|kind| PathError::new(path, i, kind))?;
+    }
+    Ok(ipld)
+  }
+
+  /// Returns a mutable reference to the value at `path`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if a segment of `path` names a missing map key, an
+  /// invalid or out of range list index, or is applied to a value that is not
+  /// a container
+  pub fn get_path_mut(
+    &mut self,
+    path: &IpldPath,
+  ) -> Result<&mut Self, PathError> {
+    let mut ipld = self;
+    for (i, segment) in path.segments().iter().enumerate() {
+      ipld = ipld
+        .get_segment_mut(segment)
+        .map_err(|kind| PathError::new(path, i, kind))?;
+    }
+    Ok(ipld)
+  }
+
+  /// Removes the value at `path` from its parent and returns it. Taking the
+  /// empty path replaces `self` with `Ipld::Null`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if a segment of `path` names a missing map key, an
+  /// invalid or out of range list index, or is applied to a value that is not
+  /// a container
+  pub fn take_path(&mut self, path: &IpldPath) -> Result<Self, PathError> {
+    match (path.parent(), path.last()) {
+      (Some(parent), Some(last)) => self
+        .get_path_mut(&parent)?
+        .take_segment(last)
+        .map_err(|kind| PathError::new(path, parent.len(), kind)),
+      _ => Ok(core::mem::replace(self, Self::Null)),
+    }
+  }
+
+  fn get_segment(&self, segment: &str) -> Result<&Self, PathErrorKind> {
+    match self {
+      Self::StringMap(map) => {
+        map.get(segment).ok_or(PathErrorKind::MissingKey)
+      }
+      Self::List(list) => list
+        .get(parse_index(segment)?)
+        .ok_or(PathErrorKind::IndexOutOfRange { len: list.len() }),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(map) => {
+        map.get(&parse_key(segment)?).ok_or(PathErrorKind::MissingKey)
+      }
+      #[cfg(feature = "unleashed")]
+      Self::Tag(_, ipld) => ipld.get_segment(segment),
+      _ => Err(PathErrorKind::NotContainer),
+    }
+  }
+
+  fn get_segment_mut(
+    &mut self,
+    segment: &str,
+  ) -> Result<&mut Self, PathErrorKind> {
+    match self {
+      Self::StringMap(map) => {
+        map.get_mut(segment).ok_or(PathErrorKind::MissingKey)
+      }
+      Self::List(list) => {
+        let len = list.len();
+        list
+          .get_mut(parse_index(segment)?)
+          .ok_or(PathErrorKind::IndexOutOfRange { len })
+      }
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(map) => {
+        map.get_mut(&parse_key(segment)?).ok_or(PathErrorKind::MissingKey)
+      }
+      #[cfg(feature = "unleashed")]
+      Self::Tag(_, ipld) => ipld.get_segment_mut(segment),
+      _ => Err(PathErrorKind::NotContainer),
+    }
+  }
+
+  pub(crate) fn take_segment(
+    &mut self,
+    segment: &str,
+  ) -> Result<Self, PathErrorKind> {
+    match self {
+      Self::StringMap(map) => {
+        map.remove(segment).ok_or(PathErrorKind::MissingKey)
+      }
+      Self::List(list) => {
+        let index = parse_index(segment)?;
+        if index < list.len() {
+          Ok(list.remove(index))
+        }
+        else {
+          Err(PathErrorKind::IndexOutOfRange { len: list.len() })
+        }
+      }
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(map) => {
+        map.remove(&parse_key(segment)?).ok_or(PathErrorKind::MissingKey)
+      }
+      #[cfg(feature = "unleashed")]
+      Self::Tag(_, ipld) => ipld.take_segment(segment),
+      _ => Err(PathErrorKind::NotContainer),
+    }
+  }
+
+  /// Inserts `value` under `segment`, overwriting a map entry or shifting
+  /// list elements to the right. The segment `-` appends to a list.
+  pub(crate) fn insert_segment(
+    &mut self,
+    segment: &str,
+    value: Self,
+  ) -> Result<(), PathErrorKind> {
+    match self {
+      Self::StringMap(map) => {
+        map.insert(segment.to_owned(), value);
+        Ok(())
+      }
+      Self::List(list) => {
+        let index =
+          if segment == "-" { list.len() } else { parse_index(segment)? };
+        if index <= list.len() {
+          list.insert(index, value);
+          Ok(())
+        }
+        else {
+          Err(PathErrorKind::IndexOutOfRange { len: list.len() })
+        }
+      }
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(map) => {
+        map.insert(parse_key(segment)?, value);
+        Ok(())
+      }
+      #[cfg(feature = "unleashed")]
+      Self::Tag(_, ipld) => ipld.insert_segment(segment, value),
+      _ => Err(PathErrorKind::NotContainer),
+    }
+  }
+}
+
+#[cfg(feature = "unleashed")]
+fn parse_key(segment: &str) -> Result<i64, PathErrorKind> {
+  segment.parse().map_err(|_| PathErrorKind::MissingKey)
 }
 
 impl<'a> Iterator for IpldIter<'a> {
@@ -102,11 +654,118 @@ impl<'a> Iterator for IpldIter<'a> {
   }
 }
 
+/// Returned by the `Index` impls when the key or index is missing.
+static NULL: Ipld = Ipld::Null;
+
+/// Looks up a key of a string map, returning `Ipld::Null` if the key is
+/// missing or the value is not a string map.
+impl Index<&str> for Ipld {
+  type Output = Self;
+
+  fn index(&self, key: &str) -> &Self {
+    match self {
+      Self::StringMap(m) => m.get(key).unwrap_or(&NULL),
+      _ => &NULL,
+    }
+  }
+}
+
+/// Looks up an element of a list, returning `Ipld::Null` if the index is out
+/// of range or the value is not a list.
+impl Index<usize> for Ipld {
+  type Output = Self;
+
+  fn index(&self, index: usize) -> &Self {
+    match self {
+      Self::List(l) => l.get(index).unwrap_or(&NULL),
+      _ => &NULL,
+    }
+  }
+}
+
 /// Ipld iterator.
 pub struct IpldIter<'a> {
   stack: Vec<Box<dyn Iterator<Item = &'a Ipld> + 'a>>,
 }
 
+/// The order in which `Ipld::walk` visits values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WalkOrder {
+  /// Visits a value, then each of its children with all their descendants.
+  DepthFirst,
+  /// Visits all values at one depth before any value at the next depth.
+  BreadthFirst,
+}
+
+/// Path-annotated Ipld iterator.
+///
+/// The children of a value are queued when the next value is requested, so
+/// calling `skip_subtree` right after a value is returned prunes everything
+/// below it.
+pub struct IpldWalk<'a> {
+  order: WalkOrder,
+  queue: VecDeque<(IpldPath, &'a Ipld)>,
+  expand: Option<(IpldPath, &'a Ipld)>,
+}
+
+impl<'a> IpldWalk<'a> {
+  /// Skips the children of the value that was returned last.
+  pub fn skip_subtree(&mut self) { self.expand = None; }
+
+  fn push_children(&mut self, path: &IpldPath, ipld: &'a Ipld) {
+    let children: Vec<_> = match ipld {
+      Ipld::List(list) => list
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (path.join(i.to_string()), x))
+        .collect(),
+      Ipld::StringMap(map) => {
+        map.iter().map(|(k, x)| (path.join(k.as_str()), x)).collect()
+      }
+      #[cfg(feature = "unleashed")]
+      Ipld::IntegerMap(map) => {
+        map.iter().map(|(k, x)| (path.join(k.to_string()), x)).collect()
+      }
+      #[cfg(feature = "unleashed")]
+      Ipld::Tag(_, ipld) => vec![(path.clone(), &**ipld)],
+      _ => return,
+    };
+    match self.order {
+      WalkOrder::DepthFirst => {
+        for child in children.into_iter().rev() {
+          self.queue.push_front(child);
+        }
+      }
+      WalkOrder::BreadthFirst => self.queue.extend(children),
+    }
+  }
+}
+
+impl<'a> Iterator for IpldWalk<'a> {
+  type Item = (IpldPath, &'a Ipld);
+
+  fn next(&mut self) -> Option<Self::Item> {
+    if let Some((path, ipld)) = self.expand.take() {
+      self.push_children(&path, ipld);
+    }
+    let (path, ipld) = self.queue.pop_front()?;
+    self.expand = Some((path.clone(), ipld));
+    Some((path, ipld))
+  }
+}
+
+/// A pending step of `Ipld::transform`. Containers are rebuilt from the last
+/// transformed values once all their children are done.
+enum Transform {
+  Visit(Ipld),
+  List(usize),
+  StringMap(Vec<String>),
+  #[cfg(feature = "unleashed")]
+  IntegerMap(Vec<i64>),
+  #[cfg(feature = "unleashed")]
+  Tag(u64),
+}
+
 #[cfg(test)]
 pub mod tests {
   use super::*;
@@ -121,6 +780,216 @@ pub mod tests {
   };
   use alloc::boxed::Box;
 
+  fn path_fixture() -> Ipld {
+    let mut inner = BTreeMap::new();
+    inner.insert("b".to_owned(), Ipld::Integer(1));
+    let mut map = BTreeMap::new();
+    map.insert(
+      "a".to_owned(),
+      Ipld::List(vec![Ipld::StringMap(inner), Ipld::Bool(true)]),
+    );
+    Ipld::StringMap(map)
+  }
+
+  #[test]
+  fn get_path() {
+    let ipld = path_fixture();
+    assert_eq!(ipld.get_path(&"a/0/b".into()), Ok(&Ipld::Integer(1)));
+    assert_eq!(ipld.get_path(&"a/1".into()), Ok(&Ipld::Bool(true)));
+    assert_eq!(ipld.get_path(&"".into()), Ok(&ipld));
+  }
+
+  #[test]
+  fn get_path_errors() {
+    let ipld = path_fixture();
+    let err = ipld.get_path(&"a/0/c".into()).unwrap_err();
+    assert_eq!((err.position, err.kind), (2, PathErrorKind::MissingKey));
+    let err = ipld.get_path(&"a/2".into()).unwrap_err();
+    assert_eq!(err.kind, PathErrorKind::IndexOutOfRange { len: 2 });
+    let err = ipld.get_path(&"a/x".into()).unwrap_err();
+    assert_eq!(err.kind, PathErrorKind::InvalidIndex);
+    let err = ipld.get_path(&"a/1/b".into()).unwrap_err();
+    assert_eq!(err.segment, "b");
+    assert_eq!(err.kind, PathErrorKind::NotContainer);
+  }
+
+  #[test]
+  fn get_path_mut() {
+    let mut ipld = path_fixture();
+    *ipld.get_path_mut(&"a/0/b".into()).unwrap() = Ipld::Null;
+    assert_eq!(ipld.get_path(&"a/0/b".into()), Ok(&Ipld::Null));
+  }
+
+  #[test]
+  fn accessors() {
+    let ipld = path_fixture();
+    assert_eq!(ipld["a"][0]["b"].as_int::<u8>(), Ok(1));
+    assert_eq!(ipld["a"][1].as_bool(), Ok(true));
+    assert_eq!(ipld["a"].as_list().map(<[Ipld]>::len), Ok(2));
+    assert_eq!(ipld["missing"][3]["x"], Ipld::Null);
+    assert_eq!(ipld[0], Ipld::Null);
+    assert_eq!(
+      ipld["a"].as_map(),
+      Err(ConversionError::WrongKind {
+        expected: IpldKind::StringMap,
+        found: IpldKind::List,
+      })
+    );
+    assert_eq!(
+      Ipld::Integer(-1).as_int::<u32>(),
+      Err(ConversionError::OutOfRange { value: -1, target: "u32" })
+    );
+    assert_eq!(Ipld::Float(0.5).as_float(), Ok(0.5));
+    assert_eq!(Ipld::Bytes(vec![1]).as_bytes(), Ok(&[1][..]));
+    assert_eq!(Ipld::String("s".to_owned()).as_str(), Ok("s"));
+  }
+
+  #[test]
+  fn take_path() {
+    let mut ipld = path_fixture();
+    let taken = ipld.get_path(&"a/0".into()).unwrap().clone();
+    assert_eq!(ipld.take_path(&"a/0".into()), Ok(taken));
+    assert_eq!(ipld.get_path(&"a/0".into()), Ok(&Ipld::Bool(true)));
+    assert!(ipld.take_path(&"a/1".into()).is_err());
+    let root = ipld.clone();
+    assert_eq!(ipld.take_path(&"".into()), Ok(root));
+    assert_eq!(ipld, Ipld::Null);
+  }
+
+  #[test]
+  fn total_order() {
+    use alloc::collections::btree_set::BTreeSet;
+    let mut values = vec![
+      ipld!({ "a": 1 }),
+      ipld!([1, 2]),
+      ipld!([1]),
+      Ipld::Float(-0.0),
+      Ipld::Float(0.0),
+      Ipld::Float(f64::NAN),
+      Ipld::Float(-f64::NAN),
+      ipld!("a"),
+      ipld!(2),
+      ipld!(true),
+      ipld!(null),
+    ];
+    values.sort();
+    assert_eq!(values, vec![
+      ipld!(null),
+      ipld!(true),
+      ipld!(2),
+      Ipld::Float(-0.0),
+      Ipld::Float(0.0),
+      Ipld::Float(f64::NAN),
+      Ipld::Float(f64::NAN),
+      ipld!("a"),
+      ipld!([1]),
+      ipld!([1, 2]),
+      ipld!({ "a": 1 }),
+    ]);
+    assert_ne!(Ipld::Float(0.0), Ipld::Float(-0.0));
+    let set: BTreeSet<_> = values.into_iter().collect();
+    assert_eq!(set.len(), 10);
+  }
+
+  #[cfg(feature = "unleashed")]
+  #[test]
+  fn empty_maps_equal() {
+    let empty = Ipld::IntegerMap(BTreeMap::new());
+    assert_eq!(empty, ipld!({}));
+    assert!(empty < ipld!({ "a": 1 }));
+    let map = Ipld::IntegerMap(vec![(1, ipld!(1))].into_iter().collect());
+    assert!(map > empty);
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  #[quickcheck]
+  fn eq_encoding(x: Ipld, y: Ipld) -> bool {
+    use crate::{
+      codec::Codec,
+      dag_cbor::DagCborCodec,
+    };
+    let encode = |x: &Ipld| DagCborCodec.encode(x).unwrap().into_inner();
+    (x == y) == (encode(&x) == encode(&y)) && encode(&x) == encode(&x.clone())
+  }
+
+  #[test]
+  fn walk() {
+    let ipld = path_fixture();
+    let paths = |order| -> Vec<String> {
+      ipld.walk(order).map(|(path, _)| path.to_string()).collect()
+    };
+    assert_eq!(paths(WalkOrder::DepthFirst), ["", "a", "a/0", "a/0/b", "a/1"]);
+    assert_eq!(paths(WalkOrder::BreadthFirst), [
+      "", "a", "a/0", "a/1", "a/0/b"
+    ]);
+    let mut walk = ipld.walk(WalkOrder::DepthFirst);
+    let mut paths = Vec::new();
+    while let Some((path, ipld)) = walk.next() {
+      if let Ipld::StringMap(_) = ipld {
+        if !path.is_empty() {
+          walk.skip_subtree();
+        }
+      }
+      paths.push(path.to_string());
+    }
+    assert_eq!(paths, ["", "a", "a/0", "a/1"]);
+  }
+
+  #[test]
+  fn references_with_paths() {
+    let cid = arbitrary_cid(&mut Gen::new(10));
+    let mut ipld = path_fixture();
+    *ipld.get_path_mut(&"a/1".into()).unwrap() = Ipld::Link(cid);
+    let mut refs = Vec::new();
+    ipld.references_with_paths(&mut refs);
+    assert_eq!(refs, vec![(IpldPath::from("a/1"), cid)]);
+  }
+
+  #[test]
+  fn walk_mut() {
+    let mut ipld = path_fixture();
+    ipld.walk_mut(|ipld| {
+      if let Ipld::Integer(i) = ipld {
+        *i += 1;
+      }
+    });
+    assert_eq!(ipld.get_path(&"a/0/b".into()), Ok(&Ipld::Integer(2)));
+  }
+
+  #[test]
+  fn transform() {
+    let ipld = path_fixture().transform(|ipld| match ipld {
+      Ipld::Bool(b) => Ipld::Bool(!b),
+      Ipld::List(mut list) => {
+        list.reverse();
+        Ipld::List(list)
+      }
+      ipld => ipld,
+    });
+    assert_eq!(ipld.get_path(&"a/0".into()), Ok(&Ipld::Bool(false)));
+    assert_eq!(ipld.get_path(&"a/1/b".into()), Ok(&Ipld::Integer(1)));
+    let mut deep = Ipld::Null;
+    for _ in 0..10_000 {
+      deep = Ipld::List(vec![deep]);
+    }
+    let mut count = 0;
+    let deep = deep.transform(|ipld| {
+      count += 1;
+      ipld
+    });
+    assert_eq!(count, 10_001);
+    assert_eq!(deep.iter().count(), 10_001);
+  }
+
+  #[test]
+  fn map_links() {
+    let mut g = Gen::new(10);
+    let (old, new) = (arbitrary_cid(&mut g), arbitrary_cid(&mut g));
+    let mut ipld = Ipld::List(vec![Ipld::Link(old), path_fixture()]);
+    ipld.map_links(|cid| if *cid == old { new } else { *cid });
+    assert_eq!(ipld[0], Ipld::Link(new));
+  }
+
   pub(crate) fn arbitrary_cid(g: &mut Gen) -> Cid {
     let mut bytes: [u8; 32] = [0; 32];
     for x in bytes.iter_mut() {
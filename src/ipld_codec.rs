@@ -0,0 +1,182 @@
+#[cfg(feature = "dag-cbor")]
+use crate::dag_cbor::DagCborCodec;
+#[cfg(feature = "dag-json")]
+use crate::dag_json::DagJsonCodec;
+use crate::{
+  codec::{
+    Codec,
+    Decode,
+    Encode,
+    Error,
+    References,
+    SliceCursor,
+    UnsupportedCodec,
+    Writer,
+  },
+  ipld::Ipld,
+  limits::DecodeBudget,
+};
+
+use core::convert::TryFrom;
+use sp_cid::Cid;
+
+/// Every codec supported by this build, selected by its multicodec code.
+///
+/// This dispatches to the codec named in a cid, so that a block can be
+/// decoded knowing only its cid.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IpldCodec {
+  /// The dag-cbor codec, `0x71`.
+  #[cfg(feature = "dag-cbor")]
+  DagCbor,
+  /// The dag-json codec, `0x0129`.
+  #[cfg(feature = "dag-json")]
+  DagJson,
+}
+
+impl Codec for IpldCodec {}
+
+impl From<IpldCodec> for u64 {
+  fn from(codec: IpldCodec) -> Self {
+    match codec {
+      #[cfg(feature = "dag-cbor")]
+      IpldCodec::DagCbor => DagCborCodec.into(),
+      #[cfg(feature = "dag-json")]
+      IpldCodec::DagJson => DagJsonCodec.into(),
+    }
+  }
+}
+
+impl TryFrom<u64> for IpldCodec {
+  type Error = UnsupportedCodec;
+
+  fn try_from(code: u64) -> core::result::Result<Self, Self::Error> {
+    #[cfg(feature = "dag-cbor")]
+    if let Ok(codec) = DagCborCodec::try_from(code) {
+      return Ok(codec.into());
+    }
+    #[cfg(feature = "dag-json")]
+    if let Ok(codec) = DagJsonCodec::try_from(code) {
+      return Ok(codec.into());
+    }
+    Err(UnsupportedCodec(code))
+  }
+}
+
+impl TryFrom<&Cid> for IpldCodec {
+  type Error = UnsupportedCodec;
+
+  fn try_from(cid: &Cid) -> core::result::Result<Self, Self::Error> {
+    Self::try_from(cid.codec())
+  }
+}
+
+#[cfg(feature = "dag-cbor")]
+impl From<DagCborCodec> for IpldCodec {
+  fn from(_: DagCborCodec) -> Self { Self::DagCbor }
+}
+
+#[cfg(feature = "dag-json")]
+impl From<DagJsonCodec> for IpldCodec {
+  fn from(_: DagJsonCodec) -> Self { Self::DagJson }
+}
+
+impl Encode<IpldCodec> for Ipld {
+  fn encode<W: Writer>(
+    &self,
+    c: IpldCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
+    match c {
+      #[cfg(feature = "dag-cbor")]
+      IpldCodec::DagCbor => self.encode(DagCborCodec, w),
+      #[cfg(feature = "dag-json")]
+      IpldCodec::DagJson => self.encode(DagJsonCodec, w),
+    }
+  }
+}
+
+impl Decode<IpldCodec> for Ipld {
+  fn decode(c: IpldCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::decode_limited(c, r, &mut DecodeBudget::default())
+  }
+
+  fn decode_limited(
+    c: IpldCodec,
+    r: &mut SliceCursor,
+    budget: &mut DecodeBudget,
+  ) -> Result<Self, Error> {
+    match c {
+      #[cfg(feature = "dag-cbor")]
+      IpldCodec::DagCbor => Self::decode_limited(DagCborCodec, r, budget),
+      #[cfg(feature = "dag-json")]
+      IpldCodec::DagJson => Self::decode_limited(DagJsonCodec, r, budget),
+    }
+  }
+}
+
+impl References<IpldCodec> for Ipld {
+  fn references<E: Extend<Cid>>(
+    c: IpldCodec,
+    r: &mut SliceCursor,
+    set: &mut E,
+  ) -> Result<(), Error> {
+    Self::references_limited(c, r, set, &mut DecodeBudget::default())
+  }
+
+  fn references_limited<E: Extend<Cid>>(
+    c: IpldCodec,
+    r: &mut SliceCursor,
+    set: &mut E,
+    budget: &mut DecodeBudget,
+  ) -> Result<(), Error> {
+    match c {
+      #[cfg(feature = "dag-cbor")]
+      IpldCodec::DagCbor => {
+        Self::references_limited(DagCborCodec, r, set, budget)
+      }
+      #[cfg(feature = "dag-json")]
+      IpldCodec::DagJson => {
+        Self::references_limited(DagJsonCodec, r, set, budget)
+      }
+    }
+  }
+}
+
+#[cfg(all(test, feature = "dag-cbor", feature = "dag-json"))]
+pub mod tests {
+  use super::*;
+  use bytecursor::ByteCursor;
+  use crate::{
+    dag_cbor,
+    dag_json,
+  };
+  use alloc::vec::Vec;
+
+  #[test]
+  fn codes() {
+    for codec in [IpldCodec::DagCbor, IpldCodec::DagJson] {
+      assert_eq!(IpldCodec::try_from(u64::from(codec)), Ok(codec));
+    }
+    assert_eq!(DagCborCodec::try_from(0x71), Ok(DagCborCodec));
+    assert_eq!(DagCborCodec::try_from(0x0129), Err(UnsupportedCodec(0x0129)));
+    assert_eq!(DagJsonCodec::try_from(0x0129), Ok(DagJsonCodec));
+    assert_eq!(DagJsonCodec::try_from(0x71), Err(UnsupportedCodec(0x71)));
+    assert_eq!(IpldCodec::try_from(0x55), Err(UnsupportedCodec(0x55)));
+  }
+
+  #[test]
+  fn dispatch() {
+    let link = dag_cbor::cid(&ipld!("leaf"));
+    let ipld = ipld!({ "a": [1, "b", link], "c": null });
+    for cid in [dag_cbor::cid(&ipld), dag_json::cid(&ipld)] {
+      let codec = IpldCodec::try_from(&cid).unwrap();
+      let bytes = codec.encode(&ipld).unwrap().into_inner();
+      let decoded = codec.decode(ByteCursor::new(bytes.clone()));
+      assert_eq!(decoded, Ok(ipld.clone()));
+      let mut set = Vec::new();
+      codec.references::<Ipld, _>(ByteCursor::new(bytes), &mut set).unwrap();
+      assert_eq!(set, vec![link]);
+    }
+  }
+}
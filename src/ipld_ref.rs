@@ -0,0 +1,91 @@
+use crate::ipld::{
+  Ipld,
+  IpldKind,
+};
+
+#[cfg(feature = "unleashed")]
+use alloc::boxed::Box;
+use alloc::{
+  borrow::ToOwned,
+  collections::btree_map::BTreeMap,
+  vec::Vec,
+};
+use sp_cid::Cid;
+
+/// An IPLD value whose strings and bytes borrow from the buffer it was
+/// decoded from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpldRef<'a> {
+  /// Represents the absence of a value or the value undefined.
+  Null,
+  /// Represents a boolean value.
+  Bool(bool),
+  /// Represents an integer.
+  Integer(i128),
+  /// Represents a floating point value.
+  Float(f64),
+  /// Represents an UTF-8 string.
+  String(&'a str),
+  /// Represents a sequence of bytes.
+  Bytes(&'a [u8]),
+  /// Represents a list.
+  List(Vec<IpldRef<'a>>),
+  /// Represents a map of strings.
+  StringMap(BTreeMap<&'a str, IpldRef<'a>>),
+  /// Represents a link to an Ipld node.
+  Link(Cid),
+  /// Represents a map of integers.
+  #[cfg(feature = "unleashed")]
+  IntegerMap(BTreeMap<i64, IpldRef<'a>>),
+  /// Represents a value tagged with a CBOR semantic tag.
+  #[cfg(feature = "unleashed")]
+  Tag(u64, Box<IpldRef<'a>>),
+}
+
+impl<'a> IpldRef<'a> {
+  /// Returns the kind of the value.
+  pub fn kind(&self) -> IpldKind {
+    match self {
+      Self::Null => IpldKind::Null,
+      Self::Bool(_) => IpldKind::Bool,
+      Self::Integer(_) => IpldKind::Integer,
+      Self::Float(_) => IpldKind::Float,
+      Self::String(_) => IpldKind::String,
+      Self::Bytes(_) => IpldKind::Bytes,
+      Self::List(_) => IpldKind::List,
+      Self::StringMap(_) => IpldKind::StringMap,
+      Self::Link(_) => IpldKind::Link,
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(_) => IpldKind::IntegerMap,
+      #[cfg(feature = "unleashed")]
+      Self::Tag(..) => IpldKind::Tag,
+    }
+  }
+
+  /// Copies the value into an owned `Ipld`.
+  pub fn to_ipld(&self) -> Ipld {
+    match self {
+      Self::Null => Ipld::Null,
+      Self::Bool(b) => Ipld::Bool(*b),
+      Self::Integer(i) => Ipld::Integer(*i),
+      Self::Float(f) => Ipld::Float(*f),
+      Self::String(s) => Ipld::String((*s).to_owned()),
+      Self::Bytes(b) => Ipld::Bytes(b.to_vec()),
+      Self::List(l) => Ipld::List(l.iter().map(Self::to_ipld).collect()),
+      Self::StringMap(m) => Ipld::StringMap(
+        m.iter().map(|(k, v)| ((*k).to_owned(), v.to_ipld())).collect(),
+      ),
+      Self::Link(cid) => Ipld::Link(*cid),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(m) => {
+        Ipld::IntegerMap(m.iter().map(|(k, v)| (*k, v.to_ipld())).collect())
+      }
+      #[cfg(feature = "unleashed")]
+      Self::Tag(tag, ipld) => Ipld::Tag(*tag, Box::new(Self::to_ipld(ipld))),
+    }
+  }
+}
+
+impl From<IpldRef<'_>> for Ipld {
+  fn from(ipld: IpldRef<'_>) -> Self { ipld.to_ipld() }
+}
@@ -8,6 +8,9 @@
 
 #[macro_use]
 extern crate alloc;
+// Lets the code generated by `sp-ipld-derive` refer to `::sp_ipld` from within
+// this crate.
+extern crate self as sp_ipld;
 
 #[cfg(test)]
 extern crate quickcheck;
@@ -17,24 +20,88 @@ extern crate quickcheck_macros;
 #[cfg(test)]
 extern crate rand;
 
+#[macro_use]
+mod macros;
+
+#[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+pub mod block;
+#[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+pub mod cid_builder;
 pub mod codec;
+pub mod convert;
 #[cfg(feature = "dag-cbor")]
 pub mod dag_cbor;
 #[cfg(feature = "dag-json")]
 pub mod dag_json;
+pub mod diff;
 pub mod ipld;
+#[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+pub mod ipld_codec;
+pub mod ipld_ref;
+pub mod limits;
+pub mod patch;
+pub mod path;
+pub mod pretty;
+#[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+pub mod resolve;
+#[cfg(feature = "serde-codec")]
+pub mod serde;
 
+#[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+pub use block::*;
+#[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+pub use cid_builder::*;
 pub use codec::*;
+pub use convert::*;
+pub use diff::*;
 pub use ipld::*;
+#[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+pub use ipld_codec::*;
+pub use ipld_ref::*;
+pub use limits::*;
+pub use patch::*;
+pub use path::*;
+pub use pretty::*;
+#[cfg(feature = "serde-codec")]
+pub use crate::serde::{
+  from_ipld,
+  to_ipld,
+  SerdeError,
+};
+
+#[cfg(feature = "derive")]
+pub use sp_ipld_derive::DagCbor;
+
+#[doc(hidden)]
+pub mod __private {
+  pub use alloc::{
+    collections::btree_map::BTreeMap,
+    format,
+    string::String,
+    vec,
+  };
+  #[cfg(feature = "dag-cbor")]
+  pub use crate::dag_cbor::{
+    decode::{
+      has_next,
+      read_int,
+      read_list_len,
+      read_map_len,
+      read_null,
+    },
+    encode::{
+      header_len,
+      write_null,
+      write_u64,
+    },
+  };
+}
 
 #[cfg(test)]
 pub mod tests {
   use super::*;
   #[cfg(feature = "dag-cbor")]
-  use super::{
-    dag_cbor,
-    dag_cbor::DagCborCodec,
-  };
+  use super::dag_cbor::DagCborCodec;
   use bytecursor::ByteCursor;
   use quickcheck::quickcheck;
   use reqwest::multipart;
@@ -47,7 +114,7 @@ pub mod tests {
       "{}{}?{}",
       host,
       "/api/v0/dag/put",
-      "format=dag-cbor&pin=true&input-enc=cbor&hash=blake2b-256"
+      "format=dag-cbor&pin=true&input-enc=cbor"
     );
     let cbor = DagCborCodec.encode(&dag).unwrap().into_inner();
     let client = reqwest::Client::new();
@@ -58,7 +125,8 @@ pub mod tests {
     println!("PUT response: {:?}", response);
 
     let ipfs_cid: String = response["Cid"]["/"].as_str().unwrap().to_string();
-    let local_cid: String = dag_cbor::cid(&dag).to_string();
+    let local_cid: String =
+      CidBuilder::new(DagCborCodec).build(&dag).unwrap().to_string();
 
     if ipfs_cid == local_cid {
       Ok(ipfs_cid)
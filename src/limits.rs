@@ -0,0 +1,162 @@
+use core::fmt;
+
+/// Bounds on the resources a decoder may use, for decoding untrusted input.
+///
+/// Independently of these limits, decoders never allocate more elements or
+/// bytes up front than the remaining input could contain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DecodeLimits {
+  /// The maximum nesting depth of lists, maps and tags.
+  pub max_depth: usize,
+  /// The maximum number of elements of a list or entries of a map.
+  pub max_len: usize,
+  /// The maximum length in bytes of a string or byte string.
+  pub max_bytes_len: usize,
+  /// The maximum number of bytes allocated for all strings, byte strings,
+  /// list elements and map entries of a decoded value.
+  pub max_alloc: usize,
+}
+
+impl Default for DecodeLimits {
+  /// Bounds the nesting depth to 128, like `serde_json` does, and nothing
+  /// else.
+  fn default() -> Self {
+    Self {
+      max_depth: 128,
+      max_len: usize::MAX,
+      max_bytes_len: usize::MAX,
+      max_alloc: usize::MAX,
+    }
+  }
+}
+
+/// The limit of `DecodeLimits` that was exceeded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitExceeded {
+  /// A value was nested deeper than `max_depth`.
+  Depth(usize),
+  /// A list or map was longer than `max_len`.
+  Len(usize),
+  /// A string or byte string was longer than `max_bytes_len`.
+  BytesLen(usize),
+  /// More than `max_alloc` bytes were allocated.
+  Alloc(usize),
+}
+
+impl fmt::Display for LimitExceeded {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Depth(max) => {
+        write!(f, "Decode limit exceeded: nesting deeper than {}", max)
+      }
+      Self::Len(max) => {
+        write!(f, "Decode limit exceeded: collection longer than {}", max)
+      }
+      Self::BytesLen(max) => {
+        write!(f, "Decode limit exceeded: bytes longer than {}", max)
+      }
+      Self::Alloc(max) => {
+        write!(f, "Decode limit exceeded: more than {} bytes allocated", max)
+      }
+    }
+  }
+}
+
+/// Tracks the resources used while decoding a single value against
+/// `DecodeLimits`.
+///
+/// A budget is not meant to be reused after decoding failed, since the
+/// nesting depth is not unwound on errors.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodeBudget {
+  limits: DecodeLimits,
+  depth: usize,
+  allocated: usize,
+}
+
+impl DecodeBudget {
+  /// Returns a budget with nothing used yet.
+  pub fn new(limits: DecodeLimits) -> Self {
+    Self { limits, depth: 0, allocated: 0 }
+  }
+
+  /// Returns the limits of the budget.
+  pub fn limits(&self) -> &DecodeLimits { &self.limits }
+
+  /// Returns the current nesting depth.
+  pub fn depth(&self) -> usize { self.depth }
+
+  /// Returns the number of bytes allocated so far.
+  pub fn allocated(&self) -> usize { self.allocated }
+
+  /// Enters a list, map or tag.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if this nests deeper than `max_depth`
+  pub fn enter(&mut self) -> Result<(), LimitExceeded> {
+    if self.depth >= self.limits.max_depth {
+      return Err(LimitExceeded::Depth(self.limits.max_depth));
+    }
+    self.depth += 1;
+    Ok(())
+  }
+
+  /// Leaves the list, map or tag last entered.
+  pub fn exit(&mut self) { self.depth = self.depth.saturating_sub(1); }
+
+  /// Checks the length of a list or map.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `len` is greater than `max_len`
+  pub fn check_len(&self, len: usize) -> Result<(), LimitExceeded> {
+    if len > self.limits.max_len {
+      return Err(LimitExceeded::Len(self.limits.max_len));
+    }
+    Ok(())
+  }
+
+  /// Charges `size` bytes to the allocation budget.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if more than `max_alloc` bytes would be allocated
+  pub fn alloc(&mut self, size: usize) -> Result<(), LimitExceeded> {
+    match self.allocated.checked_add(size) {
+      Some(allocated) if allocated <= self.limits.max_alloc => {
+        self.allocated = allocated;
+        Ok(())
+      }
+      _ => Err(LimitExceeded::Alloc(self.limits.max_alloc)),
+    }
+  }
+
+  /// Checks the length of a string or byte string.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `len` is greater than `max_bytes_len`
+  pub fn check_bytes_len(&self, len: usize) -> Result<(), LimitExceeded> {
+    if len > self.limits.max_bytes_len {
+      return Err(LimitExceeded::BytesLen(self.limits.max_bytes_len));
+    }
+    Ok(())
+  }
+
+  /// Checks the length of a string or byte string and charges it to the
+  /// allocation budget.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if `len` is greater than `max_bytes_len` or more than
+  /// `max_alloc` bytes would be allocated
+  pub fn alloc_bytes(&mut self, len: usize) -> Result<(), LimitExceeded> {
+    self.check_bytes_len(len)?;
+    self.alloc(len)
+  }
+}
+
+impl From<DecodeLimits> for DecodeBudget {
+  fn from(limits: DecodeLimits) -> Self { Self::new(limits) }
+}
@@ -0,0 +1,327 @@
+/// Constructs an `Ipld` value from a JSON-like literal.
+///
+/// `null`, `true` and `false` become the corresponding scalars, `[...]` a
+/// `Ipld::List` and `{...}` a `Ipld::StringMap`. Any other expression is
+/// converted with `Ipld::from`, so numbers, strings, byte strings such as
+/// `b"..."`, `Vec<u8>` and `Cid` values can be interpolated directly.
+///
+/// ```
+/// use sp_ipld::{
+///   ipld,
+///   Ipld,
+/// };
+///
+/// let name = "sp-ipld";
+/// let value = ipld!({
+///   "name": name,
+///   "tags": ["ipld", "no_std"],
+///   "version": [0, 2, 0],
+///   "data": b"\x01\x02",
+///   "parent": null,
+/// });
+/// assert_eq!(value.get_path(&"tags/1".into()), Ok(&ipld!("no_std")));
+/// assert_eq!(value.get_path(&"data".into()), Ok(&Ipld::Bytes(vec![1, 2])));
+/// ```
+#[macro_export]
+macro_rules! ipld {
+  ($($ipld:tt)+) => {
+    $crate::ipld_internal!($($ipld)+)
+  };
+}
+
+// Adapted from the `json!` macro of `serde_json`.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! ipld_internal {
+  // TT muncher for parsing the inside of a list `[...]`. Produces a `vec![...]`
+  // of the elements.
+  //
+  // Must be invoked as: ipld_internal!(@list [] $($tt)*)
+
+  // Done with trailing comma.
+  (@list [$($elems:expr,)*]) => {
+    $crate::__private::vec![$($elems,)*]
+  };
+
+  // Done without trailing comma.
+  (@list [$($elems:expr),*]) => {
+    $crate::__private::vec![$($elems),*]
+  };
+
+  // Next element is `null`.
+  (@list [$($elems:expr,)*] null $($rest:tt)*) => {
+    $crate::ipld_internal!(
+      @list [$($elems,)* $crate::ipld_internal!(null)] $($rest)*
+    )
+  };
+
+  // Next element is `true`.
+  (@list [$($elems:expr,)*] true $($rest:tt)*) => {
+    $crate::ipld_internal!(
+      @list [$($elems,)* $crate::ipld_internal!(true)] $($rest)*
+    )
+  };
+
+  // Next element is `false`.
+  (@list [$($elems:expr,)*] false $($rest:tt)*) => {
+    $crate::ipld_internal!(
+      @list [$($elems,)* $crate::ipld_internal!(false)] $($rest)*
+    )
+  };
+
+  // Next element is a list.
+  (@list [$($elems:expr,)*] [$($list:tt)*] $($rest:tt)*) => {
+    $crate::ipld_internal!(
+      @list [$($elems,)* $crate::ipld_internal!([$($list)*])] $($rest)*
+    )
+  };
+
+  // Next element is a map.
+  (@list [$($elems:expr,)*] {$($map:tt)*} $($rest:tt)*) => {
+    $crate::ipld_internal!(
+      @list [$($elems,)* $crate::ipld_internal!({$($map)*})] $($rest)*
+    )
+  };
+
+  // Next element is an expression followed by comma.
+  (@list [$($elems:expr,)*] $next:expr, $($rest:tt)*) => {
+    $crate::ipld_internal!(
+      @list [$($elems,)* $crate::ipld_internal!($next),] $($rest)*
+    )
+  };
+
+  // Last element is an expression with no trailing comma.
+  (@list [$($elems:expr,)*] $last:expr) => {
+    $crate::ipld_internal!(@list [$($elems,)* $crate::ipld_internal!($last)])
+  };
+
+  // Comma after the most recent element.
+  (@list [$($elems:expr),*] , $($rest:tt)*) => {
+    $crate::ipld_internal!(@list [$($elems,)*] $($rest)*)
+  };
+
+  // Unexpected token after most recent element.
+  (@list [$($elems:expr),*] $unexpected:tt $($rest:tt)*) => {
+    $crate::ipld_unexpected!($unexpected)
+  };
+
+  // TT muncher for parsing the inside of a map `{...}`. Each entry is inserted
+  // into the given map variable.
+  //
+  // Must be invoked as: ipld_internal!(@map $map () ($($tt)*) ($($tt)*))
+  //
+  // We require two copies of the input tokens so that we can match on one copy
+  // and trigger errors on the other copy.
+
+  // Done.
+  (@map $map:ident () () ()) => {};
+
+  // Insert the current entry followed by trailing comma.
+  (@map $map:ident [$($key:tt)+] ($value:expr) , $($rest:tt)*) => {
+    let _ = $map.insert(($($key)+).into(), $value);
+    $crate::ipld_internal!(@map $map () ($($rest)*) ($($rest)*));
+  };
+
+  // Current entry followed by unexpected token.
+  (@map $map:ident [$($key:tt)+] ($value:expr) $unexpected:tt $($rest:tt)*) => {
+    $crate::ipld_unexpected!($unexpected);
+  };
+
+  // Insert the last entry without trailing comma.
+  (@map $map:ident [$($key:tt)+] ($value:expr)) => {
+    let _ = $map.insert(($($key)+).into(), $value);
+  };
+
+  // Next value is `null`.
+  (@map $map:ident ($($key:tt)+) (: null $($rest:tt)*) $copy:tt) => {
+    $crate::ipld_internal!(
+      @map $map [$($key)+] ($crate::ipld_internal!(null)) $($rest)*
+    );
+  };
+
+  // Next value is `true`.
+  (@map $map:ident ($($key:tt)+) (: true $($rest:tt)*) $copy:tt) => {
+    $crate::ipld_internal!(
+      @map $map [$($key)+] ($crate::ipld_internal!(true)) $($rest)*
+    );
+  };
+
+  // Next value is `false`.
+  (@map $map:ident ($($key:tt)+) (: false $($rest:tt)*) $copy:tt) => {
+    $crate::ipld_internal!(
+      @map $map [$($key)+] ($crate::ipld_internal!(false)) $($rest)*
+    );
+  };
+
+  // Next value is a list.
+  (@map $map:ident ($($key:tt)+) (: [$($list:tt)*] $($rest:tt)*) $copy:tt) => {
+    $crate::ipld_internal!(
+      @map $map [$($key)+] ($crate::ipld_internal!([$($list)*])) $($rest)*
+    );
+  };
+
+  // Next value is a map.
+  (@map $map:ident ($($key:tt)+) (: {$($inner:tt)*} $($rest:tt)*) $copy:tt) => {
+    $crate::ipld_internal!(
+      @map $map [$($key)+] ($crate::ipld_internal!({$($inner)*})) $($rest)*
+    );
+  };
+
+  // Next value is an expression followed by comma.
+  (@map $map:ident ($($key:tt)+) (: $value:expr , $($rest:tt)*) $copy:tt) => {
+    $crate::ipld_internal!(
+      @map $map [$($key)+] ($crate::ipld_internal!($value)) , $($rest)*
+    );
+  };
+
+  // Last value is an expression with no trailing comma.
+  (@map $map:ident ($($key:tt)+) (: $value:expr) $copy:tt) => {
+    $crate::ipld_internal!(
+      @map $map [$($key)+] ($crate::ipld_internal!($value))
+    );
+  };
+
+  // Missing value for last entry. Trigger a reasonable error message.
+  (@map $map:ident ($($key:tt)+) (:) $copy:tt) => {
+    // "unexpected end of macro invocation"
+    $crate::ipld_internal!();
+  };
+
+  // Missing colon and value for last entry. Trigger a reasonable error
+  // message.
+  (@map $map:ident ($($key:tt)+) () $copy:tt) => {
+    // "unexpected end of macro invocation"
+    $crate::ipld_internal!();
+  };
+
+  // Misplaced colon. Trigger a reasonable error message.
+  (@map $map:ident () (: $($rest:tt)*) ($colon:tt $($copy:tt)*)) => {
+    // Takes no arguments so "no rules expected the token `:`".
+    $crate::ipld_unexpected!($colon);
+  };
+
+  // Found a comma inside a key. Trigger a reasonable error message.
+  (@map $map:ident ($($key:tt)*) (, $($rest:tt)*) ($comma:tt $($copy:tt)*)) => {
+    // Takes no arguments so "no rules expected the token `,`".
+    $crate::ipld_unexpected!($comma);
+  };
+
+  // Key is fully parenthesized. This avoids clippy double_parens false
+  // positives because the parenthesization may be necessary here.
+  (@map $map:ident () (($key:expr) : $($rest:tt)*) $copy:tt) => {
+    $crate::ipld_internal!(@map $map ($key) (: $($rest)*) (: $($rest)*));
+  };
+
+  // Refuse to absorb colon token into key expression.
+  (@map $map:ident ($($key:tt)*) (: $($unexpected:tt)+) $copy:tt) => {
+    $crate::ipld_expect_expr_comma!($($unexpected)+);
+  };
+
+  // Munch a token into the current key.
+  (@map $map:ident ($($key:tt)*) ($tt:tt $($rest:tt)*) $copy:tt) => {
+    $crate::ipld_internal!(@map $map ($($key)* $tt) ($($rest)*) ($($rest)*));
+  };
+
+  // The main implementation.
+  //
+  // Must be invoked as: ipld_internal!($($ipld)+)
+
+  (null) => {
+    $crate::Ipld::Null
+  };
+
+  (true) => {
+    $crate::Ipld::Bool(true)
+  };
+
+  (false) => {
+    $crate::Ipld::Bool(false)
+  };
+
+  ([]) => {
+    $crate::Ipld::List($crate::__private::vec![])
+  };
+
+  ([ $($tt:tt)+ ]) => {
+    $crate::Ipld::List($crate::ipld_internal!(@list [] $($tt)+))
+  };
+
+  ({}) => {
+    $crate::Ipld::StringMap($crate::__private::BTreeMap::new())
+  };
+
+  ({ $($tt:tt)+ }) => {
+    $crate::Ipld::StringMap({
+      let mut map = $crate::__private::BTreeMap::new();
+      $crate::ipld_internal!(@map map () ($($tt)+) ($($tt)+));
+      map
+    })
+  };
+
+  // Any type with a conversion into `Ipld`: numbers, strings, bytes, cids,
+  // variables etc. Must be below every other rule.
+  ($other:expr) => {
+    $crate::Ipld::from($other)
+  };
+}
+
+// Used by the `ipld!` macro to trigger an error at the position of an
+// unexpected token.
+#[macro_export]
+#[doc(hidden)]
+macro_rules! ipld_unexpected {
+  () => {};
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! ipld_expect_expr_comma {
+  ($e:expr , $($tt:tt)*) => {};
+}
+
+#[cfg(test)]
+pub mod tests {
+  use crate::ipld::Ipld;
+  use alloc::{
+    borrow::ToOwned,
+    collections::btree_map::BTreeMap,
+  };
+
+  #[test]
+  fn scalars() {
+    assert_eq!(ipld!(null), Ipld::Null);
+    assert_eq!(ipld!(true), Ipld::Bool(true));
+    assert_eq!(ipld!(-1), Ipld::Integer(-1));
+    assert_eq!(ipld!(1.5), Ipld::Float(1.5));
+    assert_eq!(ipld!("a"), Ipld::String("a".to_owned()));
+    assert_eq!(ipld!(b"a"), Ipld::Bytes(vec![b'a']));
+  }
+
+  #[test]
+  fn containers() {
+    let key = "b".to_owned();
+    let value = 2u64;
+    let mut inner = BTreeMap::new();
+    inner.insert("c".to_owned(), Ipld::Null);
+    let mut map = BTreeMap::new();
+    map.insert("a".to_owned(), Ipld::List(vec![Ipld::Integer(1), Ipld::Null]));
+    map.insert("b".to_owned(), Ipld::Integer(2));
+    map.insert("d".to_owned(), Ipld::StringMap(inner));
+    map.insert("e".to_owned(), Ipld::List(vec![]));
+    let ipld = ipld!({
+      "a": [1, null],
+      (key): value,
+      "d": { "c": null },
+      "e": [],
+    });
+    assert_eq!(ipld, Ipld::StringMap(map));
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  #[test]
+  fn link() {
+    let cid = crate::dag_cbor::cid(&Ipld::Null);
+    let ipld = ipld!([cid.to_owned()]);
+    assert_eq!(ipld, Ipld::List(vec![Ipld::Link(cid)]));
+  }
+}
@@ -0,0 +1,586 @@
+#[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+use crate::codec::{
+  Decode,
+  Encode,
+  SliceCursor,
+  Writer,
+};
+#[cfg(feature = "dag-cbor")]
+use crate::dag_cbor::DagCborCodec;
+#[cfg(feature = "dag-json")]
+use crate::dag_json::DagJsonCodec;
+use crate::{
+  codec::{
+    Error,
+    ErrorKind,
+  },
+  ipld::{
+    Ipld,
+    IpldKind,
+  },
+  path::{
+    escape,
+    unescape,
+    IpldPath,
+    PathError,
+  },
+};
+
+use alloc::{
+  borrow::ToOwned,
+  collections::btree_map::BTreeMap,
+  string::{
+    String,
+    ToString,
+  },
+  vec::Vec,
+};
+use core::{
+  convert::TryFrom,
+  fmt,
+};
+
+/// A single IPLD Patch operation.
+///
+/// Paths are encoded as JSON pointers (RFC 6901) such as `/a/0`, in which
+/// `~0` stands for `~` and `~1` for `/`. When adding to a list, the last
+/// segment may be `-` to append.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+  /// Inserts `value` at `path`, overwriting a map entry or shifting list
+  /// elements to the right.
+  Add {
+    /// Where to insert the value.
+    path: IpldPath,
+    /// The value to insert.
+    value: Ipld,
+  },
+  /// Removes the value at `path`.
+  Remove {
+    /// The value to remove.
+    path: IpldPath,
+  },
+  /// Replaces the existing value at `path` with `value`.
+  Replace {
+    /// The value to replace.
+    path: IpldPath,
+    /// The new value.
+    value: Ipld,
+  },
+  /// Removes the value at `from` and adds it at `path`.
+  Move {
+    /// The value to move.
+    from: IpldPath,
+    /// Where to add the value.
+    path: IpldPath,
+  },
+  /// Adds a copy of the value at `from` at `path`.
+  Copy {
+    /// The value to copy.
+    from: IpldPath,
+    /// Where to add the copy.
+    path: IpldPath,
+  },
+  /// Checks that the value at `path` is equal to `value`.
+  Test {
+    /// The value to check.
+    path: IpldPath,
+    /// The expected value.
+    value: Ipld,
+  },
+}
+
+impl Operation {
+  /// Returns the name of the operation in the encoded patch.
+  pub fn name(&self) -> &'static str {
+    match self {
+      Self::Add { .. } => "add",
+      Self::Remove { .. } => "remove",
+      Self::Replace { .. } => "replace",
+      Self::Move { .. } => "move",
+      Self::Copy { .. } => "copy",
+      Self::Test { .. } => "test",
+    }
+  }
+
+  fn apply(&self, ipld: &mut Ipld) -> Result<(), PatchErrorKind> {
+    match self {
+      Self::Add { path, value } => add(ipld, path, value.clone()),
+      Self::Remove { path } => {
+        ipld.take_path(path)?;
+        Ok(())
+      }
+      Self::Replace { path, value } => {
+        *ipld.get_path_mut(path)? = value.clone();
+        Ok(())
+      }
+      Self::Move { from, path } => {
+        if path.len() > from.len()
+          && path.segments().starts_with(from.segments())
+        {
+          return Err(PatchErrorKind::MoveIntoChild);
+        }
+        let value = ipld.take_path(from)?;
+        add(ipld, path, value)
+      }
+      Self::Copy { from, path } => {
+        let value = ipld.get_path(from)?.clone();
+        add(ipld, path, value)
+      }
+      Self::Test { path, value } => {
+        if ipld.get_path(path)? == value {
+          Ok(())
+        }
+        else {
+          Err(PatchErrorKind::TestFailed)
+        }
+      }
+    }
+  }
+}
+
+fn add(
+  ipld: &mut Ipld,
+  path: &IpldPath,
+  value: Ipld,
+) -> Result<(), PatchErrorKind> {
+  match (path.parent(), path.last()) {
+    (Some(parent), Some(last)) => ipld
+      .get_path_mut(&parent)?
+      .insert_segment(last, value)
+      .map_err(|kind| PathError::new(path, parent.len(), kind).into()),
+    _ => {
+      *ipld = value;
+      Ok(())
+    }
+  }
+}
+
+/// A sequence of IPLD Patch operations, applied in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Patch {
+  /// The operations of the patch.
+  pub operations: Vec<Operation>,
+}
+
+impl Patch {
+  /// Creates a patch from its operations.
+  pub fn new(operations: Vec<Operation>) -> Self { Self { operations } }
+}
+
+/// The reason a patch operation failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatchErrorKind {
+  /// A path of the operation could not be followed.
+  Path(PathError),
+  /// A `test` operation found a different value.
+  TestFailed,
+  /// A `move` operation tried to move a value into one of its children.
+  MoveIntoChild,
+}
+
+impl From<PathError> for PatchErrorKind {
+  fn from(err: PathError) -> Self { Self::Path(err) }
+}
+
+/// An error returned when a patch cannot be applied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatchError {
+  /// The index of the operation that failed.
+  pub operation: usize,
+  /// Why the operation failed.
+  pub kind: PatchErrorKind,
+}
+
+impl fmt::Display for PatchError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Operation {}: ", self.operation)?;
+    match &self.kind {
+      PatchErrorKind::Path(e) => write!(f, "{}", e),
+      PatchErrorKind::TestFailed => write!(f, "test failed"),
+      PatchErrorKind::MoveIntoChild => {
+        write!(f, "cannot move a value into one of its children")
+      }
+    }
+  }
+}
+
+impl Ipld {
+  /// Applies the operations of `patch` in order. If any operation fails,
+  /// `self` is left unchanged.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if a path of an operation could not be followed, a
+  /// `test` operation failed or a `move` operation targets its own child
+  pub fn apply_patch(&mut self, patch: &Patch) -> Result<(), PatchError> {
+    let mut ipld = self.clone();
+    for (i, operation) in patch.operations.iter().enumerate() {
+      operation
+        .apply(&mut ipld)
+        .map_err(|kind| PatchError { operation: i, kind })?;
+    }
+    *self = ipld;
+    Ok(())
+  }
+}
+
+/// Returns `path` as a JSON pointer (RFC 6901), in which every segment is
+/// preceded by `/` and has `~` escaped as `~0` and `/` as `~1`.
+fn to_pointer(path: &IpldPath) -> Ipld {
+  Ipld::String(
+    path.segments().iter().map(|s| format!("/{}", escape(s))).collect(),
+  )
+}
+
+/// Parses a JSON pointer (RFC 6901). Unlike `IpldPath::from`, empty
+/// segments are kept, so `"/"` refers to the empty key of the root.
+///
+/// # Errors
+///
+/// Will return `Err` with `ErrorKind::InvalidPointer` if `pointer` is
+/// neither empty nor starts with `/`, or contains a `~` that is not followed
+/// by `0` or `1`
+fn parse_pointer(pointer: &str) -> Result<IpldPath, Error> {
+  let invalid = || Error::new(ErrorKind::InvalidPointer(pointer.to_owned()));
+  if pointer.is_empty() {
+    return Ok(IpldPath::new());
+  }
+  let segments = pointer.strip_prefix('/').ok_or_else(invalid)?;
+  segments
+    .split('/')
+    .map(|segment| {
+      let mut escapes = segment.split('~').skip(1);
+      if escapes.any(|s| !s.starts_with(&['0', '1'][..])) {
+        return Err(invalid());
+      }
+      Ok(unescape(segment))
+    })
+    .collect()
+}
+
+impl From<&Operation> for Ipld {
+  fn from(operation: &Operation) -> Self {
+    let mut map = BTreeMap::new();
+    map.insert("op".to_owned(), Self::String(operation.name().to_owned()));
+    match operation {
+      Operation::Remove { path } => {
+        map.insert("path".to_owned(), to_pointer(path));
+      }
+      Operation::Add { path, value }
+      | Operation::Replace { path, value }
+      | Operation::Test { path, value } => {
+        map.insert("path".to_owned(), to_pointer(path));
+        map.insert("value".to_owned(), value.clone());
+      }
+      Operation::Move { from, path } | Operation::Copy { from, path } => {
+        map.insert("from".to_owned(), to_pointer(from));
+        map.insert("path".to_owned(), to_pointer(path));
+      }
+    }
+    Self::StringMap(map)
+  }
+}
+
+impl From<&Patch> for Ipld {
+  fn from(patch: &Patch) -> Self {
+    Self::List(patch.operations.iter().map(Self::from).collect())
+  }
+}
+
+impl From<Patch> for Ipld {
+  fn from(patch: Patch) -> Self { Self::from(&patch) }
+}
+
+fn wrong_kind(expected: IpldKind, found: &Ipld) -> Error {
+  Error::new(ErrorKind::WrongKind { expected, found: found.kind() })
+}
+
+fn take_field(
+  map: &mut BTreeMap<String, Ipld>,
+  key: &str,
+) -> Result<Ipld, Error> {
+  map
+    .remove(key)
+    .ok_or_else(|| Error::new(ErrorKind::MissingKey(key.to_owned())))
+}
+
+fn take_string(
+  map: &mut BTreeMap<String, Ipld>,
+  key: &str,
+) -> Result<String, Error> {
+  match take_field(map, key)? {
+    Ipld::String(s) => Ok(s),
+    ipld => Err(wrong_kind(IpldKind::String, &ipld).within(key)),
+  }
+}
+
+fn take_pointer(
+  map: &mut BTreeMap<String, Ipld>,
+  key: &str,
+) -> Result<IpldPath, Error> {
+  parse_pointer(&take_string(map, key)?).map_err(|e| e.within(key))
+}
+
+impl TryFrom<Ipld> for Operation {
+  type Error = Error;
+
+  fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+    let mut map = match ipld {
+      Ipld::StringMap(map) => map,
+      ipld => return Err(wrong_kind(IpldKind::StringMap, &ipld)),
+    };
+    let op = take_string(&mut map, "op")?;
+    let path = take_pointer(&mut map, "path")?;
+    match op.as_str() {
+      "add" => Ok(Self::Add { path, value: take_field(&mut map, "value")? }),
+      "remove" => Ok(Self::Remove { path }),
+      "replace" => {
+        Ok(Self::Replace { path, value: take_field(&mut map, "value")? })
+      }
+      "move" => Ok(Self::Move { from: take_pointer(&mut map, "from")?, path }),
+      "copy" => Ok(Self::Copy { from: take_pointer(&mut map, "from")?, path }),
+      "test" => Ok(Self::Test { path, value: take_field(&mut map, "value")? }),
+      op => {
+        let unknown = ErrorKind::UnknownVariant(op.to_owned());
+        Err(Error::new(unknown).within("op"))
+      }
+    }
+  }
+}
+
+impl TryFrom<Ipld> for Patch {
+  type Error = Error;
+
+  fn try_from(ipld: Ipld) -> Result<Self, Self::Error> {
+    match ipld {
+      Ipld::List(list) => Ok(Self::new(
+        list
+          .into_iter()
+          .enumerate()
+          .map(|(i, operation)| {
+            Operation::try_from(operation).map_err(|e| e.within(i.to_string()))
+          })
+          .collect::<Result<_, _>>()?,
+      )),
+      ipld => Err(wrong_kind(IpldKind::List, &ipld)),
+    }
+  }
+}
+
+#[cfg(feature = "dag-cbor")]
+impl Encode<DagCborCodec> for Patch {
+  fn encode<W: Writer>(
+    &self,
+    c: DagCborCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
+    Ipld::from(self).encode(c, w)
+  }
+}
+
+#[cfg(feature = "dag-cbor")]
+impl Decode<DagCborCodec> for Patch {
+  fn decode(c: DagCborCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::try_from(Ipld::decode(c, r)?)
+  }
+}
+
+#[cfg(feature = "dag-json")]
+impl Encode<DagJsonCodec> for Patch {
+  fn encode<W: Writer>(
+    &self,
+    c: DagJsonCodec,
+    w: &mut W,
+  ) -> Result<(), Error> {
+    Ipld::from(self).encode(c, w)
+  }
+}
+
+#[cfg(feature = "dag-json")]
+impl Decode<DagJsonCodec> for Patch {
+  fn decode(c: DagJsonCodec, r: &mut SliceCursor) -> Result<Self, Error> {
+    Self::try_from(Ipld::decode(c, r)?)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use crate::path::PathErrorKind;
+  #[cfg(any(feature = "dag-cbor", feature = "dag-json"))]
+  use bytecursor::ByteCursor;
+
+  fn fixture() -> Ipld { ipld!({ "a": [1, 2], "b": { "c": true } }) }
+
+  #[test]
+  fn apply_patch() {
+    let mut ipld = fixture();
+    let patch = Patch::new(vec![
+      Operation::Test { path: "/b/c".into(), value: ipld!(true) },
+      Operation::Add { path: "/a/-".into(), value: ipld!(3) },
+      Operation::Add { path: "/a/0".into(), value: ipld!(0) },
+      Operation::Remove { path: "/a/1".into() },
+      Operation::Replace { path: "/b/c".into(), value: ipld!(false) },
+      Operation::Copy { from: "/b".into(), path: "/d".into() },
+      Operation::Move { from: "/b/c".into(), path: "/e".into() },
+    ]);
+    ipld.apply_patch(&patch).unwrap();
+    assert_eq!(
+      ipld,
+      ipld!({
+        "a": [0, 2, 3],
+        "b": {},
+        "d": { "c": false },
+        "e": false,
+      })
+    );
+  }
+
+  #[test]
+  fn apply_patch_atomic() {
+    let mut ipld = fixture();
+    let patch = Patch::new(vec![
+      Operation::Remove { path: "/a".into() },
+      Operation::Test { path: "/b/c".into(), value: ipld!(false) },
+    ]);
+    assert_eq!(
+      ipld.apply_patch(&patch),
+      Err(PatchError { operation: 1, kind: PatchErrorKind::TestFailed })
+    );
+    assert_eq!(ipld, fixture());
+    let patch = Patch::new(vec![
+      Operation::Add { path: "/a/3".into(), value: Ipld::Null },
+      Operation::Move { from: "/b".into(), path: "/b/c/d".into() },
+    ]);
+    let err = ipld.apply_patch(&patch).unwrap_err();
+    assert!(matches!(
+      err.kind,
+      PatchErrorKind::Path(PathError {
+        kind: PathErrorKind::IndexOutOfRange { len: 2 },
+        ..
+      })
+    ));
+    assert_eq!(ipld, fixture());
+  }
+
+  #[test]
+  fn patch_ipld() {
+    let patch = Patch::new(vec![
+      Operation::Add { path: "/a/-".into(), value: ipld!({ "x": null }) },
+      Operation::Move { from: "/b".into(), path: "".into() },
+    ]);
+    let ipld = Ipld::from(&patch);
+    assert_eq!(
+      ipld,
+      ipld!([
+        { "op": "add", "path": "/a/-", "value": { "x": null } },
+        { "op": "move", "from": "/b", "path": "" },
+      ])
+    );
+    assert_eq!(Patch::try_from(ipld), Ok(patch));
+    let error = |ipld| Patch::try_from(ipld).unwrap_err();
+    let unknown = error(ipld!([{ "op": "nope", "path": "" }]));
+    assert_eq!(unknown.kind(), &ErrorKind::UnknownVariant("nope".to_owned()));
+    assert_eq!(unknown.path(), &IpldPath::from("0/op"));
+    let missing = error(ipld!([{ "op": "add", "path": "" }]));
+    assert_eq!(missing.kind(), &ErrorKind::MissingKey("value".to_owned()));
+    assert_eq!(missing.path(), &IpldPath::from("0"));
+    let wrong = error(ipld!([{ "op": "remove", "path": 1 }]));
+    let kind = ErrorKind::WrongKind {
+      expected: IpldKind::String,
+      found: IpldKind::Integer,
+    };
+    assert_eq!(wrong.kind(), &kind);
+    assert_eq!(wrong.path(), &IpldPath::from("0/path"));
+    let pointer = error(ipld!([{ "op": "remove", "path": "a" }]));
+    assert_eq!(pointer.kind(), &ErrorKind::InvalidPointer("a".to_owned()));
+  }
+
+  #[test]
+  fn pointers() {
+    let pointer = |path: &[&str]| to_pointer(&path.iter().copied().collect());
+    assert_eq!(pointer(&[]), ipld!(""));
+    assert_eq!(pointer(&[""]), ipld!("/"));
+    assert_eq!(pointer(&["a/b", "~", ""]), ipld!("/a~1b/~0/"));
+    let parse = |pointer| parse_pointer(pointer).map(|p| p.segments().to_vec());
+    assert_eq!(parse(""), Ok(vec![]));
+    assert_eq!(parse("/"), Ok(vec!["".to_owned()]));
+    assert_eq!(parse("//a/"), Ok(vec!["".into(), "a".into(), "".into()]));
+    assert_eq!(parse("/a~1b/~01"), Ok(vec!["a/b".into(), "~1".into()]));
+    for invalid in &["a", "a/b", "/~", "/a~2", "/~/"] {
+      assert!(parse_pointer(invalid).is_err());
+    }
+  }
+
+  #[test]
+  fn diff_patch_roundtrip() {
+    use crate::diff::{
+      IpldChange,
+      ListAlignment,
+    };
+    let roundtrip = |old: &Ipld, new: &Ipld, lists| {
+      let patch = Patch::new(
+        old
+          .diff_with(new, lists)
+          .into_iter()
+          .map(|change| match change {
+            IpldChange::Added { path, value } => Operation::Add { path, value },
+            IpldChange::Removed { path, .. } => Operation::Remove { path },
+            IpldChange::Replaced { path, new, .. } => {
+              Operation::Replace { path, value: new }
+            }
+          })
+          .collect(),
+      );
+      let patch = Patch::try_from(Ipld::from(&patch)).unwrap();
+      let mut ipld = old.clone();
+      ipld.apply_patch(&patch).unwrap();
+      assert_eq!(&ipld, new);
+    };
+    let old = ipld!({
+      "": { "": 1, "a/b": 2 },
+      "~": [1, 2],
+      "/": { "~1": "x", "~0/": null },
+      "same": true,
+    });
+    let new = ipld!({
+      "": { "": 2, "~": 3 },
+      "~": [1, 2, 3],
+      "/": { "~1": "y", "/~": null },
+      "same": true,
+      "//": {},
+    });
+    let lists = [
+      (ipld!([1, 2, 3, 4]), ipld!([1, 2])),
+      (ipld!([1, 2, 3, 4, 5]), ipld!([0, 2, 5, 6])),
+      (ipld!([1, 2, 3]), ipld!([3, 2, 1, 0])),
+    ];
+    for alignment in [ListAlignment::Index, ListAlignment::Lcs] {
+      roundtrip(&old, &new, alignment);
+      for (old, new) in &lists {
+        roundtrip(old, new, alignment);
+        roundtrip(new, old, alignment);
+      }
+    }
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  #[test]
+  fn patch_dag_cbor() {
+    use crate::codec::Codec;
+    let patch =
+      Patch::new(vec![Operation::Test { path: "/a".into(), value: ipld!(1) }]);
+    let bytes = DagCborCodec.encode(&patch).unwrap().into_inner();
+    assert_eq!(DagCborCodec.decode(ByteCursor::new(bytes)), Ok(patch));
+  }
+
+  #[cfg(feature = "dag-json")]
+  #[test]
+  fn patch_dag_json() {
+    use crate::codec::Codec;
+    let patch = Patch::new(vec![Operation::Remove { path: "/a/0".into() }]);
+    let bytes = DagJsonCodec.encode(&patch).unwrap().into_inner();
+    assert_eq!(DagJsonCodec.decode(ByteCursor::new(bytes)), Ok(patch));
+  }
+}
@@ -0,0 +1,213 @@
+use alloc::{
+  borrow::Cow,
+  string::{
+    String,
+    ToString,
+  },
+  vec::Vec,
+};
+use core::{
+  fmt,
+  iter::FromIterator,
+};
+
+/// A path into an IPLD value, made of map keys and list indices.
+///
+/// Paths are written as segments separated by `/`, for example `"a/0/b"`.
+/// Whether a segment is a map key or a list index depends on the value it is
+/// applied to. A list index is written in decimal without a sign or leading
+/// zeros.
+///
+/// In the written form `~1` stands for `/` and `~0` for `~` inside a
+/// segment, and empty segments are skipped. Keys that are empty can only be
+/// reached by building the path with `push`, `join` or `FromIterator`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IpldPath(Vec<String>);
+
+impl IpldPath {
+  /// Returns the empty path, which refers to the root value.
+  pub fn new() -> Self { Self(Vec::new()) }
+
+  /// Returns the segments of the path.
+  pub fn segments(&self) -> &[String] { &self.0 }
+
+  /// Returns the number of segments in the path.
+  pub fn len(&self) -> usize { self.0.len() }
+
+  /// Returns `true` if the path refers to the root value.
+  pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+  /// Appends a segment to the end of the path.
+  pub fn push<S: Into<String>>(&mut self, segment: S) {
+    self.0.push(segment.into());
+  }
+
+  /// Removes the last segment of the path and returns it.
+  pub fn pop(&mut self) -> Option<String> { self.0.pop() }
+
+  /// Returns a new path with `segment` appended.
+  pub fn join<S: Into<String>>(&self, segment: S) -> Self {
+    let mut path = self.clone();
+    path.push(segment);
+    path
+  }
+
+  /// Returns the path without its last segment, or `None` for the empty path.
+  pub fn parent(&self) -> Option<Self> {
+    self.0.split_last().map(|(_, parent)| Self(parent.to_vec()))
+  }
+
+  /// Returns the last segment of the path, or `None` for the empty path.
+  pub fn last(&self) -> Option<&str> { self.0.last().map(String::as_str) }
+}
+
+impl From<&str> for IpldPath {
+  fn from(path: &str) -> Self {
+    path.split('/').filter(|s| !s.is_empty()).map(unescape).collect()
+  }
+}
+
+impl From<String> for IpldPath {
+  fn from(path: String) -> Self { Self::from(path.as_str()) }
+}
+
+impl<S: Into<String>> FromIterator<S> for IpldPath {
+  fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+    Self(iter.into_iter().map(Into::into).collect())
+  }
+}
+
+impl fmt::Display for IpldPath {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    for (i, segment) in self.0.iter().enumerate() {
+      if i > 0 {
+        write!(f, "/")?;
+      }
+      write!(f, "{}", escape(segment))?;
+    }
+    Ok(())
+  }
+}
+
+/// Returns `segment` with `~` written as `~0` and `/` as `~1`.
+pub(crate) fn escape(segment: &str) -> Cow<'_, str> {
+  if segment.contains(&['~', '/'][..]) {
+    Cow::Owned(segment.replace('~', "~0").replace('/', "~1"))
+  }
+  else {
+    Cow::Borrowed(segment)
+  }
+}
+
+/// Returns `segment` with `~1` read as `/` and `~0` as `~`.
+pub(crate) fn unescape(segment: &str) -> String {
+  segment.replace("~1", "/").replace("~0", "~")
+}
+
+/// Parses a list index, which is written in decimal without a sign or
+/// leading zeros.
+pub(crate) fn parse_index(segment: &str) -> Result<usize, PathErrorKind> {
+  match segment.as_bytes() {
+    [b'0'] => Ok(0),
+    [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit) => {
+      segment.parse().map_err(|_| PathErrorKind::InvalidIndex)
+    }
+    _ => Err(PathErrorKind::InvalidIndex),
+  }
+}
+
+/// The reason a path segment could not be followed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathErrorKind {
+  /// The map does not contain the key.
+  MissingKey,
+  /// The segment is not a valid list index.
+  InvalidIndex,
+  /// The index is past the end of a list of length `len`.
+  IndexOutOfRange {
+    /// The length of the list.
+    len: usize,
+  },
+  /// The value is neither a list nor a map.
+  NotContainer,
+}
+
+/// An error returned when a path cannot be followed through an IPLD value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PathError {
+  /// The index of the segment that failed.
+  pub position: usize,
+  /// The segment that failed.
+  pub segment: String,
+  /// Why the segment failed.
+  pub kind: PathErrorKind,
+}
+
+impl PathError {
+  /// Creates an error for the segment at `position` in `path`.
+  pub fn new(path: &IpldPath, position: usize, kind: PathErrorKind) -> Self {
+    let segment = path
+      .segments()
+      .get(position)
+      .map(ToString::to_string)
+      .unwrap_or_default();
+    Self { position, segment, kind }
+  }
+}
+
+impl fmt::Display for PathError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Segment {} `{}`: ", self.position, self.segment)?;
+    match self.kind {
+      PathErrorKind::MissingKey => write!(f, "missing key"),
+      PathErrorKind::InvalidIndex => write!(f, "invalid list index"),
+      PathErrorKind::IndexOutOfRange { len } => {
+        write!(f, "index out of range for list of length {}", len)
+      }
+      PathErrorKind::NotContainer => write!(f, "not a list or map"),
+    }
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_path() {
+    let path = IpldPath::from("a/0/b");
+    assert_eq!(path.segments(), ["a", "0", "b"]);
+    assert_eq!(IpldPath::from("/a//0/b/"), path);
+    assert!(IpldPath::from("").is_empty());
+    assert_eq!(path.to_string(), "a/0/b");
+  }
+
+  #[test]
+  fn escaped_segments() {
+    let path = IpldPath::new().join("a/b").join("~1").join("c");
+    assert_eq!(path.to_string(), "a~1b/~01/c");
+    assert_eq!(IpldPath::from(path.to_string()), path);
+    assert_eq!(IpldPath::from("~01~10").segments(), ["~1/0"]);
+  }
+
+  #[test]
+  fn index() {
+    assert_eq!(parse_index("0"), Ok(0));
+    assert_eq!(parse_index("120"), Ok(120));
+    let overflow = "18446744073709551616";
+    assert_eq!(parse_index(overflow), Err(PathErrorKind::InvalidIndex));
+    let invalid = ["", "+1", "-1", "01", "00", "1a", " 1", "1e3"];
+    for segment in &invalid {
+      assert_eq!(parse_index(segment), Err(PathErrorKind::InvalidIndex));
+    }
+  }
+
+  #[test]
+  fn join_parent() {
+    let path = IpldPath::new().join("a").join("0");
+    assert_eq!(path, IpldPath::from("a/0"));
+    assert_eq!(path.parent(), Some(IpldPath::from("a")));
+    assert_eq!(path.last(), Some("0"));
+    assert_eq!(IpldPath::new().parent(), None);
+  }
+}
@@ -0,0 +1,238 @@
+use crate::ipld::Ipld;
+
+use core::fmt;
+use sp_cid::{
+  Cid,
+  multibase::Base,
+};
+
+/// How `Ipld::Bytes` values are printed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BytesFormat {
+  /// Lowercase hexadecimal, written as `h'00ff'`.
+  Hex,
+  /// Unpadded base64, written as `b64'AP8'`.
+  Base64,
+}
+
+/// Options of the IPLD pretty printer.
+///
+/// Values are printed in a JSON-like notation. Bytes and links use the CBOR
+/// diagnostic style, for example `h'00ff'` and `cid'bafy…'`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PrettyConfig {
+  /// The number of spaces per nesting level. `0` prints on a single line.
+  pub indent: usize,
+  /// Lists and maps nested deeper than this are printed as `[…]` and `{…}`.
+  pub max_depth: Option<usize>,
+  /// Byte strings longer than this are truncated and end with `…`.
+  pub max_bytes: Option<usize>,
+  /// How bytes are printed.
+  pub bytes: BytesFormat,
+  /// The multibase links are printed in. CIDv0 links are always printed in
+  /// base58btc.
+  pub cid_base: Base,
+}
+
+impl Default for PrettyConfig {
+  fn default() -> Self {
+    Self {
+      indent: 0,
+      max_depth: None,
+      max_bytes: None,
+      bytes: BytesFormat::Hex,
+      cid_base: Base::Base32Lower,
+    }
+  }
+}
+
+impl PrettyConfig {
+  /// Returns the default options with `indent` spaces per nesting level.
+  pub fn indented(indent: usize) -> Self { Self { indent, ..Self::default() } }
+}
+
+/// Displays an `Ipld` value with the given `PrettyConfig`.
+pub struct Pretty<'a> {
+  ipld: &'a Ipld,
+  config: &'a PrettyConfig,
+}
+
+impl Ipld {
+  /// Returns a value that displays `self` according to `config`.
+  pub fn pretty<'a>(&'a self, config: &'a PrettyConfig) -> Pretty<'a> {
+    Pretty { ipld: self, config }
+  }
+}
+
+impl fmt::Display for Pretty<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write_ipld(f, self.config, self.ipld, 0)
+  }
+}
+
+/// Prints the value on a single line, or indented by two spaces with `{:#}`.
+impl fmt::Display for Ipld {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let config = PrettyConfig::indented(if f.alternate() { 2 } else { 0 });
+    write_ipld(f, &config, self, 0)
+  }
+}
+
+fn write_ipld(
+  f: &mut fmt::Formatter,
+  config: &PrettyConfig,
+  ipld: &Ipld,
+  depth: usize,
+) -> fmt::Result {
+  match ipld {
+    Ipld::Null => write!(f, "null"),
+    Ipld::Bool(b) => write!(f, "{}", b),
+    Ipld::Integer(i) => write!(f, "{}", i),
+    Ipld::Float(x) => write!(f, "{:?}", x),
+    Ipld::String(s) => write!(f, "{:?}", s),
+    Ipld::Bytes(b) => write_bytes(f, config, b),
+    Ipld::Link(cid) => write_cid(f, config, cid),
+    Ipld::List(list) => write_container(
+      f,
+      config,
+      depth,
+      ('[', ']'),
+      list.iter().map(|x| (None::<&str>, x)),
+    ),
+    Ipld::StringMap(map) => write_container(
+      f,
+      config,
+      depth,
+      ('{', '}'),
+      map.iter().map(|(k, x)| (Some(format!("{:?}", k)), x)),
+    ),
+    #[cfg(feature = "unleashed")]
+    Ipld::IntegerMap(map) => write_container(
+      f,
+      config,
+      depth,
+      ('{', '}'),
+      map.iter().map(|(k, x)| (Some(k), x)),
+    ),
+    #[cfg(feature = "unleashed")]
+    Ipld::Tag(tag, ipld) => {
+      write!(f, "{}(", tag)?;
+      write_ipld(f, config, ipld, depth)?;
+      write!(f, ")")
+    }
+  }
+}
+
+fn write_bytes(
+  f: &mut fmt::Formatter,
+  config: &PrettyConfig,
+  bytes: &[u8],
+) -> fmt::Result {
+  let len = config.max_bytes.map_or(bytes.len(), |max| max.min(bytes.len()));
+  let ellipsis = if len < bytes.len() { "…" } else { "" };
+  match config.bytes {
+    BytesFormat::Hex => {
+      write!(f, "h'{}{}'", Base::Base16Lower.encode(&bytes[..len]), ellipsis)
+    }
+    BytesFormat::Base64 => {
+      write!(f, "b64'{}{}'", Base::Base64.encode(&bytes[..len]), ellipsis)
+    }
+  }
+}
+
+fn write_cid(
+  f: &mut fmt::Formatter,
+  config: &PrettyConfig,
+  cid: &Cid,
+) -> fmt::Result {
+  match cid.to_string_of_base(config.cid_base) {
+    Ok(s) => write!(f, "cid'{}'", s),
+    Err(_) => write!(f, "cid'{}'", cid),
+  }
+}
+
+fn write_container<'a, K: fmt::Display>(
+  f: &mut fmt::Formatter,
+  config: &PrettyConfig,
+  depth: usize,
+  (open, close): (char, char),
+  entries: impl ExactSizeIterator<Item = (Option<K>, &'a Ipld)>,
+) -> fmt::Result {
+  if entries.len() == 0 {
+    return write!(f, "{}{}", open, close);
+  }
+  if config.max_depth.is_some_and(|max| depth >= max) {
+    return write!(f, "{}…{}", open, close);
+  }
+  write!(f, "{}", open)?;
+  for (i, (key, ipld)) in entries.enumerate() {
+    if i > 0 {
+      write!(f, ",")?;
+      if config.indent == 0 {
+        write!(f, " ")?;
+      }
+    }
+    if config.indent > 0 {
+      write!(f, "\n{:1$}", "", config.indent * (depth + 1))?;
+    }
+    if let Some(key) = key {
+      write!(f, "{}: ", key)?;
+    }
+    write_ipld(f, config, ipld, depth + 1)?;
+  }
+  if config.indent > 0 {
+    write!(f, "\n{:1$}", "", config.indent * depth)?;
+  }
+  write!(f, "{}", close)
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use alloc::string::ToString;
+
+  fn fixture() -> Ipld {
+    ipld!({ "a": [1, { "b": null }], "c": b"\x00\xff", "d": [] })
+  }
+
+  #[test]
+  fn display() {
+    assert_eq!(
+      fixture().to_string(),
+      r#"{"a": [1, {"b": null}], "c": h'00ff', "d": []}"#
+    );
+    assert_eq!(
+      format!("{:#}", fixture()),
+      r#"{
+  "a": [
+    1,
+    {
+      "b": null
+    }
+  ],
+  "c": h'00ff',
+  "d": []
+}"#
+    );
+  }
+
+  #[test]
+  fn pretty() {
+    let config = PrettyConfig {
+      max_depth: Some(1),
+      max_bytes: Some(1),
+      bytes: BytesFormat::Base64,
+      ..PrettyConfig::default()
+    };
+    assert_eq!(
+      fixture().pretty(&config).to_string(),
+      r#"{"a": […], "c": b64'AA…', "d": []}"#
+    );
+    let cid = crate::ipld::tests::arbitrary_cid(&mut quickcheck::Gen::new(1));
+    let config = PrettyConfig { cid_base: Base::Base58Btc, ..config };
+    assert_eq!(
+      Ipld::Link(cid).pretty(&config).to_string(),
+      format!("cid'{}'", cid.to_string_of_base(Base::Base58Btc).unwrap())
+    );
+  }
+}
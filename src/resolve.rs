@@ -0,0 +1,234 @@
+use crate::{
+  block::Block,
+  codec::{
+    Error,
+    ErrorKind,
+  },
+  ipld::Ipld,
+  ipld_codec::IpldCodec,
+  path::{
+    IpldPath,
+    PathError,
+  },
+};
+
+use alloc::{
+  borrow::ToOwned,
+  collections::btree_map::BTreeMap,
+  string::String,
+  vec::Vec,
+};
+use core::fmt;
+use sp_cid::Cid;
+
+/// A source of blocks, addressed by `Cid`.
+pub trait Loader {
+  /// Returns the bytes of the block with the given `cid`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the block could not be loaded
+  fn load(&mut self, cid: &Cid) -> Result<Vec<u8>, String>;
+}
+
+impl Loader for BTreeMap<Cid, Vec<u8>> {
+  fn load(&mut self, cid: &Cid) -> Result<Vec<u8>, String> {
+    self.get(cid).cloned().ok_or_else(|| format!("Block {} not found", cid))
+  }
+}
+
+/// The value found at the end of a path, together with the blocks that were
+/// crossed to reach it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resolved {
+  /// The value at the end of the path.
+  pub value: Ipld,
+  /// The cids of the blocks that were loaded, in the order they were crossed.
+  pub links: Vec<Cid>,
+}
+
+/// An error returned when a path cannot be resolved across blocks.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResolveError {
+  /// The loader failed to return the block.
+  Load(Cid, String),
+  /// The block's codec is not supported by this build.
+  UnsupportedCodec(Cid, u64),
+  /// The block's hash function is not supported by this build.
+  UnsupportedHash(Cid, u64),
+  /// The hash of the loaded bytes does not match the cid of the block.
+  HashMismatch(Cid),
+  /// The block could not be decoded with its codec.
+  Decode(Cid, Error),
+  /// A path segment could not be followed.
+  Path(PathError),
+}
+
+impl From<PathError> for ResolveError {
+  fn from(err: PathError) -> Self { Self::Path(err) }
+}
+
+impl fmt::Display for ResolveError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::Load(cid, e) => write!(f, "Error loading block {}: {}", cid, e),
+      Self::UnsupportedCodec(cid, code) => {
+        write!(f, "Unsupported codec `0x{:x}` for block {}", code, cid)
+      }
+      Self::UnsupportedHash(cid, code) => {
+        write!(f, "Unsupported hash `0x{:x}` for block {}", code, cid)
+      }
+      Self::HashMismatch(cid) => {
+        write!(f, "Loaded bytes do not match the hash of block {}", cid)
+      }
+      Self::Decode(cid, e) => write!(f, "Error decoding block {}: {}", cid, e),
+      Self::Path(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+/// Loads the block `cid`, checks that its bytes match the hash of the cid
+/// and decodes it with the codec named in the cid.
+///
+/// # Errors
+///
+/// Will return `Err` if the block could not be loaded, its codec or hash
+/// function is not supported, its bytes do not match the cid or it could not
+/// be decoded
+pub fn load_ipld<L: Loader>(
+  loader: &mut L,
+  cid: &Cid,
+) -> Result<Ipld, ResolveError> {
+  let bytes =
+    loader.load(cid).map_err(|e| ResolveError::Load(cid.to_owned(), e))?;
+  let block = Block::<IpldCodec>::new(cid.to_owned(), bytes).map_err(|e| {
+    let cid = cid.to_owned();
+    match e.into_kind() {
+      ErrorKind::UnsupportedCodec(code) => {
+        ResolveError::UnsupportedCodec(cid, code)
+      }
+      ErrorKind::UnsupportedHash(code) => {
+        ResolveError::UnsupportedHash(cid, code)
+      }
+      ErrorKind::HashMismatch => ResolveError::HashMismatch(cid),
+      kind => ResolveError::Decode(cid, Error::new(kind)),
+    }
+  })?;
+  block.decode().map_err(|e| ResolveError::Decode(cid.to_owned(), e))
+}
+
+/// Resolves `path` starting from the block `root`, loading and decoding every
+/// block linked along the way.
+///
+/// The root block is always loaded, so the empty path resolves to the
+/// decoded root block rather than to a link to it.
+///
+/// # Errors
+///
+/// Will return `Err` if a block could not be loaded or decoded, or a segment
+/// of `path` could not be followed
+pub fn resolve<L: Loader>(
+  loader: &mut L,
+  root: &Cid,
+  path: &IpldPath,
+) -> Result<Resolved, ResolveError> {
+  let ipld = load_ipld(loader, root)?;
+  let mut resolved = resolve_ipld(loader, ipld, path)?;
+  resolved.links.insert(0, root.to_owned());
+  Ok(resolved)
+}
+
+/// Resolves `path` starting from `ipld`. Whenever an `Ipld::Link` is met
+/// before the end of the path, the linked block is loaded and decoded and the
+/// walk continues inside it. A link at the end of the path is returned as is.
+///
+/// # Errors
+///
+/// Will return `Err` if a block could not be loaded or decoded, or a segment
+/// of `path` could not be followed
+pub fn resolve_ipld<L: Loader>(
+  loader: &mut L,
+  mut ipld: Ipld,
+  path: &IpldPath,
+) -> Result<Resolved, ResolveError> {
+  let mut links = Vec::new();
+  for (i, segment) in path.segments().iter().enumerate() {
+    while let Ipld::Link(cid) = ipld {
+      ipld = load_ipld(loader, &cid)?;
+      links.push(cid);
+    }
+    ipld = ipld
+      .take_segment(segment)
+      .map_err(|kind| PathError::new(path, i, kind))?;
+  }
+  Ok(Resolved { value: ipld, links })
+}
+
+#[cfg(all(test, feature = "dag-cbor"))]
+pub mod tests {
+  use super::*;
+  use crate::{
+    codec::Codec,
+    dag_cbor::{
+      self,
+      DagCborCodec,
+    },
+    path::PathErrorKind,
+  };
+
+  fn put(blocks: &mut BTreeMap<Cid, Vec<u8>>, ipld: &Ipld) -> Cid {
+    let cid = dag_cbor::cid(ipld);
+    let bytes = DagCborCodec.encode(ipld).unwrap().into_inner();
+    blocks.insert(cid.to_owned(), bytes);
+    cid
+  }
+
+  fn map(key: &str, value: Ipld) -> Ipld {
+    let mut map = BTreeMap::new();
+    map.insert(key.to_owned(), value);
+    Ipld::StringMap(map)
+  }
+
+  #[test]
+  fn resolve_across_links() {
+    let mut blocks = BTreeMap::new();
+    let leaf = put(&mut blocks, &map("c", Ipld::Integer(42)));
+    let middle =
+      put(&mut blocks, &Ipld::List(vec![Ipld::Link(leaf.to_owned())]));
+    let root = put(&mut blocks, &map("a", Ipld::Link(middle.to_owned())));
+
+    let resolved = resolve(&mut blocks, &root, &"a/0/c".into()).unwrap();
+    assert_eq!(resolved.value, Ipld::Integer(42));
+    let links = vec![root.to_owned(), middle.to_owned(), leaf.to_owned()];
+    assert_eq!(resolved.links, links);
+
+    let resolved = resolve(&mut blocks, &root, &"a/0".into()).unwrap();
+    assert_eq!(resolved.value, Ipld::Link(leaf));
+
+    let resolved = resolve(&mut blocks, &root, &IpldPath::new()).unwrap();
+    assert_eq!(resolved.value, map("a", Ipld::Link(middle.to_owned())));
+    assert_eq!(resolved.links, vec![root]);
+  }
+
+  #[test]
+  fn resolve_errors() {
+    let mut blocks = BTreeMap::new();
+    let missing = dag_cbor::cid(&Ipld::Null);
+    let root = put(&mut blocks, &map("a", Ipld::Link(missing.to_owned())));
+    assert!(matches!(
+      resolve(&mut blocks, &root, &"a/b".into()),
+      Err(ResolveError::Load(cid, _)) if cid == missing
+    ));
+    match resolve(&mut blocks, &root, &"b".into()) {
+      Err(ResolveError::Path(e)) => {
+        assert_eq!(e.kind, PathErrorKind::MissingKey)
+      }
+      other => panic!("Unexpected result {:?}", other),
+    }
+    blocks.insert(missing.to_owned(), blocks[&root].clone());
+    assert_eq!(
+      resolve(&mut blocks, &root, &"a/b".into()),
+      Err(ResolveError::HashMismatch(missing))
+    );
+  }
+}
@@ -0,0 +1,289 @@
+//! Serde support for converting Rust types into `Ipld` values and back.
+//!
+//! Links are represented as a newtype struct with a reserved name wrapping the
+//! CID bytes, and byte buffers as serde bytes. Fields of type `Cid` are mapped
+//! to `Ipld::Link`, as are fields using
+//! `#[serde(with = "sp_ipld::serde::cid")]` to write the newtype struct to any
+//! serializer.
+//!
+//! `Ipld` implements `Serialize` in the form `to_ipld` turns back into the
+//! same value, so `from_ipld(to_ipld(&ipld)?)` is `ipld` again. The dag-json
+//! form, with bytes and links as maps, is only written by `DagJsonCodec`.
+
+use crate::ipld::Ipld;
+use alloc::string::{
+  String,
+  ToString,
+};
+use core::fmt;
+use serde::{
+  de::DeserializeOwned,
+  Serialize,
+};
+
+mod de;
+mod ser;
+
+pub use self::ser::Serializer;
+
+/// The newtype struct name used to mark the bytes of a `Cid`.
+pub const CID_SERDE_NAME: &str = "$__sp_ipld_cid";
+
+/// The struct name `Cid` itself serializes as, with the fields its
+/// `Serialize` impl writes.
+const CID_STRUCT_NAME: &str = "Cid";
+
+/// An error raised while converting between a Rust type and `Ipld`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SerdeError(pub String);
+
+impl SerdeError {
+  fn map_key(key: &Ipld) -> Self {
+    Self(format!("Map keys must be strings, found {}", key.kind()))
+  }
+}
+
+impl fmt::Display for SerdeError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl serde::ser::StdError for SerdeError {}
+
+impl serde::ser::Error for SerdeError {
+  fn custom<T: fmt::Display>(msg: T) -> Self { Self(msg.to_string()) }
+}
+
+impl serde::de::Error for SerdeError {
+  fn custom<T: fmt::Display>(msg: T) -> Self { Self(msg.to_string()) }
+}
+
+/// Converts any serializable value into `Ipld`.
+///
+/// # Errors
+///
+/// Will return `Err` if the value has map keys that are not strings or an
+/// integer outside the range of `i128`
+pub fn to_ipld<T: Serialize>(value: T) -> Result<Ipld, SerdeError> {
+  value.serialize(Serializer)
+}
+
+/// Converts `Ipld` into any deserializable value.
+///
+/// # Errors
+///
+/// Will return `Err` if the shape of `ipld` does not match `T`
+pub fn from_ipld<T: DeserializeOwned>(ipld: Ipld) -> Result<T, SerdeError> {
+  T::deserialize(ipld)
+}
+
+/// Serializes and deserializes a `Cid` as an `Ipld::Link`, for use with
+/// `#[serde(with = "sp_ipld::serde::cid")]`.
+pub mod cid {
+  use super::CID_SERDE_NAME;
+  use alloc::vec::Vec;
+  use core::{
+    convert::TryFrom,
+    fmt,
+  };
+  use serde::{
+    de,
+    Deserializer,
+    Serializer,
+  };
+  use sp_cid::Cid;
+
+  struct CidBytes(Vec<u8>);
+
+  impl serde::Serialize for CidBytes {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+      s.serialize_bytes(&self.0)
+    }
+  }
+
+  struct CidVisitor;
+
+  impl<'de> de::Visitor<'de> for CidVisitor {
+    type Value = Cid;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+      fmt.write_str("a link")
+    }
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Cid, E>
+    where E: de::Error {
+      Cid::try_from(v).map_err(E::custom)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Cid, D::Error>
+    where D: Deserializer<'de> {
+      deserializer.deserialize_bytes(self)
+    }
+  }
+
+  /// Serializes `cid` as a link.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the serializer fails
+  pub fn serialize<S: Serializer>(cid: &Cid, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_newtype_struct(CID_SERDE_NAME, &CidBytes(cid.to_bytes()))
+  }
+
+  /// Deserializes a link into a `Cid`.
+  ///
+  /// # Errors
+  ///
+  /// Will return `Err` if the value is not a link
+  pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Cid, D::Error> {
+    d.deserialize_newtype_struct(CID_SERDE_NAME, CidVisitor)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use super::*;
+  use alloc::collections::btree_map::BTreeMap;
+  #[cfg(feature = "dag-cbor")]
+  use alloc::vec::Vec;
+  #[cfg(feature = "dag-cbor")]
+  use serde::Deserialize;
+  #[cfg(feature = "dag-cbor")]
+  use sp_cid::Cid;
+
+  #[cfg(feature = "dag-cbor")]
+  #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+  struct Post {
+    title: String,
+    parent: Cid,
+    tags: Vec<String>,
+    score: Option<u32>,
+    kind: Kind,
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+  enum Kind {
+    Draft,
+    Published { at: u64 },
+    Pair(i8, bool),
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  fn post(kind: Kind) -> Post {
+    Post {
+      title: "hello".to_owned(),
+      parent: crate::dag_cbor::cid(&Ipld::Null),
+      tags: vec!["a".to_owned()],
+      score: None,
+      kind,
+    }
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  #[test]
+  fn struct_roundtrip() {
+    let kinds =
+      vec![Kind::Draft, Kind::Published { at: 7 }, Kind::Pair(-1, true)];
+    for kind in kinds {
+      let value = post(kind);
+      let ipld = to_ipld(&value).unwrap();
+      assert_eq!(from_ipld::<Post>(ipld), Ok(value));
+    }
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  #[test]
+  fn struct_to_ipld() {
+    let value = post(Kind::Published { at: 7 });
+    let mut kind = BTreeMap::new();
+    kind.insert("at".to_owned(), Ipld::Integer(7));
+    let mut published = BTreeMap::new();
+    published.insert("Published".to_owned(), Ipld::StringMap(kind));
+    let mut map = BTreeMap::new();
+    map.insert("title".to_owned(), Ipld::String("hello".to_owned()));
+    map.insert("parent".to_owned(), Ipld::Link(value.parent.to_owned()));
+    map.insert("tags".to_owned(), Ipld::List(vec![Ipld::String("a".into())]));
+    map.insert("score".to_owned(), Ipld::Null);
+    map.insert("kind".to_owned(), Ipld::StringMap(published));
+    assert_eq!(to_ipld(&value), Ok(Ipld::StringMap(map)));
+  }
+
+  #[quickcheck]
+  fn ipld_roundtrip(x: Ipld) -> bool {
+    to_ipld(&x).and_then(from_ipld) == Ok(x)
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  #[test]
+  fn ipld_field() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Node {
+      data: Ipld,
+      next: Ipld,
+      #[serde(with = "cid")]
+      root: Cid,
+    }
+    let root = crate::dag_cbor::cid(&Ipld::Null);
+    let node = Node {
+      data: Ipld::Bytes(vec![1, 2]),
+      next: Ipld::Link(root),
+      root,
+    };
+    let ipld = to_ipld(&node).unwrap();
+    assert_eq!(ipld["data"], node.data);
+    assert_eq!(ipld["next"], node.next);
+    assert_eq!(ipld["root"], Ipld::Link(root));
+    assert_eq!(from_ipld(ipld), Ok(node));
+  }
+
+  #[cfg(feature = "dag-cbor")]
+  #[test]
+  fn cid_as_link() {
+    let cid = crate::dag_cbor::cid(&Ipld::Null);
+    assert_eq!(to_ipld(cid), Ok(Ipld::Link(cid)));
+    assert_eq!(from_ipld(Ipld::Link(cid)), Ok(cid));
+    assert!(from_ipld::<Cid>(Ipld::Bytes(cid.to_bytes())).is_err());
+    // Only the fields of a valid `Cid` are turned into a link.
+    #[derive(Serialize)]
+    #[serde(rename = "Cid")]
+    struct Other {
+      version: u8,
+    }
+    let mut map = BTreeMap::new();
+    map.insert("version".to_owned(), Ipld::Integer(1));
+    assert_eq!(to_ipld(Other { version: 1 }), Ok(Ipld::StringMap(map)));
+  }
+
+  #[cfg(feature = "dag-json")]
+  #[test]
+  fn ipld_dag_json_form() {
+    use crate::codec::Codec;
+    let cid = crate::dag_json::cid(&Ipld::Null);
+    let ipld = Ipld::List(vec![Ipld::Bytes(vec![1]), Ipld::Link(cid)]);
+    let json = crate::dag_json::DagJsonCodec.encode(&ipld).unwrap();
+    let expected = format!(
+      r#"[{{"/":{{"bytes":"AQ=="}}}},{{"/":"{}"}}]"#,
+      base64::encode(cid.to_bytes())
+    );
+    assert_eq!(json.into_inner(), expected.into_bytes());
+    assert_eq!(to_ipld(&ipld), Ok(ipld));
+  }
+
+  #[test]
+  fn newtype_struct() {
+    use serde::de::Visitor;
+    let ipld = Ipld::String("a".to_owned());
+    assert_eq!(de::IpldVisitor.visit_newtype_struct(ipld.clone()), Ok(ipld));
+  }
+
+  #[test]
+  fn errors() {
+    let mut map = BTreeMap::new();
+    map.insert(true, 1u8);
+    assert!(to_ipld(&map).is_err());
+    assert!(from_ipld::<u8>(Ipld::Integer(256)).is_err());
+    assert!(from_ipld::<String>(Ipld::Null).is_err());
+  }
+}
@@ -0,0 +1,398 @@
+use crate::{
+  ipld::Ipld,
+  serde::{
+    SerdeError,
+    Serializer,
+    CID_SERDE_NAME,
+    CID_STRUCT_NAME,
+  },
+};
+use alloc::{
+  borrow::ToOwned,
+  collections::btree_map::BTreeMap,
+  string::String,
+  vec::Vec,
+};
+use core::{
+  convert::TryFrom,
+  fmt,
+  iter,
+};
+use serde::{
+  de::{
+    self,
+    IntoDeserializer,
+    Visitor,
+  },
+  forward_to_deserialize_any,
+  Deserialize,
+  Serialize,
+};
+use sp_cid::Cid;
+
+impl<'de> Deserialize<'de> for Ipld {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where D: de::Deserializer<'de> {
+    deserializer.deserialize_any(IpldVisitor)
+  }
+}
+
+pub(super) struct IpldVisitor;
+
+impl<'de> Visitor<'de> for IpldVisitor {
+  type Value = Ipld;
+
+  fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fmt.write_str("any valid IPLD value")
+  }
+
+  fn visit_bool<E>(self, v: bool) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Bool(v))
+  }
+
+  fn visit_i64<E>(self, v: i64) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn visit_i128<E>(self, v: i128) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Integer(v))
+  }
+
+  fn visit_u64<E>(self, v: u64) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn visit_f64<E>(self, v: f64) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Float(v))
+  }
+
+  fn visit_str<E>(self, v: &str) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::String(v.to_owned()))
+  }
+
+  fn visit_string<E>(self, v: String) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::String(v))
+  }
+
+  fn visit_bytes<E>(self, v: &[u8]) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Bytes(v.to_vec()))
+  }
+
+  fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Bytes(v))
+  }
+
+  fn visit_none<E>(self) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Null)
+  }
+
+  fn visit_some<D>(self, deserializer: D) -> Result<Ipld, D::Error>
+  where D: de::Deserializer<'de> {
+    Ipld::deserialize(deserializer)
+  }
+
+  fn visit_unit<E>(self) -> Result<Ipld, E>
+  where E: de::Error {
+    Ok(Ipld::Null)
+  }
+
+  fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Ipld, D::Error>
+  where D: de::Deserializer<'de> {
+    // Only the inner value of a link is a map holding the cid bytes under
+    // the reserved name, any other newtype struct stands for its inner value.
+    match Ipld::deserialize(deserializer)? {
+      Ipld::StringMap(mut map) if map.len() == 1 => {
+        match map.remove(CID_SERDE_NAME) {
+          Some(Ipld::Bytes(bytes)) => {
+            Cid::try_from(bytes).map(Ipld::Link).map_err(de::Error::custom)
+          }
+          Some(ipld) => {
+            map.insert(CID_SERDE_NAME.to_owned(), ipld);
+            Ok(Ipld::StringMap(map))
+          }
+          None => Ok(Ipld::StringMap(map)),
+        }
+      }
+      ipld => Ok(ipld),
+    }
+  }
+
+  fn visit_seq<A>(self, mut seq: A) -> Result<Ipld, A::Error>
+  where A: de::SeqAccess<'de> {
+    let mut list = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+    while let Some(elem) = seq.next_element()? {
+      list.push(elem);
+    }
+    Ok(Ipld::List(list))
+  }
+
+  fn visit_map<A>(self, mut map: A) -> Result<Ipld, A::Error>
+  where A: de::MapAccess<'de> {
+    let mut entries: Vec<(Ipld, Ipld)> = Vec::new();
+    while let Some(entry) = map.next_entry()? {
+      entries.push(entry);
+    }
+    #[cfg(feature = "unleashed")]
+    {
+      if let Some((Ipld::Integer(_), _)) = entries.first() {
+        let mut integer_map = BTreeMap::new();
+        for (key, value) in entries {
+          let key = match key {
+            Ipld::Integer(i) => i64::try_from(i).ok(),
+            _ => None,
+          }
+          .ok_or_else(|| de::Error::custom("Expected integer map key"))?;
+          integer_map.insert(key, value);
+        }
+        return Ok(Ipld::IntegerMap(integer_map));
+      }
+    }
+    let mut string_map = BTreeMap::new();
+    for (key, value) in entries {
+      match key {
+        Ipld::String(key) => {
+          string_map.insert(key, value);
+        }
+        _ => return Err(de::Error::custom("Expected string map key")),
+      }
+    }
+    Ok(Ipld::StringMap(string_map))
+  }
+}
+
+/// Deserializes the inner value of the newtype struct a link is visited as.
+/// It is the raw bytes of the cid when bytes are expected, and otherwise a
+/// map holding them under the reserved name, which `IpldVisitor` tells apart
+/// from the inner value of any other newtype struct.
+struct CidDeserializer(Cid);
+
+impl<'de> de::Deserializer<'de> for CidDeserializer {
+  type Error = SerdeError;
+
+  fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+  where V: Visitor<'de> {
+    let entry = (CID_SERDE_NAME, Ipld::Bytes(self.0.to_bytes()));
+    visitor.visit_map(de::value::MapDeserializer::new(iter::once(entry)))
+  }
+
+  fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+  where V: Visitor<'de> {
+    visitor.visit_byte_buf(self.0.to_bytes())
+  }
+
+  fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+  where V: Visitor<'de> {
+    visitor.visit_byte_buf(self.0.to_bytes())
+  }
+
+  forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    option unit unit_struct newtype_struct seq tuple tuple_struct map struct
+    enum identifier ignored_any
+  }
+}
+
+impl<'de> IntoDeserializer<'de, SerdeError> for Ipld {
+  type Deserializer = Self;
+
+  fn into_deserializer(self) -> Self { self }
+}
+
+impl<'de> de::Deserializer<'de> for Ipld {
+  type Error = SerdeError;
+
+  fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+  where V: Visitor<'de> {
+    match self {
+      Self::Null => visitor.visit_unit(),
+      Self::Bool(b) => visitor.visit_bool(b),
+      Self::Integer(i) => match (i64::try_from(i), u64::try_from(i)) {
+        (Ok(i), _) => visitor.visit_i64(i),
+        (_, Ok(u)) => visitor.visit_u64(u),
+        _ => visitor.visit_i128(i),
+      },
+      Self::Float(f) => visitor.visit_f64(f),
+      Self::String(s) => visitor.visit_string(s),
+      Self::Bytes(b) => visitor.visit_byte_buf(b),
+      Self::List(l) => visitor.visit_seq(l.into_deserializer()),
+      Self::StringMap(m) => visitor.visit_map(m.into_deserializer()),
+      Self::Link(cid) => visitor.visit_newtype_struct(CidDeserializer(cid)),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(m) => visitor.visit_map(
+        de::value::MapDeserializer::new(
+          m.into_iter().map(|(k, v)| (Self::Integer(k.into()), v)),
+        ),
+      ),
+      #[cfg(feature = "unleashed")]
+      Self::Tag(..) => {
+        Err(de::Error::custom("Tags cannot be represented in serde"))
+      }
+    }
+  }
+
+  fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, SerdeError>
+  where V: Visitor<'de> {
+    match self {
+      Self::Null => visitor.visit_none(),
+      ipld => visitor.visit_some(ipld),
+    }
+  }
+
+  fn deserialize_newtype_struct<V>(
+    self,
+    name: &'static str,
+    visitor: V,
+  ) -> Result<V::Value, SerdeError>
+  where
+    V: Visitor<'de>,
+  {
+    match self {
+      Self::Link(cid) if name == CID_SERDE_NAME => {
+        visitor.visit_byte_buf(cid.to_bytes())
+      }
+      ipld if name == CID_SERDE_NAME => Err(SerdeError(format!(
+        "Expected link, found {}",
+        ipld.kind()
+      ))),
+      ipld => visitor.visit_newtype_struct(ipld),
+    }
+  }
+
+  fn deserialize_struct<V>(
+    self,
+    name: &'static str,
+    _fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, SerdeError>
+  where
+    V: Visitor<'de>,
+  {
+    match self {
+      // A link deserialized as a `Cid` is visited as the fields `Cid`
+      // serializes as.
+      Self::Link(cid) if name == CID_STRUCT_NAME => {
+        let mut map = BTreeMap::new();
+        map.insert("version", cid.version().serialize(Serializer)?);
+        map.insert("codec", Self::Integer(cid.codec().into()));
+        map.insert("hash", cid.hash().serialize(Serializer)?);
+        visitor.visit_map(map.into_deserializer())
+      }
+      ipld => ipld.deserialize_any(visitor),
+    }
+  }
+
+  fn deserialize_enum<V>(
+    self,
+    _name: &'static str,
+    _variants: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, SerdeError>
+  where
+    V: Visitor<'de>,
+  {
+    match self {
+      Self::String(variant) => {
+        visitor.visit_enum(EnumDeserializer { variant, value: None })
+      }
+      Self::StringMap(map) if map.len() == 1 => {
+        let (variant, value) = map.into_iter().next().unwrap();
+        visitor.visit_enum(EnumDeserializer { variant, value: Some(value) })
+      }
+      ipld => Err(SerdeError(format!(
+        "Expected string or single entry map for enum, found {}",
+        ipld.kind()
+      ))),
+    }
+  }
+
+  forward_to_deserialize_any! {
+    bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+    bytes byte_buf unit unit_struct seq tuple tuple_struct map identifier
+    ignored_any
+  }
+}
+
+/// Deserializes an enum encoded either as the variant name or as a single
+/// entry map from the variant name to its fields.
+struct EnumDeserializer {
+  variant: String,
+  value: Option<Ipld>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+  type Error = SerdeError;
+  type Variant = VariantDeserializer;
+
+  fn variant_seed<V>(
+    self,
+    seed: V,
+  ) -> Result<(V::Value, VariantDeserializer), SerdeError>
+  where
+    V: de::DeserializeSeed<'de>,
+  {
+    let variant = seed.deserialize(Ipld::String(self.variant))?;
+    Ok((variant, VariantDeserializer(self.value)))
+  }
+}
+
+struct VariantDeserializer(Option<Ipld>);
+
+impl<'de> de::VariantAccess<'de> for VariantDeserializer {
+  type Error = SerdeError;
+
+  fn unit_variant(self) -> Result<(), SerdeError> {
+    match self.0 {
+      None | Some(Ipld::Null) => Ok(()),
+      Some(ipld) => Err(SerdeError(format!(
+        "Expected unit variant, found {}",
+        ipld.kind()
+      ))),
+    }
+  }
+
+  fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, SerdeError>
+  where T: de::DeserializeSeed<'de> {
+    match self.0 {
+      Some(ipld) => seed.deserialize(ipld),
+      None => Err(SerdeError("Expected newtype variant".to_owned())),
+    }
+  }
+
+  fn tuple_variant<V>(
+    self,
+    _len: usize,
+    visitor: V,
+  ) -> Result<V::Value, SerdeError>
+  where
+    V: Visitor<'de>,
+  {
+    match self.0 {
+      Some(Ipld::List(l)) => visitor.visit_seq(l.into_deserializer()),
+      _ => Err(SerdeError("Expected tuple variant".to_owned())),
+    }
+  }
+
+  fn struct_variant<V>(
+    self,
+    _fields: &'static [&'static str],
+    visitor: V,
+  ) -> Result<V::Value, SerdeError>
+  where
+    V: Visitor<'de>,
+  {
+    match self.0 {
+      Some(Ipld::StringMap(m)) => visitor.visit_map(m.into_deserializer()),
+      _ => Err(SerdeError("Expected struct variant".to_owned())),
+    }
+  }
+}
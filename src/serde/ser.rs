@@ -0,0 +1,446 @@
+use crate::{
+  ipld::Ipld,
+  serde::{
+    SerdeError,
+    CID_SERDE_NAME,
+    CID_STRUCT_NAME,
+  },
+};
+use alloc::{
+  borrow::ToOwned,
+  collections::btree_map::BTreeMap,
+  string::{
+    String,
+    ToString,
+  },
+  vec::Vec,
+};
+use core::convert::TryFrom;
+use serde::{
+  ser::{
+    self,
+    Serialize,
+  },
+  Deserialize,
+};
+use sp_cid::Cid;
+
+/// Serializes a value in the form the `Serializer` of this module turns back
+/// into the same value, with links as `cid::serialize` writes them.
+impl Serialize for Ipld {
+  fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+  where S: ser::Serializer {
+    match self {
+      Self::Null => serializer.serialize_none(),
+      Self::Bool(b) => serializer.serialize_bool(*b),
+      Self::Integer(i) => serializer.serialize_i128(*i),
+      Self::Float(f) => serializer.serialize_f64(*f),
+      Self::String(s) => serializer.serialize_str(s),
+      Self::Bytes(b) => serializer.serialize_bytes(b),
+      Self::List(l) => serializer.collect_seq(l),
+      Self::StringMap(m) => serializer.collect_map(m),
+      Self::Link(cid) => crate::serde::cid::serialize(cid, serializer),
+      #[cfg(feature = "unleashed")]
+      Self::IntegerMap(m) => serializer.collect_map(m),
+      #[cfg(feature = "unleashed")]
+      Self::Tag(..) => {
+        Err(ser::Error::custom("Tags cannot be represented in serde"))
+      }
+    }
+  }
+}
+
+/// A serde `Serializer` producing `Ipld` values.
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+  type Error = SerdeError;
+  type Ok = Ipld;
+  type SerializeMap = SerializeMap;
+  type SerializeSeq = SerializeSeq;
+  type SerializeStruct = SerializeMap;
+  type SerializeStructVariant = SerializeVariant<SerializeMap>;
+  type SerializeTuple = SerializeSeq;
+  type SerializeTupleStruct = SerializeSeq;
+  type SerializeTupleVariant = SerializeVariant<SerializeSeq>;
+
+  fn serialize_bool(self, v: bool) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Bool(v))
+  }
+
+  fn serialize_i8(self, v: i8) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn serialize_i16(self, v: i16) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn serialize_i32(self, v: i32) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn serialize_i64(self, v: i64) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn serialize_i128(self, v: i128) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v))
+  }
+
+  fn serialize_u8(self, v: u8) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn serialize_u16(self, v: u16) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn serialize_u32(self, v: u32) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn serialize_u64(self, v: u64) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Integer(v.into()))
+  }
+
+  fn serialize_u128(self, v: u128) -> Result<Ipld, SerdeError> {
+    i128::try_from(v)
+      .map(Ipld::Integer)
+      .map_err(|_| SerdeError(format!("Integer {} out of range for i128", v)))
+  }
+
+  fn serialize_f32(self, v: f32) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Float(v.into()))
+  }
+
+  fn serialize_f64(self, v: f64) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Float(v))
+  }
+
+  fn serialize_char(self, v: char) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::String(v.to_string()))
+  }
+
+  fn serialize_str(self, v: &str) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::String(v.to_owned()))
+  }
+
+  fn serialize_bytes(self, v: &[u8]) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Bytes(v.to_vec()))
+  }
+
+  fn serialize_none(self) -> Result<Ipld, SerdeError> { Ok(Ipld::Null) }
+
+  fn serialize_some<T>(self, value: &T) -> Result<Ipld, SerdeError>
+  where T: ?Sized + Serialize {
+    value.serialize(self)
+  }
+
+  fn serialize_unit(self) -> Result<Ipld, SerdeError> { Ok(Ipld::Null) }
+
+  fn serialize_unit_struct(
+    self,
+    _name: &'static str,
+  ) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::Null)
+  }
+
+  fn serialize_unit_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+  ) -> Result<Ipld, SerdeError> {
+    Ok(Ipld::String(variant.to_owned()))
+  }
+
+  fn serialize_newtype_struct<T>(
+    self,
+    name: &'static str,
+    value: &T,
+  ) -> Result<Ipld, SerdeError>
+  where
+    T: ?Sized + Serialize,
+  {
+    let ipld = value.serialize(self)?;
+    if name != CID_SERDE_NAME {
+      return Ok(ipld);
+    }
+    match ipld {
+      Ipld::Bytes(bytes) => Cid::try_from(bytes)
+        .map(Ipld::Link)
+        .map_err(|e| SerdeError(e.to_string())),
+      _ => Err(SerdeError("Expected the bytes of a Cid".to_owned())),
+    }
+  }
+
+  fn serialize_newtype_variant<T>(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    value: &T,
+  ) -> Result<Ipld, SerdeError>
+  where
+    T: ?Sized + Serialize,
+  {
+    let mut map = BTreeMap::new();
+    map.insert(variant.to_owned(), value.serialize(self)?);
+    Ok(Ipld::StringMap(map))
+  }
+
+  fn serialize_seq(
+    self,
+    len: Option<usize>,
+  ) -> Result<SerializeSeq, SerdeError> {
+    Ok(SerializeSeq(Vec::with_capacity(len.unwrap_or(0))))
+  }
+
+  fn serialize_tuple(self, len: usize) -> Result<SerializeSeq, SerdeError> {
+    self.serialize_seq(Some(len))
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    _name: &'static str,
+    len: usize,
+  ) -> Result<SerializeSeq, SerdeError> {
+    self.serialize_seq(Some(len))
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    len: usize,
+  ) -> Result<SerializeVariant<SerializeSeq>, SerdeError> {
+    Ok(SerializeVariant { variant, inner: self.serialize_seq(Some(len))? })
+  }
+
+  fn serialize_map(
+    self,
+    _len: Option<usize>,
+  ) -> Result<SerializeMap, SerdeError> {
+    Ok(SerializeMap::default())
+  }
+
+  fn serialize_struct(
+    self,
+    name: &'static str,
+    len: usize,
+  ) -> Result<SerializeMap, SerdeError> {
+    let map = self.serialize_map(Some(len))?;
+    Ok(SerializeMap { cid: name == CID_STRUCT_NAME, ..map })
+  }
+
+  fn serialize_struct_variant(
+    self,
+    _name: &'static str,
+    _variant_index: u32,
+    variant: &'static str,
+    len: usize,
+  ) -> Result<SerializeVariant<SerializeMap>, SerdeError> {
+    Ok(SerializeVariant { variant, inner: self.serialize_map(Some(len))? })
+  }
+
+  fn is_human_readable(&self) -> bool { false }
+}
+
+#[doc(hidden)]
+pub struct SerializeSeq(Vec<Ipld>);
+
+impl ser::SerializeSeq for SerializeSeq {
+  type Error = SerdeError;
+  type Ok = Ipld;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerdeError>
+  where T: ?Sized + Serialize {
+    self.0.push(value.serialize(Serializer)?);
+    Ok(())
+  }
+
+  fn end(self) -> Result<Ipld, SerdeError> { Ok(Ipld::List(self.0)) }
+}
+
+impl ser::SerializeTuple for SerializeSeq {
+  type Error = SerdeError;
+  type Ok = Ipld;
+
+  fn serialize_element<T>(&mut self, value: &T) -> Result<(), SerdeError>
+  where T: ?Sized + Serialize {
+    ser::SerializeSeq::serialize_element(self, value)
+  }
+
+  fn end(self) -> Result<Ipld, SerdeError> { ser::SerializeSeq::end(self) }
+}
+
+impl ser::SerializeTupleStruct for SerializeSeq {
+  type Error = SerdeError;
+  type Ok = Ipld;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerdeError>
+  where T: ?Sized + Serialize {
+    ser::SerializeSeq::serialize_element(self, value)
+  }
+
+  fn end(self) -> Result<Ipld, SerdeError> { ser::SerializeSeq::end(self) }
+}
+
+/// Collects map entries. Keys must all be strings, or with the `unleashed`
+/// feature all be integers.
+#[doc(hidden)]
+#[derive(Default)]
+pub struct SerializeMap {
+  entries: Vec<(Ipld, Ipld)>,
+  next_key: Option<Ipld>,
+  /// Whether the entries are the fields of a struct named like `Cid`.
+  cid: bool,
+}
+
+impl SerializeMap {
+  fn into_ipld(self) -> Result<Ipld, SerdeError> {
+    #[cfg(feature = "unleashed")]
+    {
+      if let Some((Ipld::Integer(_), _)) = self.entries.first() {
+        let mut map = BTreeMap::new();
+        for (key, value) in self.entries {
+          match key {
+            Ipld::Integer(i) => match i64::try_from(i) {
+              Ok(i) => {
+                map.insert(i, value);
+              }
+              Err(_) => return Err(SerdeError::map_key(&Ipld::Integer(i))),
+            },
+            key => return Err(SerdeError::map_key(&key)),
+          }
+        }
+        return Ok(Ipld::IntegerMap(map));
+      }
+    }
+    let mut map = BTreeMap::new();
+    for (key, value) in self.entries {
+      match key {
+        Ipld::String(s) => {
+          map.insert(s, value);
+        }
+        key => return Err(SerdeError::map_key(&key)),
+      }
+    }
+    Ok(Ipld::StringMap(map))
+  }
+}
+
+impl ser::SerializeMap for SerializeMap {
+  type Error = SerdeError;
+  type Ok = Ipld;
+
+  fn serialize_key<T>(&mut self, key: &T) -> Result<(), SerdeError>
+  where T: ?Sized + Serialize {
+    self.next_key = Some(key.serialize(Serializer)?);
+    Ok(())
+  }
+
+  fn serialize_value<T>(&mut self, value: &T) -> Result<(), SerdeError>
+  where T: ?Sized + Serialize {
+    let key = self
+      .next_key
+      .take()
+      .ok_or_else(|| SerdeError("Map value without a key".to_owned()))?;
+    self.entries.push((key, value.serialize(Serializer)?));
+    Ok(())
+  }
+
+  fn end(self) -> Result<Ipld, SerdeError> { self.into_ipld() }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+  type Error = SerdeError;
+  type Ok = Ipld;
+
+  fn serialize_field<T>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), SerdeError>
+  where
+    T: ?Sized + Serialize,
+  {
+    ser::SerializeMap::serialize_entry(self, key, value)
+  }
+
+  fn end(self) -> Result<Ipld, SerdeError> {
+    let cid = self.cid;
+    match self.into_ipld()? {
+      Ipld::StringMap(fields) if cid => link(fields),
+      ipld => Ok(ipld),
+    }
+  }
+}
+
+/// Turns the fields `Cid` serializes as back into a link. The fields of any
+/// other struct of the same name are kept as a map.
+///
+/// # Errors
+///
+/// Will return `Err` if the fields are those of an invalid `Cid`
+fn link(fields: BTreeMap<String, Ipld>) -> Result<Ipld, SerdeError> {
+  let fields = Ipld::StringMap(fields);
+  match Cid::deserialize(fields.clone()) {
+    Ok(cid) => Cid::new(cid.version(), cid.codec(), *cid.hash())
+      .map(Ipld::Link)
+      .map_err(|e| SerdeError(e.to_string())),
+    Err(_) => Ok(fields),
+  }
+}
+
+/// Wraps the serialized fields of an enum variant in a single entry map keyed
+/// by the variant name.
+#[doc(hidden)]
+pub struct SerializeVariant<T> {
+  variant: &'static str,
+  inner: T,
+}
+
+impl<T> SerializeVariant<T> {
+  fn wrap(variant: &'static str, ipld: Ipld) -> Ipld {
+    let mut map = BTreeMap::new();
+    map.insert(variant.to_owned(), ipld);
+    Ipld::StringMap(map)
+  }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeSeq> {
+  type Error = SerdeError;
+  type Ok = Ipld;
+
+  fn serialize_field<T>(&mut self, value: &T) -> Result<(), SerdeError>
+  where T: ?Sized + Serialize {
+    ser::SerializeSeq::serialize_element(&mut self.inner, value)
+  }
+
+  fn end(self) -> Result<Ipld, SerdeError> {
+    Ok(Self::wrap(self.variant, ser::SerializeSeq::end(self.inner)?))
+  }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+  type Error = SerdeError;
+  type Ok = Ipld;
+
+  fn serialize_field<T>(
+    &mut self,
+    key: &'static str,
+    value: &T,
+  ) -> Result<(), SerdeError>
+  where
+    T: ?Sized + Serialize,
+  {
+    ser::SerializeMap::serialize_entry(&mut self.inner, key, value)
+  }
+
+  fn end(self) -> Result<Ipld, SerdeError> {
+    Ok(Self::wrap(self.variant, self.inner.into_ipld()?))
+  }
+}